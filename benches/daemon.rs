@@ -0,0 +1,69 @@
+//! Compares per-operation latency of opening the database for every call (what
+//! each CLI invocation does) against sending the call to a running daemon.
+//!
+//! Run with `cargo bench --bench daemon`.
+
+use douglance_kv::daemon::{self, Client};
+use douglance_kv::{Database, GetOptions, KvStore, SetOptions};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 500;
+
+fn main() {
+    let dir = std::env::temp_dir().join(format!("kv-bench-daemon-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let db_path = dir.join("kv.db");
+    let socket = dir.join("kv.sock");
+
+    Database::open_at(&db_path)
+        .unwrap()
+        .set("bench", b"value", &SetOptions::default())
+        .unwrap();
+
+    let direct = time(|| {
+        let db = Database::open_at(&db_path).unwrap();
+        db.get("bench", &GetOptions::default()).unwrap();
+    });
+
+    {
+        let db = Database::open_at(&db_path).unwrap();
+        let socket = socket.clone();
+        thread::spawn(move || daemon::serve(db, &socket));
+    }
+    wait_for(&socket);
+
+    let via_daemon = time(|| {
+        let mut client = Client::connect_to(&socket).unwrap();
+        client.get("bench", &GetOptions::default()).unwrap();
+    });
+
+    println!("get, open per call:   {:>8.1} µs/op", micros(direct));
+    println!("get, via daemon:      {:>8.1} µs/op", micros(via_daemon));
+    println!("speedup:              {:>8.1}x", direct.as_secs_f64() / via_daemon.as_secs_f64());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+fn time<F: FnMut()>(mut op: F) -> Duration {
+    // Warm up caches before measuring
+    for _ in 0..10 {
+        op();
+    }
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        op();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn micros(d: Duration) -> f64 {
+    d.as_secs_f64() * 1_000_000.0
+}
+
+fn wait_for(socket: &Path) {
+    while Client::connect_to(socket).is_err() {
+        thread::sleep(Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,70 @@
+//! Times garbage collection on a store with a million entries: 100,000 keys
+//! with ten versions each, some of them expired or soft-deleted.
+//!
+//! Run with `cargo bench --bench gc`.
+
+use chrono::{Duration, Utc};
+use douglance_kv::{Database, GcOptions, KvStore};
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::time::Instant;
+
+const KEYS: i64 = 100_000;
+const VERSIONS: i64 = 10;
+
+fn main() {
+    let dir = std::env::temp_dir().join(format!("kv-bench-gc-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let template = dir.join("template.db");
+    populate(&template);
+
+    let cases = [
+        ("dry run", GcOptions { dry_run: true, ..Default::default() }),
+        ("expired + deleted", GcOptions::default()),
+        ("keep 3 versions", GcOptions { keep_versions: Some(3), ..Default::default() }),
+        ("older than 5 days", GcOptions { older_than: Some(Duration::days(5)), ..Default::default() }),
+    ];
+    for (name, opts) in cases {
+        let path = dir.join("kv.db");
+        std::fs::copy(&template, &path).unwrap();
+        let db = Database::builder().path(&path).auto_gc(false).open().unwrap();
+
+        let start = Instant::now();
+        let result = db.gc(&opts).unwrap();
+        let elapsed = start.elapsed();
+        println!("{:<20} {:>8} entries {:>10.1} ms", name, result.entries_count, elapsed.as_secs_f64() * 1000.0);
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// Bulk-load entries straight into SQLite; going through `set` would take minutes
+fn populate(path: &Path) {
+    Database::builder().path(path).auto_gc(false).open().unwrap();
+
+    let mut conn = Connection::open(path).unwrap();
+    let tx = conn.transaction().unwrap();
+    {
+        let mut insert = tx
+            .prepare(
+                "INSERT INTO entries (key, value, version, size_bytes, created_at, expires_at, deleted_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )
+            .unwrap();
+        let now = Utc::now();
+        let value = [b'x'; 64];
+        for key in 0..KEYS {
+            let name = format!("key/{}", key);
+            // Every 20th key has expired and every 25th has been deleted
+            let expires_at = (key % 20 == 0).then(|| (now - Duration::hours(1)).to_rfc3339());
+            let deleted_at = (key % 25 == 1).then(|| (now - Duration::hours(1)).to_rfc3339());
+            for version in 1..=VERSIONS {
+                let created_at = (now - Duration::days(VERSIONS - version)).to_rfc3339();
+                insert
+                    .execute(params![name, &value[..], version, value.len() as i64, created_at, expires_at, deleted_at])
+                    .unwrap();
+            }
+        }
+    }
+    tx.commit().unwrap();
+}
@@ -0,0 +1,279 @@
+//! Async front end to `Database` for tokio-based programs (`async` feature).
+//!
+//! One dedicated thread owns the SQLite connection. Calls are shipped to it
+//! over a channel and their results come back through oneshot channels, so
+//! the executor never blocks on disk I/O or on another process's write lock.
+//! The thread stops once every `AsyncDatabase` handle has been dropped.
+
+use crate::db::{Change, Database, DatabaseBuilder};
+use crate::error::KvError;
+use crate::store::{
+    DeleteOptions, Entry, GetOptions, HistoryOptions, KeySummary, KvStore, ListOptions, SetOptions, SetResult,
+};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc as async_mpsc, oneshot};
+
+/// How often the worker checks the change log for watched keys
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+type Job = Box<dyn FnOnce(&Database) + Send>;
+
+enum Message {
+    Job(Job),
+    Watch(Watcher),
+}
+
+struct Watcher {
+    prefix: String,
+    scope: Option<String>,
+    seq: i64,
+    tx: async_mpsc::UnboundedSender<Change>,
+}
+
+#[derive(Clone)]
+pub struct AsyncDatabase {
+    tx: mpsc::Sender<Message>,
+}
+
+impl AsyncDatabase {
+    /// Open the default database under the user's config directory
+    pub async fn open() -> Result<Self, KvError> {
+        Self::open_with(Database::builder()).await
+    }
+
+    /// Open a database configured by `builder` on the worker thread
+    pub async fn open_with(builder: DatabaseBuilder) -> Result<Self, KvError> {
+        let (ready_tx, ready_rx) = oneshot::channel();
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || match builder.open() {
+            Ok(db) => {
+                let _ = ready_tx.send(Ok(()));
+                run(db, rx);
+            }
+            Err(e) => {
+                let _ = ready_tx.send(Err(e));
+            }
+        });
+
+        ready_rx.await.map_err(|_| stopped())??;
+        Ok(Self { tx })
+    }
+
+    /// Hand an already open database to a new worker thread
+    pub fn from_database(db: Database) -> Self {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || run(db, rx));
+        Self { tx }
+    }
+
+    /// Run `f` against the database on the worker thread. Every other method is
+    /// built on this; use it for operations without a dedicated wrapper.
+    pub async fn call<R, F>(&self, f: F) -> Result<R, KvError>
+    where
+        R: Send + 'static,
+        F: FnOnce(&Database) -> Result<R, KvError> + Send + 'static,
+    {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let job: Job = Box::new(move |db| {
+            let _ = reply_tx.send(f(db));
+        });
+        self.tx.send(Message::Job(job)).map_err(|_| stopped())?;
+        reply_rx.await.map_err(|_| stopped())?
+    }
+
+    pub async fn get(&self, key: &str, opts: &GetOptions<'_>) -> Result<Entry, KvError> {
+        let key = key.to_string();
+        let scope = opts.scope.map(str::to_string);
+        let version = opts.version;
+        self.call(move |db| {
+            db.get(
+                &key,
+                &GetOptions {
+                    scope: scope.as_deref(),
+                    version,
+                },
+            )
+        })
+        .await
+    }
+
+    pub async fn set(&self, key: &str, value: &[u8], opts: &SetOptions<'_>) -> Result<SetResult, KvError> {
+        let key = key.to_string();
+        let value = value.to_vec();
+        let scope = opts.scope.map(str::to_string);
+        let content_type = opts.content_type.map(str::to_string);
+        let original_filename = opts.original_filename.map(str::to_string);
+        let expires_at = opts.expires_at;
+        let sliding_ttl = opts.sliding_ttl;
+        self.call(move |db| {
+            db.set(
+                &key,
+                &value,
+                &SetOptions {
+                    scope: scope.as_deref(),
+                    content_type: content_type.as_deref(),
+                    original_filename: original_filename.as_deref(),
+                    expires_at,
+                    sliding_ttl,
+                },
+            )
+        })
+        .await
+    }
+
+    pub async fn delete(&self, key: &str, opts: &DeleteOptions<'_>) -> Result<u64, KvError> {
+        let key = key.to_string();
+        let scope = opts.scope.map(str::to_string);
+        let hard = opts.hard;
+        self.call(move |db| {
+            db.delete(
+                &key,
+                &DeleteOptions {
+                    scope: scope.as_deref(),
+                    hard,
+                },
+            )
+        })
+        .await
+    }
+
+    pub async fn list_keys(&self, opts: &ListOptions<'_>) -> Result<Vec<KeySummary>, KvError> {
+        let scope = opts.scope.map(str::to_string);
+        let prefix = opts.prefix.map(str::to_string);
+        let all_scopes = opts.all_scopes;
+        let limit = opts.limit;
+        self.call(move |db| {
+            db.list_keys(&ListOptions {
+                scope: scope.as_deref(),
+                all_scopes,
+                prefix: prefix.as_deref(),
+                limit,
+            })
+        })
+        .await
+    }
+
+    pub async fn history(&self, key: &str, opts: &HistoryOptions<'_>) -> Result<Vec<Entry>, KvError> {
+        let key = key.to_string();
+        let scope = opts.scope.map(str::to_string);
+        let limit = opts.limit;
+        self.call(move |db| {
+            db.history(
+                &key,
+                &HistoryOptions {
+                    scope: scope.as_deref(),
+                    limit,
+                },
+            )
+        })
+        .await
+    }
+
+    /// Stream change log events for keys starting with `prefix` (an empty
+    /// prefix watches the whole scope), beginning with the next change made.
+    /// Changes from other processes are picked up too.
+    pub async fn watch(&self, prefix: &str, scope: Option<&str>) -> Result<async_mpsc::UnboundedReceiver<Change>, KvError> {
+        let seq = self.call(|db| db.last_change_seq()).await?;
+        let (tx, rx) = async_mpsc::unbounded_channel();
+        let watcher = Watcher {
+            prefix: prefix.to_string(),
+            scope: scope.map(str::to_string),
+            seq,
+            tx,
+        };
+        self.tx.send(Message::Watch(watcher)).map_err(|_| stopped())?;
+        Ok(rx)
+    }
+}
+
+fn stopped() -> KvError {
+    KvError::Database("database thread has stopped".into())
+}
+
+fn run(db: Database, rx: mpsc::Receiver<Message>) {
+    let mut watchers: Vec<Watcher> = Vec::new();
+    let mut last_poll = Instant::now();
+
+    loop {
+        match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(Message::Job(job)) => job(&db),
+            Ok(Message::Watch(watcher)) => watchers.push(watcher),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => return,
+        }
+
+        if !watchers.is_empty() && last_poll.elapsed() >= POLL_INTERVAL {
+            last_poll = Instant::now();
+            watchers.retain_mut(|w| poll(&db, w));
+        }
+    }
+}
+
+/// Forward new events to a watcher. Returns false once its receiver is gone.
+fn poll(db: &Database, watcher: &mut Watcher) -> bool {
+    if watcher.tx.is_closed() {
+        return false;
+    }
+
+    let changes = match db.changes(watcher.seq, None, watcher.scope.as_deref(), false) {
+        Ok(changes) => changes,
+        Err(e) => {
+            eprintln!("warning: watch poll failed: {}", e);
+            return true;
+        }
+    };
+
+    for change in changes {
+        watcher.seq = change.seq;
+        if change.key.starts_with(&watcher.prefix) && watcher.tx.send(change).is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_round_trip_and_watch() {
+        let db = AsyncDatabase::open_with(Database::builder().in_memory()).await.unwrap();
+        let mut changes = db.watch("job/", Some("s")).await.unwrap();
+
+        let scoped = SetOptions {
+            scope: Some("s"),
+            ..Default::default()
+        };
+        assert!(db.set("job/1", b"queued", &scoped).await.unwrap().was_saved);
+        db.set("other", b"x", &scoped).await.unwrap();
+        db.set("job/1", b"done", &SetOptions::default()).await.unwrap();
+
+        let get = GetOptions {
+            scope: Some("s"),
+            ..Default::default()
+        };
+        assert_eq!(db.get("job/1", &get).await.unwrap().value, b"queued");
+        let list = ListOptions {
+            scope: Some("s"),
+            ..Default::default()
+        };
+        assert_eq!(db.list_keys(&list).await.unwrap().len(), 2);
+
+        let change = changes.recv().await.unwrap();
+        assert_eq!((change.event.as_str(), change.key.as_str(), change.version), ("set", "job/1", Some(1)));
+
+        db.delete("job/1", &DeleteOptions { scope: Some("s"), hard: false }).await.unwrap();
+        assert_eq!(changes.recv().await.unwrap().event, "delete");
+        assert!(changes.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_open_failure_is_reported() {
+        let builder = Database::builder().path("/dev/null/kv.db");
+        assert!(AsyncDatabase::open_with(builder).await.is_err());
+    }
+}
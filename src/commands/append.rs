@@ -0,0 +1,77 @@
+use crate::commands::set::SIZE_LIMIT;
+use crate::db::Database;
+use crate::detection::detect_input;
+use crate::error::KvError;
+use crate::scope::current_scope;
+
+pub fn execute(
+    key: &str,
+    value: Option<&str>,
+    separator: Option<&str>,
+    literal: bool,
+    force: bool,
+    no_history: bool,
+    global: bool,
+) -> Result<(), KvError> {
+    let input = detect_input(value, literal)?;
+
+    let content = input.content();
+    let size = content.len() as u64;
+
+    // Check size limit
+    if size > SIZE_LIMIT && !force {
+        return Err(KvError::SizeLimitExceeded {
+            size,
+            limit: SIZE_LIMIT,
+        });
+    }
+
+    let scope = if global {
+        None
+    } else {
+        current_scope()
+    };
+
+    let separator = separator.map(unescape);
+
+    let db = Database::open()?;
+    let (version, total) = db.append(
+        key,
+        content,
+        separator.as_deref().map(str::as_bytes),
+        input.content_type(),
+        scope.as_deref(),
+        no_history,
+    )?;
+
+    let scope_info = if global { " (global)" } else { "" };
+    eprintln!(
+        "appended {} bytes to {}{} (version {}, {} bytes)",
+        size, key, scope_info, version, total
+    );
+
+    Ok(())
+}
+
+/// Expand `\n`, `\t` and `\\` so separators can be passed without shell quoting tricks
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
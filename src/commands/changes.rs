@@ -0,0 +1,69 @@
+use crate::db::Database;
+use crate::error::KvError;
+use crate::scope::current_scope;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct ChangeJson {
+    seq: i64,
+    event: String,
+    key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field: Option<String>,
+    created_at: String,
+}
+
+pub fn execute(since: i64, limit: Option<usize>, global: bool, all: bool, json: bool) -> Result<(), KvError> {
+    let scope = if global || all {
+        None
+    } else {
+        current_scope()
+    };
+
+    let db = Database::open()?;
+    let changes = db.changes(since, limit, scope.as_deref(), all)?;
+
+    if json {
+        let output: Vec<ChangeJson> = changes.into_iter().map(|c| ChangeJson {
+            seq: c.seq,
+            event: c.event,
+            key: c.key,
+            scope: c.scope,
+            version: c.version,
+            field: c.field,
+            created_at: c.created_at.to_rfc3339(),
+        }).collect();
+        println!("{}", serde_json::to_string(&output).unwrap());
+        return Ok(());
+    }
+
+    if changes.is_empty() {
+        eprintln!("no changes found");
+        return Ok(());
+    }
+
+    println!("{:>8} {:<8} {:<30} {:>8} CREATED", "SEQ", "EVENT", "KEY", "VERSION");
+    println!("{}", "-".repeat(80));
+
+    for change in changes {
+        let version = change.version.map(|v| v.to_string()).unwrap_or_else(|| "-".into());
+        let key = match &change.field {
+            Some(field) => format!("{}.{}", change.key, field),
+            None => change.key,
+        };
+        println!(
+            "{:>8} {:<8} {:<30} {:>8} {}",
+            change.seq,
+            change.event,
+            key,
+            version,
+            change.created_at.format("%Y-%m-%d %H:%M:%S")
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,15 @@
+use crate::daemon;
+use crate::db::Database;
+use crate::error::KvError;
+use std::path::PathBuf;
+
+pub fn execute(socket: Option<&str>) -> Result<(), KvError> {
+    let path = match socket {
+        Some(s) => PathBuf::from(s),
+        None => daemon::socket_path()
+            .ok_or_else(|| KvError::Database("could not find config directory".into()))?,
+    };
+
+    let db = Database::open()?;
+    daemon::serve(db, &path)
+}
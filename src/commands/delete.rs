@@ -1,6 +1,7 @@
 use crate::db::Database;
 use crate::error::KvError;
 use crate::scope::current_scope;
+use crate::store::{DeleteOptions, KvStore};
 
 pub fn execute(key: &str, hard: bool, global: bool) -> Result<(), KvError> {
     let scope = if global {
@@ -9,8 +10,7 @@ pub fn execute(key: &str, hard: bool, global: bool) -> Result<(), KvError> {
         current_scope()
     };
 
-    let db = Database::open()?;
-    let affected = db.delete(key, hard, scope.as_deref())?;
+    let affected = remove(key, hard, scope.as_deref())?;
 
     if hard {
         eprintln!("permanently deleted {} entries for key '{}'", affected, key);
@@ -20,3 +20,15 @@ pub fn execute(key: &str, hard: bool, global: bool) -> Result<(), KvError> {
 
     Ok(())
 }
+
+/// Prefer a running daemon, falling back to opening the database directly
+fn remove(key: &str, hard: bool, scope: Option<&str>) -> Result<u64, KvError> {
+    let opts = DeleteOptions { scope, hard };
+
+    #[cfg(unix)]
+    if let Some(mut client) = crate::daemon::Client::connect() {
+        return client.delete(key, &opts);
+    }
+
+    Database::open()?.delete(key, &opts)
+}
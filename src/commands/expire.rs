@@ -0,0 +1,32 @@
+use crate::db::Database;
+use crate::error::KvError;
+use crate::scope::current_scope;
+use crate::ttl::{parse_expires_at, parse_ttl};
+
+/// Set a new expiry on an existing key, from a TTL or an absolute time
+pub fn execute(key: &str, ttl: Option<&str>, at: Option<&str>, global: bool) -> Result<(), KvError> {
+    let expires_at = match (ttl, at) {
+        (Some(ttl), _) => parse_ttl(ttl)?,
+        (None, Some(at)) => parse_expires_at(at)?,
+        (None, None) => return Err(KvError::InvalidTtl("a TTL or --at is required".into())),
+    };
+
+    let scope = if global {
+        None
+    } else {
+        current_scope()
+    };
+
+    let db = Database::open()?;
+    let version = db.set_expiry(key, Some(expires_at), scope.as_deref())?;
+
+    let scope_info = if global { " (global)" } else { "" };
+    eprintln!(
+        "{}{} expires {} (version {})",
+        key,
+        scope_info,
+        expires_at.format("%Y-%m-%d %H:%M:%S UTC"),
+        version
+    );
+    Ok(())
+}
@@ -1,13 +1,71 @@
 use crate::commands::list::format_size;
-use crate::db::Database;
+use crate::db::{AutoGcConfig, Database};
 use crate::error::KvError;
+use crate::store::{GcOptions, KvStore};
+use crate::ttl::{format_duration, parse_duration};
+use chrono::Duration;
+
+/// An auto-GC threshold given on the command line: a limit, or "off"
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Threshold<T> {
+    Off,
+    At(T),
+}
+
+impl<T> Threshold<T> {
+    fn into_option(self) -> Option<T> {
+        match self {
+            Threshold::Off => None,
+            Threshold::At(limit) => Some(limit),
+        }
+    }
+}
+
+fn parse_threshold<T>(s: &str, parse: impl FnOnce(&str) -> Result<T, String>) -> Result<Threshold<T>, String> {
+    if s.eq_ignore_ascii_case("off") {
+        Ok(Threshold::Off)
+    } else {
+        parse(s).map(Threshold::At)
+    }
+}
+
+/// Parse a size such as "500KB", "100MB" or "1GB" (1024-based), or "off"
+pub fn parse_size_threshold(s: &str) -> Result<Threshold<u64>, String> {
+    parse_threshold(s, |s| {
+        let upper = s.trim().to_ascii_uppercase();
+        let digits = upper.find(|c: char| !c.is_ascii_digit()).unwrap_or(upper.len());
+        let multiplier: u64 = match upper[digits..].trim() {
+            "" | "B" => 1,
+            "K" | "KB" => 1024,
+            "M" | "MB" => 1024 * 1024,
+            "G" | "GB" => 1024 * 1024 * 1024,
+            _ => return Err(format!("invalid size: {} (e.g. 500KB, 100MB, 1GB)", s)),
+        };
+        upper[..digits]
+            .parse::<u64>()
+            .ok()
+            .and_then(|n| n.checked_mul(multiplier))
+            .ok_or_else(|| format!("invalid size: {}", s))
+    })
+}
+
+/// Parse an entry count, or "off"
+pub fn parse_count_threshold(s: &str) -> Result<Threshold<u64>, String> {
+    parse_threshold(s, |s| s.parse().map_err(|_| format!("invalid count: {}", s)))
+}
+
+/// Parse an interval such as "12h" or "7d", or "off"
+pub fn parse_interval_threshold(s: &str) -> Result<Threshold<Duration>, String> {
+    parse_threshold(s, |s| parse_duration(s).map_err(|e| e.to_string()))
+}
 
 pub fn execute(
     run: bool,
     older_than: Option<u64>,
-    keep_versions: Option<i64>,
+    keep_versions: Option<usize>,
     expired: bool,
     deleted: bool,
+    vacuum: bool,
 ) -> Result<(), KvError> {
     let db = Database::open()?;
 
@@ -21,10 +79,14 @@ pub fn execute(
         eprintln!("  --keep-versions N  Keep only last N versions per key");
         eprintln!("  --expired          Only clean expired entries");
         eprintln!("  --deleted          Only clean soft-deleted entries");
+        eprintln!("  --vacuum           Also return freed space to the filesystem");
         eprintln!();
 
         // Show what would be cleaned with default settings (expired + deleted)
-        let result = db.gc(false, None, None, false, false)?;
+        let result = db.gc(&GcOptions {
+            dry_run: true,
+            ..Default::default()
+        })?;
         if result.entries_count > 0 {
             eprintln!(
                 "Without filters: {} entries ({}) would be cleaned",
@@ -37,7 +99,15 @@ pub fn execute(
         return Ok(());
     }
 
-    let result = db.gc(run, older_than, keep_versions, expired, deleted)?;
+    // --expired and --deleted narrow the default of cleaning both
+    let result = db.gc(&GcOptions {
+        dry_run: !run,
+        expired: expired || !deleted,
+        deleted: deleted || !expired,
+        deleted_older_than: None,
+        older_than: older_than.map(|days| Duration::days(days as i64)),
+        keep_versions,
+    })?;
 
     if result.was_run {
         if result.entries_count > 0 {
@@ -49,6 +119,9 @@ pub fn execute(
         } else {
             eprintln!("No entries to clean.");
         }
+        if vacuum {
+            eprintln!("Vacuumed, returned {} to the filesystem", format_size(db.vacuum()? as i64));
+        }
     } else {
         if result.entries_count > 0 {
             eprintln!(
@@ -63,3 +136,48 @@ pub fn execute(
 
     Ok(())
 }
+
+/// Parse how long deleted keys are kept, such as "30d"
+pub fn parse_retention(s: &str) -> Result<Duration, String> {
+    parse_duration(s).map_err(|e| e.to_string())
+}
+
+/// Show or change when writes trigger an automatic GC
+pub fn auto(
+    max_size: Option<Threshold<u64>>,
+    max_entries: Option<Threshold<u64>>,
+    interval: Option<Threshold<Duration>>,
+    deleted_retention: Option<Duration>,
+    enable: bool,
+    disable: bool,
+) -> Result<(), KvError> {
+    let db = Database::open()?;
+    let mut config = db.auto_gc_config()?;
+
+    let changed = max_size.is_some() || max_entries.is_some() || interval.is_some() || deleted_retention.is_some();
+    if changed || enable || disable {
+        config = AutoGcConfig {
+            enabled: (config.enabled || enable) && !disable,
+            max_size: max_size.map_or(config.max_size, Threshold::into_option),
+            max_entries: max_entries.map_or(config.max_entries, Threshold::into_option),
+            interval: interval.map_or(config.interval, Threshold::into_option),
+            deleted_retention: deleted_retention.unwrap_or(config.deleted_retention),
+        };
+        db.set_auto_gc_config(&config)?;
+        eprintln!("auto-GC settings updated");
+    }
+
+    let off = || "off".to_string();
+    println!("auto-GC:     {}", if config.enabled { "enabled" } else { "disabled" });
+    println!("max size:    {}", config.max_size.map_or_else(off, |n| format_size(n as i64)));
+    println!("max entries: {}", config.max_entries.map_or_else(off, |n| n.to_string()));
+    println!("interval:    {}", config.interval.map_or_else(off, format_duration));
+    println!("retention:   {} for deleted keys", format_duration(config.deleted_retention));
+    println!(
+        "last run:    {}",
+        db.last_gc()?
+            .map_or_else(|| "never".to_string(), |t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+    );
+    println!("file size:   {}", format_size(db.file_size()? as i64));
+    Ok(())
+}
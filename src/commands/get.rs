@@ -1,6 +1,9 @@
-use crate::db::Database;
+use crate::db::{Database, Entry};
+use crate::encoding::{encode_value, ValueEncoding};
 use crate::error::KvError;
+use crate::query::JsonPath;
 use crate::scope::current_scope;
+use crate::store::{GetOptions, KvStore};
 use serde::Serialize;
 use std::io::{self, IsTerminal, Write};
 
@@ -8,6 +11,7 @@ use std::io::{self, IsTerminal, Write};
 struct JsonOutput {
     key: String,
     value: String,
+    value_encoding: ValueEncoding,
     version: i64,
     scope: Option<String>,
     content_type: Option<String>,
@@ -18,22 +22,35 @@ struct JsonOutput {
     expires_at: Option<String>,
 }
 
-pub fn execute(key: &str, version: Option<i64>, verbose: bool, global: bool, json: bool) -> Result<(), KvError> {
+pub fn execute(
+    key: &str,
+    version: Option<i64>,
+    verbose: bool,
+    global: bool,
+    json: bool,
+    encoding: ValueEncoding,
+    path: Option<&str>,
+) -> Result<(), KvError> {
+    let path = path.map(JsonPath::parse).transpose()?;
     let scope = if global {
         None
     } else {
         current_scope()
     };
 
-    let db = Database::open()?;
-    let entry = db.get(key, version, scope.as_deref())?;
+    let entry = fetch(key, version, scope.as_deref())?;
+
+    if let Some(path) = path {
+        return print_path(&entry, &path, json);
+    }
 
     if json {
         // JSON output mode
-        let value_str = String::from_utf8_lossy(&entry.value).to_string();
+        let (value, value_encoding) = encode_value(&entry.value, encoding);
         let output = JsonOutput {
             key: entry.key.clone(),
-            value: value_str,
+            value,
+            value_encoding,
             version: entry.version,
             scope: entry.scope.clone(),
             content_type: entry.content_type.clone(),
@@ -83,3 +100,35 @@ pub fn execute(key: &str, version: Option<i64>, verbose: bool, global: bool, jso
 
     Ok(())
 }
+
+/// Print the field at `path` of a JSON value: strings raw, anything else as JSON
+fn print_path(entry: &Entry, path: &JsonPath, json: bool) -> Result<(), KvError> {
+    let doc: serde_json::Value = serde_json::from_slice(&entry.value).map_err(|e| KvError::Decode {
+        key: entry.key.clone(),
+        format: "json",
+        message: e.to_string(),
+    })?;
+
+    let field = path.extract(&doc).ok_or_else(|| KvError::FieldNotFound {
+        key: entry.key.clone(),
+        field: path.to_string(),
+    })?;
+
+    match field {
+        serde_json::Value::String(s) if !json => println!("{}", s),
+        other => println!("{}", other),
+    }
+    Ok(())
+}
+
+/// Prefer a running daemon, falling back to opening the database directly
+fn fetch(key: &str, version: Option<i64>, scope: Option<&str>) -> Result<Entry, KvError> {
+    let opts = GetOptions { scope, version };
+
+    #[cfg(unix)]
+    if let Some(mut client) = crate::daemon::Client::connect() {
+        return client.get(key, &opts);
+    }
+
+    Database::open()?.get(key, &opts)
+}
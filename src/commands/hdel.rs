@@ -0,0 +1,18 @@
+use crate::db::Database;
+use crate::error::KvError;
+use crate::scope::current_scope;
+
+pub fn execute(key: &str, field: &str, global: bool) -> Result<(), KvError> {
+    let scope = if global {
+        None
+    } else {
+        current_scope()
+    };
+
+    let db = Database::open()?;
+    let affected = db.hdel(key, field, scope.as_deref())?;
+
+    eprintln!("soft-deleted {} entries for field '{}' of key '{}'", affected, field, key);
+
+    Ok(())
+}
@@ -0,0 +1,27 @@
+use crate::db::Database;
+use crate::error::KvError;
+use crate::scope::current_scope;
+use std::io::{self, IsTerminal, Write};
+
+pub fn execute(key: &str, field: &str, global: bool) -> Result<(), KvError> {
+    let scope = if global {
+        None
+    } else {
+        current_scope()
+    };
+
+    let db = Database::open()?;
+    let field = db.hget(key, field, scope.as_deref())?;
+
+    // Write raw value to stdout
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    handle.write_all(&field.value)?;
+
+    // Add newline for terminal display, but not when piping (preserves exact data)
+    if io::stdout().is_terminal() && !field.value.ends_with(b"\n") {
+        handle.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
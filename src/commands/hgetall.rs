@@ -0,0 +1,75 @@
+use crate::db::{Database, HashField};
+use crate::encoding::{encode_value, ValueEncoding};
+use crate::error::KvError;
+use crate::scope::current_scope;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+#[derive(Serialize)]
+struct FieldJson {
+    value: String,
+    value_encoding: ValueEncoding,
+    version: i64,
+}
+
+pub fn execute(key: &str, global: bool, json: bool) -> Result<(), KvError> {
+    let scope = if global {
+        None
+    } else {
+        current_scope()
+    };
+
+    let db = Database::open()?;
+    let fields = db.hgetall(key, scope.as_deref())?;
+
+    if json {
+        println!("{}", serde_json::to_string(&to_json(fields)).unwrap());
+        return Ok(());
+    }
+
+    for f in fields {
+        println!("{}\t{}", f.field, String::from_utf8_lossy(&f.value));
+    }
+
+    Ok(())
+}
+
+/// Fields keyed by name, with binary values base64-encoded
+fn to_json(fields: Vec<HashField>) -> Map<String, Value> {
+    fields
+        .into_iter()
+        .map(|f| {
+            let (value, value_encoding) = encode_value(&f.value, ValueEncoding::Utf8);
+            let field = FieldJson {
+                value,
+                value_encoding,
+                version: f.version,
+            };
+            (f.field, serde_json::to_value(field).unwrap())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::decode_value;
+
+    #[test]
+    fn test_binary_fields_round_trip() {
+        let db = Database::open_in_memory().unwrap();
+        let binary = [0u8, 159, 146, 150];
+        db.hset("h", "bin", &binary, None).unwrap();
+        db.hset("h", "text", b"hello", None).unwrap();
+
+        let output = to_json(db.hgetall("h", None).unwrap());
+        let json: Value = serde_json::from_str(&serde_json::to_string(&output).unwrap()).unwrap();
+        assert_eq!(json["text"]["value"], "hello");
+        assert_eq!(json["text"]["value_encoding"], "utf8");
+        assert_eq!(json["bin"]["value_encoding"], "base64");
+
+        let encoding: ValueEncoding = serde_json::from_value(json["bin"]["value_encoding"].clone()).unwrap();
+        let value = decode_value("h", json["bin"]["value"].as_str().unwrap(), encoding);
+        assert_eq!(value.unwrap(), binary);
+    }
+}
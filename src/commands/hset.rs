@@ -0,0 +1,45 @@
+use crate::commands::set::SIZE_LIMIT;
+use crate::db::Database;
+use crate::detection::detect_input;
+use crate::error::KvError;
+use crate::scope::current_scope;
+
+pub fn execute(
+    key: &str,
+    field: &str,
+    value: Option<&str>,
+    literal: bool,
+    force: bool,
+    global: bool,
+) -> Result<(), KvError> {
+    let input = detect_input(value, literal)?;
+
+    let content = input.content();
+    let size = content.len() as u64;
+
+    // Check size limit
+    if size > SIZE_LIMIT && !force {
+        return Err(KvError::SizeLimitExceeded {
+            size,
+            limit: SIZE_LIMIT,
+        });
+    }
+
+    let scope = if global {
+        None
+    } else {
+        current_scope()
+    };
+
+    let db = Database::open()?;
+    let (version, was_saved) = db.hset(key, field, content, scope.as_deref())?;
+
+    if was_saved {
+        let scope_info = if global { " (global)" } else { "" };
+        eprintln!("set {}.{}{} (version {}, {} bytes)", key, field, scope_info, version, size);
+    } else {
+        eprintln!("{}.{} unchanged (version {})", key, field, version);
+    }
+
+    Ok(())
+}
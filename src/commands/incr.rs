@@ -0,0 +1,38 @@
+use crate::db::Database;
+use crate::error::KvError;
+use crate::scope::current_scope;
+use crate::store::{IncrOptions, KvStore};
+
+/// Shared by `incr` and `decr`; `decrement` flips the sign of `by`.
+pub fn execute(key: &str, by: i64, decrement: bool, no_history: bool, global: bool) -> Result<(), KvError> {
+    let scope = if global {
+        None
+    } else {
+        current_scope()
+    };
+
+    let delta = if decrement {
+        by.checked_neg()
+            .ok_or_else(|| KvError::IntegerOverflow(key.to_string()))?
+    } else {
+        by
+    };
+
+    let value = apply(key, delta, scope.as_deref(), no_history)?;
+
+    println!("{}", value);
+
+    Ok(())
+}
+
+/// Prefer a running daemon, falling back to opening the database directly
+fn apply(key: &str, delta: i64, scope: Option<&str>, in_place: bool) -> Result<i64, KvError> {
+    let opts = IncrOptions { scope, in_place };
+
+    #[cfg(unix)]
+    if let Some(mut client) = crate::daemon::Client::connect() {
+        return client.incr(key, delta, &opts);
+    }
+
+    Database::open()?.incr(key, delta, &opts)
+}
@@ -0,0 +1,18 @@
+use crate::db::Database;
+use crate::error::KvError;
+use crate::scope::current_scope;
+
+pub fn execute(key: &str, global: bool) -> Result<(), KvError> {
+    let scope = if global {
+        None
+    } else {
+        current_scope()
+    };
+
+    let db = Database::open()?;
+    let len = db.list_len(key, scope.as_deref())?;
+
+    println!("{}", len);
+
+    Ok(())
+}
@@ -1,6 +1,9 @@
 use crate::db::Database;
+use crate::encoding::{encode_value, ValueEncoding};
 use crate::error::KvError;
+use crate::query::Filter;
 use crate::scope::current_scope;
+use crate::store::{HistoryOptions, KvStore, ListOptions};
 use serde::Serialize;
 
 #[derive(Serialize)]
@@ -16,6 +19,8 @@ struct KeyJson {
 #[derive(Serialize)]
 struct HistoryJson {
     version: i64,
+    value: String,
+    value_encoding: ValueEncoding,
     size: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
     content_type: Option<String>,
@@ -28,7 +33,16 @@ struct HistoryJson {
     expires_at: Option<String>,
 }
 
-pub fn execute(key: Option<&str>, limit: Option<usize>, global: bool, all: bool, json: bool) -> Result<(), KvError> {
+pub fn execute(
+    key: Option<&str>,
+    limit: Option<usize>,
+    global: bool,
+    all: bool,
+    json: bool,
+    encoding: ValueEncoding,
+    filter: Option<&str>,
+) -> Result<(), KvError> {
+    let filter = filter.map(Filter::parse).transpose()?;
     let scope = if global || all {
         None
     } else {
@@ -38,13 +52,29 @@ pub fn execute(key: Option<&str>, limit: Option<usize>, global: bool, all: bool,
     let db = Database::open()?;
 
     match key {
-        Some(k) => list_key_history(&db, k, limit, scope.as_deref(), json),
-        None => list_all_keys(&db, limit, scope.as_deref(), all, json),
+        Some(k) => list_key_history(&db, k, limit, scope.as_deref(), json, encoding),
+        None => list_all_keys(&db, limit, scope.as_deref(), all, json, filter.as_ref()),
     }
 }
 
-fn list_all_keys(db: &Database, limit: Option<usize>, scope: Option<&str>, all: bool, json: bool) -> Result<(), KvError> {
-    let keys = db.list_keys(limit, scope, all)?;
+fn list_all_keys(
+    db: &Database,
+    limit: Option<usize>,
+    scope: Option<&str>,
+    all: bool,
+    json: bool,
+    filter: Option<&Filter>,
+) -> Result<(), KvError> {
+    let opts = ListOptions {
+        scope,
+        all_scopes: all,
+        limit,
+        ..Default::default()
+    };
+    let keys = match filter {
+        Some(filter) => db.list_keys_where(&opts, filter)?,
+        None => db.list_keys(&opts)?,
+    };
 
     if keys.is_empty() {
         if !json {
@@ -69,7 +99,7 @@ fn list_all_keys(db: &Database, limit: Option<usize>, scope: Option<&str>, all:
 
     if all {
         // Show scope column when listing all scopes
-        println!("{:<30} {:>8} {:>12} {:<14} {}", "KEY", "VERSIONS", "SIZE", "SCOPE", "LAST UPDATED");
+        println!("{:<30} {:>8} {:>12} {:<14} LAST UPDATED", "KEY", "VERSIONS", "SIZE", "SCOPE");
         println!("{}", "-".repeat(85));
 
         for summary in keys {
@@ -84,7 +114,7 @@ fn list_all_keys(db: &Database, limit: Option<usize>, scope: Option<&str>, all:
             );
         }
     } else {
-        println!("{:<30} {:>8} {:>12} {}", "KEY", "VERSIONS", "SIZE", "LAST UPDATED");
+        println!("{:<30} {:>8} {:>12} LAST UPDATED", "KEY", "VERSIONS", "SIZE");
         println!("{}", "-".repeat(70));
 
         for summary in keys {
@@ -101,24 +131,36 @@ fn list_all_keys(db: &Database, limit: Option<usize>, scope: Option<&str>, all:
     Ok(())
 }
 
-fn list_key_history(db: &Database, key: &str, limit: Option<usize>, scope: Option<&str>, json: bool) -> Result<(), KvError> {
-    let entries = db.list_key_history(key, limit, scope)?;
+fn list_key_history(
+    db: &Database,
+    key: &str,
+    limit: Option<usize>,
+    scope: Option<&str>,
+    json: bool,
+    encoding: ValueEncoding,
+) -> Result<(), KvError> {
+    let entries = db.history(key, &HistoryOptions { scope, limit })?;
 
     if json {
-        let output: Vec<HistoryJson> = entries.iter().map(|e| HistoryJson {
-            version: e.version,
-            size: e.size_bytes,
-            content_type: e.content_type.clone(),
-            created_at: e.created_at.to_rfc3339(),
-            original_filename: e.original_filename.clone(),
-            deleted_at: e.deleted_at.map(|dt| dt.to_rfc3339()),
-            expires_at: e.expires_at.map(|dt| dt.to_rfc3339()),
+        let output: Vec<HistoryJson> = entries.iter().map(|e| {
+            let (value, value_encoding) = encode_value(&e.value, encoding);
+            HistoryJson {
+                version: e.version,
+                value,
+                value_encoding,
+                size: e.size_bytes,
+                content_type: e.content_type.clone(),
+                created_at: e.created_at.to_rfc3339(),
+                original_filename: e.original_filename.clone(),
+                deleted_at: e.deleted_at.map(|dt| dt.to_rfc3339()),
+                expires_at: e.expires_at.map(|dt| dt.to_rfc3339()),
+            }
         }).collect();
         println!("{}", serde_json::to_string(&output).unwrap());
         return Ok(());
     }
 
-    println!("{:>8} {:>12} {:<20} {:<20} {}", "VERSION", "SIZE", "TYPE", "CREATED", "FILENAME");
+    println!("{:>8} {:>12} {:<20} {:<20} FILENAME", "VERSION", "SIZE", "TYPE", "CREATED");
     println!("{}", "-".repeat(80));
 
     let now = chrono::Utc::now();
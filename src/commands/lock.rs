@@ -0,0 +1,122 @@
+use crate::db::Database;
+use crate::error::KvError;
+use crate::scope::current_scope;
+use crate::ttl::{format_duration, parse_duration};
+use chrono::{Duration, Utc};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+pub fn execute(
+    name: &str,
+    ttl: &str,
+    wait: Option<&str>,
+    renew: bool,
+    token: Option<&str>,
+    global: bool,
+) -> Result<(), KvError> {
+    let scope = if global {
+        None
+    } else {
+        current_scope()
+    };
+
+    let ttl = parse_lease(ttl)?;
+    let db = Database::open()?;
+
+    if renew {
+        // clap guarantees --token accompanies --renew
+        renew_lease(&db, name, scope.as_deref(), token.unwrap_or_default(), ttl)?;
+        eprintln!("renewed lock {} for {}s", name, ttl.num_seconds());
+        return Ok(());
+    }
+
+    let token = token.map(str::to_string).unwrap_or_else(new_token);
+    let wait = wait.map(parse_duration).transpose()?;
+    acquire(&db, name, scope.as_deref(), &token, ttl, wait)?;
+
+    // Print the owner token so callers can renew or unlock
+    println!("{}", token);
+
+    Ok(())
+}
+
+/// Parse a lease duration. A lease that isn't positive would already have expired.
+pub fn parse_lease(ttl: &str) -> Result<Duration, KvError> {
+    let lease = parse_duration(ttl)?;
+    if lease <= Duration::zero() {
+        return Err(KvError::InvalidTtl(format!("lock TTL must be positive, got {}", format_duration(lease))));
+    }
+    Ok(lease)
+}
+
+/// Extend a lease held by `token`, failing if it is held by someone else or has lapsed
+pub fn renew_lease(db: &Database, name: &str, scope: Option<&str>, token: &str, ttl: Duration) -> Result<(), KvError> {
+    if !db.renew_lock(name, scope, token, ttl)? {
+        return Err(KvError::LockNotHeld(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Acquire a lock, polling until `wait` elapses if it is currently held
+pub fn acquire(
+    db: &Database,
+    name: &str,
+    scope: Option<&str>,
+    token: &str,
+    ttl: Duration,
+    wait: Option<Duration>,
+) -> Result<(), KvError> {
+    let deadline = Utc::now() + wait.unwrap_or_else(Duration::zero);
+
+    loop {
+        if db.acquire_lock(name, scope, token, ttl)? {
+            return Ok(());
+        }
+        if Utc::now() >= deadline {
+            return Err(KvError::LockHeld(name.to_string()));
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Generate an owner token unique to this process and moment
+pub fn new_token() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!("{:x}-{:x}", std::process::id(), nanos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lease() {
+        assert_eq!(parse_lease("30s").unwrap(), Duration::seconds(30));
+        for ttl in ["0s", "PT0S", "0m"] {
+            assert!(matches!(parse_lease(ttl), Err(KvError::InvalidTtl(_))), "{}", ttl);
+        }
+    }
+
+    #[test]
+    fn test_lock_and_renew() {
+        let db = Database::open_in_memory().unwrap();
+        let ttl = Duration::seconds(30);
+        acquire(&db, "m", None, "a", ttl, None).unwrap();
+        assert!(matches!(acquire(&db, "m", None, "b", ttl, None), Err(KvError::LockHeld(_))));
+
+        // Waiting polls until it gives up
+        let wait = Some(Duration::milliseconds(250));
+        assert!(matches!(acquire(&db, "m", None, "b", ttl, wait), Err(KvError::LockHeld(_))));
+
+        renew_lease(&db, "m", None, "a", ttl).unwrap();
+        assert!(matches!(renew_lease(&db, "m", None, "b", ttl), Err(KvError::LockNotHeld(_))));
+        assert!(matches!(renew_lease(&db, "other", None, "a", ttl), Err(KvError::LockNotHeld(_))));
+
+        assert!(db.release_lock("m", None, Some("a")).unwrap());
+        acquire(&db, "m", None, "b", ttl, wait).unwrap();
+    }
+}
@@ -0,0 +1,20 @@
+use crate::db::Database;
+use crate::error::KvError;
+use crate::mcp::McpServer;
+use crate::scope::hash_path;
+use std::path::Path;
+
+pub fn execute(root: Option<&str>, global: bool) -> Result<(), KvError> {
+    let scope = if global {
+        None
+    } else {
+        let root = match root {
+            Some(r) => Path::new(r).to_path_buf(),
+            None => std::env::current_dir()?,
+        };
+        Some(hash_path(&root))
+    };
+
+    let db = Database::open()?;
+    McpServer::new(db, scope).serve_stdio()
+}
@@ -1,6 +1,31 @@
+pub mod append;
+pub mod changes;
+#[cfg(unix)]
+pub mod daemon;
 pub mod delete;
+pub mod expire;
 pub mod gc;
 pub mod get;
+pub mod hdel;
+pub mod hget;
+pub mod hgetall;
+pub mod hset;
+pub mod incr;
+pub mod len;
 pub mod list;
+pub mod lock;
+pub mod mcp;
+pub mod patch;
+pub mod persist;
+pub mod policy;
+pub mod pop;
+pub mod push;
+pub mod restore;
+pub mod schema;
+pub mod serve;
 pub mod set;
 pub mod stats;
+pub mod ttl;
+pub mod unlock;
+pub mod watch;
+pub mod with_lock;
@@ -0,0 +1,34 @@
+use crate::db::Database;
+use crate::error::KvError;
+use crate::patch::Patch;
+use crate::scope::current_scope;
+use crate::store::SetResult;
+use std::io::{self, IsTerminal, Read};
+
+pub fn execute(key: &str, global: bool) -> Result<(), KvError> {
+    let stdin = io::stdin();
+    if stdin.is_terminal() {
+        return Err(KvError::PatchFailed("pipe a JSON Patch or merge patch on stdin".into()));
+    }
+    let mut input = Vec::new();
+    stdin.lock().read_to_end(&mut input)?;
+    let patch = Patch::parse(&input)?;
+
+    let scope = if global {
+        None
+    } else {
+        current_scope()
+    };
+
+    let db = Database::open()?;
+    let SetResult { version, was_saved } = db.patch(key, &patch, scope.as_deref())?;
+
+    if was_saved {
+        let scope_info = if global { " (global)" } else { "" };
+        eprintln!("patched {}{} (version {})", key, scope_info, version);
+    } else {
+        eprintln!("{} unchanged (version {})", key, version);
+    }
+
+    Ok(())
+}
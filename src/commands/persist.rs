@@ -0,0 +1,19 @@
+use crate::db::Database;
+use crate::error::KvError;
+use crate::scope::current_scope;
+
+/// Remove the expiry from an existing key
+pub fn execute(key: &str, global: bool) -> Result<(), KvError> {
+    let scope = if global {
+        None
+    } else {
+        current_scope()
+    };
+
+    let db = Database::open()?;
+    let version = db.set_expiry(key, None, scope.as_deref())?;
+
+    let scope_info = if global { " (global)" } else { "" };
+    eprintln!("{}{} no longer expires (version {})", key, scope_info, version);
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use crate::db::Database;
+use crate::error::KvError;
+use crate::scope::current_scope;
+use crate::ttl::{format_duration, parse_duration};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct PolicyJson {
+    pattern: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default_ttl: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_versions: Option<usize>,
+    created_at: String,
+}
+
+fn scope(global: bool) -> Option<String> {
+    if global {
+        None
+    } else {
+        current_scope()
+    }
+}
+
+/// Set the default TTL and/or version limit for a key, `prefix*` pattern, or `*` for the whole scope
+pub fn set(pattern: &str, ttl: Option<&str>, max_versions: Option<usize>, global: bool) -> Result<(), KvError> {
+    let default_ttl = ttl.map(parse_duration).transpose()?;
+
+    let scope = scope(global);
+    Database::open()?.set_policy(pattern, scope.as_deref(), default_ttl, max_versions)?;
+
+    let scope_info = if global { " (global)" } else { "" };
+    eprintln!("policy set for {}{}", pattern, scope_info);
+    Ok(())
+}
+
+pub fn list(global: bool, json: bool) -> Result<(), KvError> {
+    let scope = scope(global);
+    let policies = Database::open()?.policies(scope.as_deref())?;
+
+    if json {
+        let output: Vec<PolicyJson> = policies
+            .into_iter()
+            .map(|policy| PolicyJson {
+                pattern: policy.pattern,
+                scope: policy.scope,
+                default_ttl: policy.default_ttl.map(|ttl| ttl.num_seconds()),
+                max_versions: policy.max_versions,
+                created_at: policy.created_at.to_rfc3339(),
+            })
+            .collect();
+        println!("{}", serde_json::to_string(&output).unwrap());
+        return Ok(());
+    }
+
+    if policies.is_empty() {
+        eprintln!("no policies");
+        return Ok(());
+    }
+    for policy in policies {
+        let ttl = policy.default_ttl.map(format_duration).unwrap_or_else(|| "-".into());
+        let versions = policy.max_versions.map(|n| n.to_string()).unwrap_or_else(|| "-".into());
+        println!("{:<30} ttl {:<10} max versions {}", policy.pattern, ttl, versions);
+    }
+    Ok(())
+}
+
+pub fn remove(pattern: &str, global: bool) -> Result<(), KvError> {
+    let scope = scope(global);
+    if !Database::open()?.remove_policy(pattern, scope.as_deref())? {
+        return Err(KvError::PolicyNotFound(pattern.to_string()));
+    }
+    eprintln!("policy removed from {}", pattern);
+    Ok(())
+}
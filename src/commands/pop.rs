@@ -0,0 +1,33 @@
+use crate::db::Database;
+use crate::error::KvError;
+use crate::scope::current_scope;
+use std::io::{self, IsTerminal, Write};
+
+/// Shared by `pop` and `peek`; `peek` leaves the item in the list.
+pub fn execute(key: &str, peek: bool, global: bool) -> Result<(), KvError> {
+    let scope = if global {
+        None
+    } else {
+        current_scope()
+    };
+
+    let db = Database::open()?;
+    let item = if peek {
+        db.peek(key, scope.as_deref())?
+    } else {
+        db.pop(key, scope.as_deref())?
+    };
+    let value = item.ok_or_else(|| KvError::ListEmpty(key.to_string()))?;
+
+    // Write raw value to stdout
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    handle.write_all(&value)?;
+
+    // Add newline for terminal display, but not when piping (preserves exact data)
+    if io::stdout().is_terminal() && !value.ends_with(b"\n") {
+        handle.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use crate::commands::set::SIZE_LIMIT;
+use crate::db::Database;
+use crate::detection::detect_input;
+use crate::error::KvError;
+use crate::scope::current_scope;
+use crate::ttl::parse_ttl;
+
+pub fn execute(
+    key: &str,
+    value: Option<&str>,
+    literal: bool,
+    force: bool,
+    global: bool,
+    ttl: Option<&str>,
+) -> Result<(), KvError> {
+    let input = detect_input(value, literal)?;
+
+    let content = input.content();
+    let size = content.len() as u64;
+
+    // Check size limit
+    if size > SIZE_LIMIT && !force {
+        return Err(KvError::SizeLimitExceeded {
+            size,
+            limit: SIZE_LIMIT,
+        });
+    }
+
+    let scope = if global {
+        None
+    } else {
+        current_scope()
+    };
+
+    let expires_at = ttl.map(parse_ttl).transpose()?;
+
+    let db = Database::open()?;
+    let len = db.push(key, content, scope.as_deref(), expires_at)?;
+
+    println!("{}", len);
+
+    Ok(())
+}
@@ -0,0 +1,19 @@
+use crate::db::Database;
+use crate::error::KvError;
+use crate::scope::current_scope;
+use crate::store::KvStore;
+
+pub fn execute(key: &str, global: bool) -> Result<(), KvError> {
+    let scope = if global {
+        None
+    } else {
+        current_scope()
+    };
+
+    let db = Database::open()?;
+    let affected = db.restore(key, scope.as_deref())?;
+
+    eprintln!("restored {} entries for key '{}'", affected, key);
+
+    Ok(())
+}
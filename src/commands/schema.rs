@@ -0,0 +1,85 @@
+use crate::db::Database;
+use crate::detection::detect_input;
+use crate::error::KvError;
+use crate::scope::current_scope;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct SchemaJson {
+    pattern: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+    schema: serde_json::Value,
+    created_at: String,
+}
+
+fn scope(global: bool) -> Option<String> {
+    if global {
+        None
+    } else {
+        current_scope()
+    }
+}
+
+/// Attach a schema (file path, inline JSON, or stdin) to a key or `prefix*` pattern
+pub fn set(pattern: &str, schema: Option<&str>, global: bool) -> Result<(), KvError> {
+    let input = detect_input(schema, false)?;
+    let schema: serde_json::Value = serde_json::from_slice(input.content())
+        .map_err(|e| KvError::InvalidSchema(format!("not valid JSON: {}", e)))?;
+
+    let scope = scope(global);
+    Database::open()?.set_schema(pattern, scope.as_deref(), &schema)?;
+
+    let scope_info = if global { " (global)" } else { "" };
+    eprintln!("schema set for {}{}", pattern, scope_info);
+    Ok(())
+}
+
+pub fn get(pattern: &str, global: bool) -> Result<(), KvError> {
+    let scope = scope(global);
+    let rule = Database::open()?
+        .schemas(scope.as_deref())?
+        .into_iter()
+        .find(|rule| rule.pattern == pattern)
+        .ok_or_else(|| KvError::SchemaNotFound(pattern.to_string()))?;
+
+    println!("{}", serde_json::to_string_pretty(&rule.schema).unwrap());
+    Ok(())
+}
+
+pub fn list(global: bool, json: bool) -> Result<(), KvError> {
+    let scope = scope(global);
+    let rules = Database::open()?.schemas(scope.as_deref())?;
+
+    if json {
+        let output: Vec<SchemaJson> = rules
+            .into_iter()
+            .map(|rule| SchemaJson {
+                pattern: rule.pattern,
+                scope: rule.scope,
+                schema: rule.schema,
+                created_at: rule.created_at.to_rfc3339(),
+            })
+            .collect();
+        println!("{}", serde_json::to_string(&output).unwrap());
+        return Ok(());
+    }
+
+    if rules.is_empty() {
+        eprintln!("no schemas");
+        return Ok(());
+    }
+    for rule in rules {
+        println!("{:<30} {}", rule.pattern, rule.created_at.format("%Y-%m-%d %H:%M:%S"));
+    }
+    Ok(())
+}
+
+pub fn remove(pattern: &str, global: bool) -> Result<(), KvError> {
+    let scope = scope(global);
+    if !Database::open()?.remove_schema(pattern, scope.as_deref())? {
+        return Err(KvError::SchemaNotFound(pattern.to_string()));
+    }
+    eprintln!("schema removed from {}", pattern);
+    Ok(())
+}
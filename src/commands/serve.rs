@@ -0,0 +1,25 @@
+use crate::db::Database;
+use crate::error::KvError;
+use crate::scope::current_scope;
+use crate::{http, resp};
+
+pub fn execute(
+    bind: &str,
+    token: Option<&str>,
+    resp: bool,
+    scope: Option<&str>,
+    global: bool,
+) -> Result<(), KvError> {
+    let db = Database::open()?;
+
+    if resp {
+        let scope = if global {
+            None
+        } else {
+            scope.map(str::to_string).or_else(current_scope)
+        };
+        return resp::serve(db, bind, scope);
+    }
+
+    http::serve(db, bind, token.map(str::to_string))
+}
@@ -1,11 +1,26 @@
 use crate::db::Database;
-use crate::detection::detect_input;
+use crate::detection::{detect_input, sniff_content_type};
+use crate::encoding::{decode_value, ValueEncoding};
 use crate::error::KvError;
 use crate::scope::current_scope;
-use chrono::{Duration, Utc};
+use crate::store::{KvStore, SetOptions, SetResult};
+use crate::ttl::{parse_duration, parse_expires_at, parse_ttl};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
 
-const SIZE_LIMIT: u64 = 100 * 1024 * 1024; // 100 MB
+pub const SIZE_LIMIT: u64 = 100 * 1024 * 1024; // 100 MB
 
+/// The object printed by `get --json`; its other fields are ignored
+#[derive(Deserialize)]
+struct JsonInput {
+    value: String,
+    value_encoding: Option<ValueEncoding>,
+    content_type: Option<String>,
+    original_filename: Option<String>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn execute(
     key: &str,
     value: Option<&str>,
@@ -13,10 +28,34 @@ pub fn execute(
     force: bool,
     global: bool,
     ttl: Option<&str>,
+    expires_at: Option<&str>,
+    sliding: bool,
+    content_type: Option<&str>,
+    from_json: bool,
 ) -> Result<(), KvError> {
     let input = detect_input(value, literal)?;
 
-    let content = input.content();
+    let json_input = if from_json {
+        let parsed: JsonInput = serde_json::from_slice(input.content()).map_err(|e| KvError::Decode {
+            key: key.to_string(),
+            format: "json",
+            message: e.to_string(),
+        })?;
+        let value = decode_value(key, &parsed.value, parsed.value_encoding.unwrap_or(ValueEncoding::Utf8))?;
+        Some((parsed, value))
+    } else {
+        None
+    };
+
+    let (content, detected_type, original_filename, stored_expiry) = match &json_input {
+        Some((parsed, value)) => (
+            value.as_slice(),
+            parsed.content_type.as_deref().or(Some(sniff_content_type(value))),
+            parsed.original_filename.as_deref(),
+            parsed.expires_at,
+        ),
+        None => (input.content(), input.content_type(), input.original_filename(), None),
+    };
     let size = content.len() as u64;
 
     // Check size limit
@@ -34,29 +73,32 @@ pub fn execute(
         current_scope()
     };
 
-    // Parse TTL
-    let expires_at = if let Some(ttl_str) = ttl {
-        Some(parse_ttl(ttl_str)?)
-    } else {
-        None
+    // Parse TTL or absolute expiry
+    let expires_at = match (ttl, expires_at) {
+        (Some(ttl_str), _) => Some(parse_ttl(ttl_str)?),
+        (None, Some(at)) => Some(parse_expires_at(at)?),
+        (None, None) => stored_expiry,
+    };
+    let sliding_ttl = match ttl {
+        Some(ttl_str) if sliding => Some(parse_duration(ttl_str)?),
+        _ => None,
     };
 
-    let db = Database::open()?;
-    let (version, was_saved) = db.set(
-        key,
-        content,
-        input.content_type(),
-        input.original_filename(),
-        scope.as_deref(),
+    let opts = SetOptions {
+        scope: scope.as_deref(),
+        content_type: content_type.or(detected_type),
+        original_filename,
         expires_at,
-    )?;
+        sliding_ttl,
+    };
+    let SetResult { version, was_saved } = store(key, content, &opts)?;
 
     if was_saved {
         let scope_info = if global { " (global)" } else { "" };
-        let ttl_info = if let Some(exp) = expires_at {
-            format!(" expires {}", exp.format("%Y-%m-%d %H:%M:%S UTC"))
-        } else {
-            String::new()
+        let ttl_info = match (expires_at, sliding_ttl) {
+            (Some(_), Some(_)) => format!(" expires {} after last read", ttl.unwrap_or_default()),
+            (Some(exp), None) => format!(" expires {}", exp.format("%Y-%m-%d %H:%M:%S UTC")),
+            (None, _) => String::new(),
         };
         eprintln!("set {}{} (version {}, {} bytes){}", key, scope_info, version, size, ttl_info);
     } else {
@@ -66,23 +108,15 @@ pub fn execute(
     Ok(())
 }
 
-/// Parse a TTL string like "30s", "5m", "1h", "7d" into a DateTime
-fn parse_ttl(ttl: &str) -> Result<chrono::DateTime<Utc>, KvError> {
-    let ttl = ttl.trim();
-    if ttl.is_empty() {
-        return Err(KvError::InvalidTtl("empty TTL".into()));
+/// Prefer a running daemon, falling back to opening the database directly
+fn store(key: &str, value: &[u8], opts: &SetOptions) -> Result<SetResult, KvError> {
+    // The daemon refuses payloads over the size limit, so forced writes go direct
+    #[cfg(unix)]
+    if value.len() as u64 <= SIZE_LIMIT {
+        if let Some(mut client) = crate::daemon::Client::connect() {
+            return client.set(key, value, opts);
+        }
     }
 
-    let (num_str, unit) = ttl.split_at(ttl.len() - 1);
-    let num: i64 = num_str.parse().map_err(|_| KvError::InvalidTtl(format!("invalid number in TTL: {}", ttl)))?;
-
-    let duration = match unit {
-        "s" => Duration::seconds(num),
-        "m" => Duration::minutes(num),
-        "h" => Duration::hours(num),
-        "d" => Duration::days(num),
-        _ => return Err(KvError::InvalidTtl(format!("invalid unit in TTL: {} (use s/m/h/d)", ttl))),
-    };
-
-    Ok(Utc::now() + duration)
+    Database::open()?.set(key, value, opts)
 }
@@ -1,6 +1,7 @@
 use crate::commands::list::format_size;
 use crate::db::Database;
 use crate::error::KvError;
+use crate::store::KvStore;
 use serde::Serialize;
 
 #[derive(Serialize)]
@@ -0,0 +1,45 @@
+use crate::db::Database;
+use crate::error::KvError;
+use crate::scope::current_scope;
+use chrono::Utc;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct TtlJson {
+    key: String,
+    /// Whole seconds left, or null if the key never expires
+    ttl: Option<i64>,
+    expires_at: Option<String>,
+    /// Sliding window in seconds, if reads restart the TTL
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sliding: Option<i64>,
+}
+
+/// Print how long a key has left: seconds, or -1 if it never expires
+pub fn execute(key: &str, global: bool, json: bool) -> Result<(), KvError> {
+    let scope = if global {
+        None
+    } else {
+        current_scope()
+    };
+
+    // Peek so that asking about a sliding key doesn't restart its TTL
+    let entry = Database::open()?.peek_entry(key, scope.as_deref())?;
+    let remaining = entry
+        .expires_at
+        .map(|exp| ((exp - Utc::now()).num_milliseconds().max(0) + 999) / 1000);
+
+    if json {
+        let output = TtlJson {
+            key: entry.key,
+            ttl: remaining,
+            expires_at: entry.expires_at.map(|dt| dt.to_rfc3339()),
+            sliding: entry.sliding_ttl.map(|d| d.num_seconds()),
+        };
+        println!("{}", serde_json::to_string(&output).unwrap());
+    } else {
+        println!("{}", remaining.unwrap_or(-1));
+    }
+
+    Ok(())
+}
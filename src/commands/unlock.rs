@@ -0,0 +1,22 @@
+use crate::db::Database;
+use crate::error::KvError;
+use crate::scope::current_scope;
+
+pub fn execute(name: &str, token: Option<&str>, force: bool, global: bool) -> Result<(), KvError> {
+    let scope = if global {
+        None
+    } else {
+        current_scope()
+    };
+
+    let db = Database::open()?;
+    let owner = if force { None } else { token };
+
+    if !db.release_lock(name, scope.as_deref(), owner)? {
+        return Err(KvError::LockNotHeld(name.to_string()));
+    }
+
+    eprintln!("released lock {}", name);
+
+    Ok(())
+}
@@ -0,0 +1,367 @@
+use crate::db::{Change, Database, KeyHead};
+use crate::encoding::{encode_value, ValueEncoding};
+use crate::error::KvError;
+use crate::scope::current_scope;
+use crate::store::{GetOptions, KvStore};
+use crate::ttl::parse_duration;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::{self, IsTerminal, Write};
+use std::time::Duration;
+
+/// How often to poll the change log; the query is a cheap range scan on its sequence number
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Serialize)]
+struct WatchEvent {
+    event: &'static str,
+    key: String,
+    version: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value_encoding: Option<ValueEncoding>,
+}
+
+/// Live watched keys that will expire, keyed by name: (version, expires_at)
+type Expiries = BTreeMap<String, (i64, DateTime<Utc>)>;
+
+/// Follows the change log for one key or prefix. Writes are picked up by
+/// sequence number, so in-place updates that keep the version are seen too;
+/// expiry isn't a write, so pending TTLs are tracked separately.
+struct Watcher<'a> {
+    db: &'a Database,
+    pattern: &'a str,
+    prefix: bool,
+    scope: Option<&'a str>,
+    seq: i64,
+    expiries: Expiries,
+}
+
+impl<'a> Watcher<'a> {
+    fn new(db: &'a Database, pattern: &'a str, prefix: bool, scope: Option<&'a str>) -> Result<Self, KvError> {
+        let mut watcher = Watcher {
+            db,
+            pattern,
+            prefix,
+            scope,
+            seq: db.last_change_seq()?,
+            expiries: Expiries::new(),
+        };
+        watcher.expiries = watcher.pending_expiries(&[])?;
+        Ok(watcher)
+    }
+
+    fn heads(&self) -> Result<Vec<KeyHead>, KvError> {
+        self.db.heads(self.pattern, self.prefix, self.scope)
+    }
+
+    /// TTLs still to fire for live watched keys. A key that has already expired is
+    /// only included if one of `written` touched it, so an expired write is reported.
+    fn pending_expiries(&self, written: &[Change]) -> Result<Expiries, KvError> {
+        let now = Utc::now();
+        Ok(self
+            .heads()?
+            .into_iter()
+            .filter(|head| !head.deleted)
+            .filter_map(|head| {
+                let at = head.expires_at?;
+                (at >= now || written.iter().any(|c| c.key == head.key)).then_some((head.key, (head.version, at)))
+            })
+            .collect())
+    }
+
+    /// Events since the last poll, oldest first
+    fn poll(&mut self) -> Result<Vec<WatchEvent>, KvError> {
+        let changes: Vec<Change> = self
+            .db
+            .changes(self.seq, None, self.scope, false)?
+            .into_iter()
+            .inspect(|c| self.seq = c.seq)
+            .filter(|c| c.field.is_none() && key_matches(&c.key, self.pattern, self.prefix))
+            .collect();
+        let mut events = change_events(&changes);
+
+        // Any write may have changed a TTL
+        if !changes.is_empty() {
+            self.expiries = self.pending_expiries(&changes)?;
+        }
+        events.extend(due_expiries(&mut self.expiries, Utc::now()));
+        Ok(events)
+    }
+}
+
+pub fn execute(
+    key: Option<&str>,
+    prefix: Option<&str>,
+    timeout: Option<&str>,
+    after_version: Option<i64>,
+    follow: bool,
+    global: bool,
+    json: bool,
+) -> Result<(), KvError> {
+    let scope = if global {
+        None
+    } else {
+        current_scope()
+    };
+
+    // clap guarantees exactly one of key / --prefix
+    let (pattern, is_prefix) = match (key, prefix) {
+        (Some(k), _) => (k, false),
+        (None, p) => (p.unwrap_or_default(), true),
+    };
+
+    let deadline = timeout
+        .map(parse_duration)
+        .transpose()?
+        .map(|d| Utc::now() + d);
+
+    let db = Database::open()?;
+    let mut watcher = Watcher::new(&db, pattern, is_prefix, scope.as_deref())?;
+
+    // A head already newer than --after-version counts as a change that happened while we weren't looking
+    if let Some(after) = after_version {
+        let now = Utc::now();
+        let pending: Vec<WatchEvent> = watcher
+            .heads()?
+            .iter()
+            .filter(|head| head.version > after && !head.deleted && head.expires_at.is_none_or(|e| e >= now))
+            .map(set_event)
+            .collect();
+        if !pending.is_empty() {
+            emit(&db, pending, is_prefix, scope.as_deref(), json)?;
+            if !follow {
+                return Ok(());
+            }
+        }
+    }
+
+    loop {
+        if let Some(deadline) = deadline {
+            if Utc::now() >= deadline {
+                return if follow { Ok(()) } else { Err(KvError::WatchTimeout) };
+            }
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+
+        let events = watcher.poll()?;
+        if events.is_empty() {
+            continue;
+        }
+
+        emit(&db, events, is_prefix, scope.as_deref(), json)?;
+        if !follow {
+            return Ok(());
+        }
+    }
+}
+
+fn key_matches(key: &str, pattern: &str, prefix: bool) -> bool {
+    if prefix {
+        key.starts_with(pattern)
+    } else {
+        key == pattern
+    }
+}
+
+/// Watch events for change log rows. Expire rows (TTL updates and gc of
+/// expired versions) and gc rows don't change what a reader sees, so they
+/// are left to the expiry tracking.
+fn change_events(changes: &[Change]) -> Vec<WatchEvent> {
+    changes
+        .iter()
+        .filter_map(|c| {
+            let event = match c.event.as_str() {
+                "set" => "set",
+                "delete" => "delete",
+                "restore" => "restore",
+                _ => return None,
+            };
+            Some(WatchEvent {
+                event,
+                key: c.key.clone(),
+                version: c.version.unwrap_or_default(),
+                value: None,
+                value_encoding: None,
+            })
+        })
+        .collect()
+}
+
+/// Remove and report keys whose TTL has passed by `now`
+fn due_expiries(expiries: &mut Expiries, now: DateTime<Utc>) -> Vec<WatchEvent> {
+    let due: Vec<String> = expiries
+        .iter()
+        .filter(|(_, (_, at))| *at <= now)
+        .map(|(key, _)| key.clone())
+        .collect();
+    due.into_iter()
+        .filter_map(|key| expiries.remove_entry(&key))
+        .map(|(key, (version, _))| WatchEvent {
+            event: "expire",
+            key,
+            version,
+            value: None,
+            value_encoding: None,
+        })
+        .collect()
+}
+
+fn set_event(head: &KeyHead) -> WatchEvent {
+    WatchEvent {
+        event: "set",
+        key: head.key.clone(),
+        version: head.version,
+        value: None,
+        value_encoding: None,
+    }
+}
+
+fn emit(
+    db: &Database,
+    events: Vec<WatchEvent>,
+    prefix: bool,
+    scope: Option<&str>,
+    json: bool,
+) -> Result<(), KvError> {
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+
+    for mut event in events {
+        // The version may already be gone (gc, max_versions, hard delete), in
+        // which case the event is still reported, just without its value
+        let value = if matches!(event.event, "set" | "restore") {
+            let opts = GetOptions {
+                scope,
+                version: Some(event.version),
+            };
+            match db.get(&event.key, &opts) {
+                Ok(entry) => Some(entry.value),
+                Err(KvError::KeyNotFound(_) | KvError::VersionNotFound { .. }) => None,
+                Err(e) => return Err(e),
+            }
+        } else {
+            None
+        };
+
+        if json {
+            if let Some((value, encoding)) = value.map(|v| encode_value(&v, ValueEncoding::Utf8)) {
+                event.value = Some(value);
+                event.value_encoding = Some(encoding);
+            }
+            writeln!(handle, "{}", serde_json::to_string(&event).unwrap())?;
+        } else if prefix {
+            writeln!(handle, "{} {} {}", event.event, event.key, event.version)?;
+        } else if let Some(value) = value {
+            handle.write_all(&value)?;
+            // Add newline for terminal display, but not when piping (preserves exact data)
+            if io::stdout().is_terminal() && !value.ends_with(b"\n") {
+                handle.write_all(b"\n")?;
+            }
+        } else if event.event == "set" {
+            eprintln!("{} set (version {}), value no longer available", event.key, event.version);
+        } else {
+            eprintln!("{} {}d (version {})", event.key, event.event, event.version);
+        }
+        handle.flush()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{DeleteOptions, IncrOptions, SetOptions};
+
+    fn events(watcher: &mut Watcher) -> Vec<(&'static str, String, i64)> {
+        watcher.poll().unwrap().into_iter().map(|e| (e.event, e.key, e.version)).collect()
+    }
+
+    fn event(event: &'static str, key: &str, version: i64) -> (&'static str, String, i64) {
+        (event, key.to_string(), version)
+    }
+
+    #[test]
+    fn test_change_events() {
+        let change = |seq: i64, event: &str, version: Option<i64>| Change {
+            seq,
+            event: event.to_string(),
+            key: "k".to_string(),
+            scope: None,
+            version,
+            field: None,
+            created_at: Utc::now(),
+        };
+        let changes = [
+            change(1, "set", Some(1)),
+            change(2, "expire", Some(1)),
+            change(3, "delete", Some(1)),
+            change(4, "restore", Some(1)),
+            change(5, "gc", Some(1)),
+            change(6, "delete", None),
+        ];
+        let events: Vec<_> = change_events(&changes).into_iter().map(|e| (e.event, e.version)).collect();
+        assert_eq!(events, [("set", 1), ("delete", 1), ("restore", 1), ("delete", 0)]);
+    }
+
+    #[test]
+    fn test_due_expiries() {
+        let now = Utc::now();
+        let mut expiries = Expiries::new();
+        expiries.insert("a".into(), (1, now - chrono::Duration::seconds(1)));
+        expiries.insert("b".into(), (2, now + chrono::Duration::hours(1)));
+
+        let due: Vec<_> = due_expiries(&mut expiries, now).into_iter().map(|e| (e.event, e.key)).collect();
+        assert_eq!(due, [("expire", "a".to_string())]);
+        assert!(due_expiries(&mut expiries, now).is_empty());
+        assert_eq!(expiries.len(), 1);
+    }
+
+    #[test]
+    fn test_watch_sees_writes_that_keep_the_version() {
+        let db = Database::open_in_memory().unwrap();
+        db.set("c", b"1", &SetOptions::default()).unwrap();
+        db.set("other", b"x", &SetOptions::default()).unwrap();
+        let mut watcher = Watcher::new(&db, "c", false, None).unwrap();
+        assert!(events(&mut watcher).is_empty());
+
+        db.incr("c", 1, &IncrOptions { scope: None, in_place: true }).unwrap();
+        assert_eq!(events(&mut watcher), [event("set", "c", 1)]);
+
+        db.append("c", b"0", None, None, None, true).unwrap();
+        db.set("other", b"y", &SetOptions::default()).unwrap();
+        assert_eq!(events(&mut watcher), [event("set", "c", 1)]);
+
+        db.delete("c", &DeleteOptions::default()).unwrap();
+        db.restore("c", None).unwrap();
+        assert_eq!(events(&mut watcher), [event("delete", "c", 1), event("restore", "c", 1)]);
+
+        db.set_expiry("c", Some(Utc::now() - chrono::Duration::seconds(1)), None).unwrap();
+        assert_eq!(events(&mut watcher), [event("expire", "c", 1)]);
+        assert!(events(&mut watcher).is_empty());
+    }
+
+    #[test]
+    fn test_watch_prefix_and_ttl() {
+        let db = Database::open_in_memory().unwrap();
+        let mut watcher = Watcher::new(&db, "job/", true, Some("s")).unwrap();
+
+        let ttl = SetOptions {
+            scope: Some("s"),
+            expires_at: Some(Utc::now() + chrono::Duration::milliseconds(200)),
+            ..Default::default()
+        };
+        db.set("job/1", b"a", &ttl).unwrap();
+        db.set("job/2", b"b", &SetOptions::default()).unwrap();
+        db.set("other", b"c", &ttl).unwrap();
+        assert_eq!(events(&mut watcher), [event("set", "job/1", 1)]);
+        assert!(events(&mut watcher).is_empty());
+
+        std::thread::sleep(std::time::Duration::from_millis(250));
+        assert_eq!(events(&mut watcher), [event("expire", "job/1", 1)]);
+    }
+}
@@ -0,0 +1,187 @@
+use crate::commands::lock::{acquire, new_token, parse_lease, renew_lease};
+use crate::db::Database;
+use crate::error::KvError;
+use crate::scope::current_scope;
+use crate::ttl::{format_duration, parse_duration};
+use chrono::Duration;
+use std::process::{Command, ExitStatus};
+use std::sync::mpsc;
+use std::thread;
+
+/// Shortest lease that still leaves room to renew at a third of the TTL
+const MIN_TTL: Duration = Duration::seconds(3);
+
+/// How often to check on the child while waiting for it to exit
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+pub fn execute(
+    name: &str,
+    ttl: &str,
+    wait: Option<&str>,
+    global: bool,
+    command: &[String],
+) -> Result<(), KvError> {
+    let scope = if global {
+        None
+    } else {
+        current_scope()
+    };
+
+    let ttl = parse_ttl(ttl)?;
+    let wait = wait.map(parse_duration).transpose()?;
+
+    let status = run(Database::open, name, scope.as_deref(), ttl, wait, command)?;
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+
+    Ok(())
+}
+
+/// Parse the lease, which must be long enough to renew at a third of it
+fn parse_ttl(ttl: &str) -> Result<Duration, KvError> {
+    let ttl = parse_lease(ttl)?;
+    if ttl < MIN_TTL {
+        return Err(KvError::InvalidTtl(format!(
+            "{} is too short to renew, use at least {}",
+            format_duration(ttl),
+            format_duration(MIN_TTL)
+        )));
+    }
+    Ok(ttl)
+}
+
+/// Run `command` while holding the lock, renewing it from a second connection
+/// made with `open`. The child is killed if the lease can't be renewed.
+fn run<F>(
+    open: F,
+    name: &str,
+    scope: Option<&str>,
+    ttl: Duration,
+    wait: Option<Duration>,
+    command: &[String],
+) -> Result<ExitStatus, KvError>
+where
+    F: Fn() -> Result<Database, KvError> + Send + 'static,
+{
+    let token = new_token();
+    let db = open()?;
+    acquire(&db, name, scope, &token, ttl, wait)?;
+
+    // Keep the lease alive while the child runs by renewing at a third of the TTL.
+    // The renewer drops `lost_tx` when it exits, so a disconnect while the child
+    // is still running means the lease can no longer be guaranteed.
+    let (stop_tx, stop_rx) = mpsc::channel::<()>();
+    let (lost_tx, lost_rx) = mpsc::channel::<()>();
+    let renewer = {
+        let name = name.to_string();
+        let scope = scope.map(str::to_string);
+        let token = token.clone();
+        let interval = (ttl / 3).to_std().unwrap_or_default();
+        thread::spawn(move || -> Result<(), KvError> {
+            let _lost_tx = lost_tx;
+            let db = open()?;
+            while let Err(mpsc::RecvTimeoutError::Timeout) = stop_rx.recv_timeout(interval) {
+                renew_lease(&db, &name, scope.as_deref(), &token, ttl)?;
+            }
+            Ok(())
+        })
+    };
+
+    let child = Command::new(&command[0])
+        .args(&command[1..])
+        .env("KV_LOCK_TOKEN", &token)
+        .spawn();
+    let status = child.and_then(|mut child| loop {
+        if let Some(status) = child.try_wait()? {
+            break Ok(Some(status));
+        }
+        if let Err(mpsc::RecvTimeoutError::Disconnected) = lost_rx.recv_timeout(POLL_INTERVAL) {
+            child.kill()?;
+            child.wait()?;
+            break Ok(None);
+        }
+    });
+
+    let _ = stop_tx.send(());
+    let renewed = renewer.join().unwrap_or(Ok(()));
+    db.release_lock(name, scope, Some(&token))?;
+
+    let Some(status) = status? else {
+        // The child was killed because the lease could not be renewed
+        return Err(match renewed {
+            Err(e) => e,
+            Ok(()) => KvError::LockNotHeld(name.to_string()),
+        });
+    };
+    if let Err(e) = renewed {
+        eprintln!("warning: failed to renew lock {}: {}", name, e);
+    }
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_db(name: &str) -> (PathBuf, impl Fn() -> Result<Database, KvError> + Clone + Send + 'static) {
+        let dir = std::env::temp_dir().join(format!("kv-test-with-lock-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("kv.db");
+        let _ = std::fs::remove_file(&path);
+        let open_path = path.clone();
+        (dir, move || Database::open_at(&open_path))
+    }
+
+    fn command(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_ttl() {
+        assert_eq!(parse_ttl("30s").unwrap(), Duration::seconds(30));
+        for ttl in ["0s", "1s", "2s"] {
+            assert!(matches!(parse_ttl(ttl), Err(KvError::InvalidTtl(_))), "{}", ttl);
+        }
+    }
+
+    #[test]
+    fn test_runs_command_and_releases_lock() {
+        let (dir, open) = temp_db("run");
+        let ttl = Duration::seconds(30);
+
+        let status = run(open.clone(), "m", Some("s"), ttl, None, &command(&["sh", "-c", "exit 3"])).unwrap();
+        assert_eq!(status.code(), Some(3));
+        assert!(run(open.clone(), "m", Some("s"), ttl, None, &command(&["true"])).unwrap().success());
+        assert!(open().unwrap().acquire_lock("m", Some("s"), "other", ttl).unwrap());
+
+        // Held by someone else, so the command never runs
+        let marker = dir.join("ran");
+        let touch = command(&["touch", marker.to_str().unwrap()]);
+        assert!(matches!(run(open, "m", Some("s"), ttl, None, &touch), Err(KvError::LockHeld(_))));
+        assert!(!marker.exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_kills_command_when_lease_is_lost() {
+        let (dir, open) = temp_db("lost");
+        let runner = {
+            let open = open.clone();
+            thread::spawn(move || run(open, "m", None, MIN_TTL, None, &command(&["sleep", "30"])))
+        };
+
+        let db = open().unwrap();
+        thread::sleep(std::time::Duration::from_millis(500));
+        assert!(!db.acquire_lock("m", None, "other", MIN_TTL).unwrap());
+        assert!(db.release_lock("m", None, None).unwrap());
+
+        let started = std::time::Instant::now();
+        assert!(matches!(runner.join().unwrap(), Err(KvError::LockNotHeld(_))));
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
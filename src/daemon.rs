@@ -0,0 +1,569 @@
+//! Unix socket daemon that keeps one `Database` open for low-latency access.
+//!
+//! Each message is a single JSON header line, followed by `len` raw payload
+//! bytes when the header carries a value. Connections may send any number of
+//! requests. The CLI connects transparently when the socket is live and falls
+//! back to opening the database itself otherwise (or when `KV_NO_DAEMON` is set).
+
+use crate::commands::set::SIZE_LIMIT;
+use crate::db::{Database, Entry};
+use crate::error::KvError;
+use crate::store::{DeleteOptions, GetOptions, IncrOptions, KvStore, SetOptions, SetResult};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Request {
+    op: String,
+    key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original_filename: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<DateTime<Utc>>,
+    /// Sliding expiry window in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sliding_ttl: Option<i64>,
+    hard: bool,
+    delta: i64,
+    in_place: bool,
+    len: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Response {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_kind: Option<ErrorKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entry: Option<EntryHeader>,
+    version: i64,
+    saved: bool,
+    count: i64,
+    len: usize,
+}
+
+/// Which `KvError` the server raised, with its fields, so the client returns the
+/// same variant it would get without a daemon. Errors that can't cross the
+/// socket (I/O, encoding) arrive as `KvError::Daemon` with the message only.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum ErrorKind {
+    KeyNotFound { key: String },
+    VersionNotFound { key: String, version: i64 },
+    Database { message: String },
+    SizeLimitExceeded { size: u64, limit: u64 },
+    InvalidTtl { message: String },
+    NotAnInteger { key: String },
+    IntegerOverflow { key: String },
+    ListEmpty { key: String },
+    FieldNotFound { key: String, field: String },
+    LockHeld { name: String },
+    LockNotHeld { name: String },
+    WatchTimeout,
+    VersionMismatch { key: String, expected: i64, actual: i64 },
+    InvalidQuery { message: String },
+    PatchFailed { message: String },
+    InvalidSchema { message: String },
+    SchemaNotFound { pattern: String },
+    PolicyNotFound { pattern: String },
+    ValidationFailed { key: String, violations: Vec<String> },
+}
+
+impl ErrorKind {
+    fn of(err: KvError) -> Option<Self> {
+        Some(match err {
+            KvError::KeyNotFound(key) => ErrorKind::KeyNotFound { key },
+            KvError::VersionNotFound { key, version } => ErrorKind::VersionNotFound { key, version },
+            KvError::Database(message) => ErrorKind::Database { message },
+            KvError::SizeLimitExceeded { size, limit } => ErrorKind::SizeLimitExceeded { size, limit },
+            KvError::InvalidTtl(message) => ErrorKind::InvalidTtl { message },
+            KvError::NotAnInteger(key) => ErrorKind::NotAnInteger { key },
+            KvError::IntegerOverflow(key) => ErrorKind::IntegerOverflow { key },
+            KvError::ListEmpty(key) => ErrorKind::ListEmpty { key },
+            KvError::FieldNotFound { key, field } => ErrorKind::FieldNotFound { key, field },
+            KvError::LockHeld(name) => ErrorKind::LockHeld { name },
+            KvError::LockNotHeld(name) => ErrorKind::LockNotHeld { name },
+            KvError::WatchTimeout => ErrorKind::WatchTimeout,
+            KvError::VersionMismatch { key, expected, actual } => ErrorKind::VersionMismatch { key, expected, actual },
+            KvError::InvalidQuery(message) => ErrorKind::InvalidQuery { message },
+            KvError::PatchFailed(message) => ErrorKind::PatchFailed { message },
+            KvError::InvalidSchema(message) => ErrorKind::InvalidSchema { message },
+            KvError::SchemaNotFound(pattern) => ErrorKind::SchemaNotFound { pattern },
+            KvError::PolicyNotFound(pattern) => ErrorKind::PolicyNotFound { pattern },
+            KvError::ValidationFailed { key, violations } => ErrorKind::ValidationFailed { key, violations },
+            KvError::Io(_) | KvError::Daemon(_) | KvError::Encode { .. } | KvError::Decode { .. } => return None,
+        })
+    }
+
+    fn into_error(self) -> KvError {
+        match self {
+            ErrorKind::KeyNotFound { key } => KvError::KeyNotFound(key),
+            ErrorKind::VersionNotFound { key, version } => KvError::VersionNotFound { key, version },
+            ErrorKind::Database { message } => KvError::Database(message),
+            ErrorKind::SizeLimitExceeded { size, limit } => KvError::SizeLimitExceeded { size, limit },
+            ErrorKind::InvalidTtl { message } => KvError::InvalidTtl(message),
+            ErrorKind::NotAnInteger { key } => KvError::NotAnInteger(key),
+            ErrorKind::IntegerOverflow { key } => KvError::IntegerOverflow(key),
+            ErrorKind::ListEmpty { key } => KvError::ListEmpty(key),
+            ErrorKind::FieldNotFound { key, field } => KvError::FieldNotFound { key, field },
+            ErrorKind::LockHeld { name } => KvError::LockHeld(name),
+            ErrorKind::LockNotHeld { name } => KvError::LockNotHeld(name),
+            ErrorKind::WatchTimeout => KvError::WatchTimeout,
+            ErrorKind::VersionMismatch { key, expected, actual } => KvError::VersionMismatch { key, expected, actual },
+            ErrorKind::InvalidQuery { message } => KvError::InvalidQuery(message),
+            ErrorKind::PatchFailed { message } => KvError::PatchFailed(message),
+            ErrorKind::InvalidSchema { message } => KvError::InvalidSchema(message),
+            ErrorKind::SchemaNotFound { pattern } => KvError::SchemaNotFound(pattern),
+            ErrorKind::PolicyNotFound { pattern } => KvError::PolicyNotFound(pattern),
+            ErrorKind::ValidationFailed { key, violations } => KvError::ValidationFailed { key, violations },
+        }
+    }
+}
+
+/// Everything in an `Entry` except the value, which travels as the payload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EntryHeader {
+    id: i64,
+    key: String,
+    version: i64,
+    content_type: Option<String>,
+    original_filename: Option<String>,
+    size_bytes: i64,
+    created_at: DateTime<Utc>,
+    deleted_at: Option<DateTime<Utc>>,
+    scope: Option<String>,
+    expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    sliding_ttl: Option<i64>,
+}
+
+/// Default socket location, next to the database file
+pub fn socket_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join("kv").join("kv.sock"))
+}
+
+/// Listen on `path` until the process is killed. A stale socket file left by a
+/// crashed daemon is replaced; a live one is an error.
+pub fn serve(db: Database, path: &Path) -> Result<(), KvError> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(KvError::Daemon(format!("daemon already running on {}", path.display())));
+        }
+        std::fs::remove_file(path)?;
+    }
+
+    let listener = UnixListener::bind(path)?;
+    eprintln!("listening on {}", path.display());
+
+    let db = Arc::new(Mutex::new(db));
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("warning: accept failed: {}", e);
+                continue;
+            }
+        };
+        let db = Arc::clone(&db);
+        thread::spawn(move || {
+            if let Err(e) = handle_connection(stream, &db) {
+                eprintln!("warning: connection error: {}", e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+fn handle_connection(stream: UnixStream, db: &Mutex<Database>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    while let Some((request, payload)) = read_message::<Request, _>(&mut reader, SIZE_LIMIT)? {
+        let (response, payload) = {
+            let db = db.lock().unwrap_or_else(|e| e.into_inner());
+            dispatch(&db, &request, payload)
+        };
+        write_message(&mut writer, &response, &payload)?;
+    }
+
+    Ok(())
+}
+
+fn dispatch(db: &Database, req: &Request, payload: Vec<u8>) -> (Response, Vec<u8>) {
+    let scope = req.scope.as_deref();
+    let result = match req.op.as_str() {
+        "get" => db
+            .get(
+                &req.key,
+                &GetOptions {
+                    scope,
+                    version: req.version,
+                },
+            )
+            .map(|entry| {
+            let (header, value) = split_entry(entry);
+            (
+                Response {
+                    entry: Some(header),
+                    ..Default::default()
+                },
+                value,
+            )
+        }),
+        "set" => db
+            .set(
+                &req.key,
+                &payload,
+                &SetOptions {
+                    scope,
+                    content_type: req.content_type.as_deref(),
+                    original_filename: req.original_filename.as_deref(),
+                    expires_at: req.expires_at,
+                    sliding_ttl: req.sliding_ttl.map(chrono::Duration::seconds),
+                },
+            )
+            .map(|result| {
+                (
+                    Response {
+                        version: result.version,
+                        saved: result.was_saved,
+                        ..Default::default()
+                    },
+                    Vec::new(),
+                )
+            }),
+        "delete" => db.delete(&req.key, &DeleteOptions { scope, hard: req.hard }).map(|affected| {
+            (
+                Response {
+                    count: affected as i64,
+                    ..Default::default()
+                },
+                Vec::new(),
+            )
+        }),
+        "incr" => db
+            .incr(
+                &req.key,
+                req.delta,
+                &IncrOptions {
+                    scope,
+                    in_place: req.in_place,
+                },
+            )
+            .map(|value| {
+            (
+                Response {
+                    count: value,
+                    ..Default::default()
+                },
+                Vec::new(),
+            )
+        }),
+        "ping" => Ok((Response::default(), Vec::new())),
+        other => Err(KvError::Daemon(format!("unknown op: {}", other))),
+    };
+
+    result.unwrap_or_else(|e| {
+        (
+            Response {
+                error: Some(e.to_string()),
+                error_kind: ErrorKind::of(e),
+                ..Default::default()
+            },
+            Vec::new(),
+        )
+    })
+}
+
+fn split_entry(entry: Entry) -> (EntryHeader, Vec<u8>) {
+    (
+        EntryHeader {
+            id: entry.id,
+            key: entry.key,
+            version: entry.version,
+            content_type: entry.content_type,
+            original_filename: entry.original_filename,
+            size_bytes: entry.size_bytes,
+            created_at: entry.created_at,
+            deleted_at: entry.deleted_at,
+            scope: entry.scope,
+            expires_at: entry.expires_at,
+            sliding_ttl: entry.sliding_ttl.map(|d| d.num_seconds()),
+        },
+        entry.value,
+    )
+}
+
+/// Headers that announce the length of the payload following them
+trait Framed {
+    fn payload_len(&self) -> usize;
+    fn set_payload_len(&mut self, len: usize);
+}
+
+impl Framed for Request {
+    fn payload_len(&self) -> usize {
+        self.len
+    }
+    fn set_payload_len(&mut self, len: usize) {
+        self.len = len;
+    }
+}
+
+impl Framed for Response {
+    fn payload_len(&self) -> usize {
+        self.len
+    }
+    fn set_payload_len(&mut self, len: usize) {
+        self.len = len;
+    }
+}
+
+/// Read one header line plus its payload. Returns None on a clean EOF.
+/// Payloads announced as longer than `max_len` are rejected before reading them.
+fn read_message<T, R>(reader: &mut R, max_len: u64) -> io::Result<Option<(T, Vec<u8>)>>
+where
+    T: for<'de> Deserialize<'de> + Framed,
+    R: BufRead,
+{
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    let header: T = serde_json::from_str(&line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let len = header.payload_len() as u64;
+    if len > max_len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("payload of {} bytes exceeds the {} byte limit", len, max_len),
+        ));
+    }
+    let mut payload = Vec::new();
+    if reader.take(len).read_to_end(&mut payload)? as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(Some((header, payload)))
+}
+
+fn write_message<T, W>(writer: &mut W, header: &T, payload: &[u8]) -> io::Result<()>
+where
+    T: Serialize + Framed + Clone,
+    W: Write,
+{
+    let mut header = header.clone();
+    header.set_payload_len(payload.len());
+    let mut line = serde_json::to_vec(&header).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    line.push(b'\n');
+    line.extend_from_slice(payload);
+    writer.write_all(&line)?;
+    writer.flush()
+}
+
+/// Connection to a running daemon, mirroring the `Database` methods the CLI hot path uses
+pub struct Client {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl Client {
+    /// Connect to the default socket, or None if no daemon is running
+    pub fn connect() -> Option<Self> {
+        if std::env::var_os("KV_NO_DAEMON").is_some() {
+            return None;
+        }
+        Self::connect_to(&socket_path()?).ok()
+    }
+
+    pub fn connect_to(path: &Path) -> io::Result<Self> {
+        let stream = UnixStream::connect(path)?;
+        Ok(Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        })
+    }
+
+    fn call(&mut self, request: Request, payload: &[u8]) -> Result<(Response, Vec<u8>), KvError> {
+        write_message(&mut self.writer, &request, payload)?;
+        let (response, payload) = read_message::<Response, _>(&mut self.reader, u64::MAX)?
+            .ok_or_else(|| KvError::Daemon("daemon closed the connection".into()))?;
+        match response.error {
+            Some(message) => Err(response
+                .error_kind
+                .map(ErrorKind::into_error)
+                .unwrap_or(KvError::Daemon(message))),
+            None => Ok((response, payload)),
+        }
+    }
+
+    pub fn ping(&mut self) -> Result<(), KvError> {
+        self.call(
+            Request {
+                op: "ping".into(),
+                ..Default::default()
+            },
+            &[],
+        )
+        .map(|_| ())
+    }
+
+    pub fn get(&mut self, key: &str, opts: &GetOptions) -> Result<Entry, KvError> {
+        let (response, value) = self.call(
+            Request {
+                op: "get".into(),
+                key: key.to_string(),
+                scope: opts.scope.map(str::to_string),
+                version: opts.version,
+                ..Default::default()
+            },
+            &[],
+        )?;
+        let h = response
+            .entry
+            .ok_or_else(|| KvError::Daemon("malformed get response".into()))?;
+        Ok(Entry {
+            id: h.id,
+            key: h.key,
+            value,
+            version: h.version,
+            content_type: h.content_type,
+            original_filename: h.original_filename,
+            size_bytes: h.size_bytes,
+            created_at: h.created_at,
+            deleted_at: h.deleted_at,
+            scope: h.scope,
+            expires_at: h.expires_at,
+            sliding_ttl: h.sliding_ttl.map(chrono::Duration::seconds),
+        })
+    }
+
+    pub fn set(&mut self, key: &str, value: &[u8], opts: &SetOptions) -> Result<SetResult, KvError> {
+        let (response, _) = self.call(
+            Request {
+                op: "set".into(),
+                key: key.to_string(),
+                scope: opts.scope.map(str::to_string),
+                content_type: opts.content_type.map(str::to_string),
+                original_filename: opts.original_filename.map(str::to_string),
+                expires_at: opts.expires_at,
+                sliding_ttl: opts.sliding_ttl.map(|d| d.num_seconds()),
+                ..Default::default()
+            },
+            value,
+        )?;
+        Ok(SetResult {
+            version: response.version,
+            was_saved: response.saved,
+        })
+    }
+
+    pub fn delete(&mut self, key: &str, opts: &DeleteOptions) -> Result<u64, KvError> {
+        let (response, _) = self.call(
+            Request {
+                op: "delete".into(),
+                key: key.to_string(),
+                scope: opts.scope.map(str::to_string),
+                hard: opts.hard,
+                ..Default::default()
+            },
+            &[],
+        )?;
+        Ok(response.count as u64)
+    }
+
+    pub fn incr(&mut self, key: &str, delta: i64, opts: &IncrOptions) -> Result<i64, KvError> {
+        let (response, _) = self.call(
+            Request {
+                op: "incr".into(),
+                key: key.to_string(),
+                scope: opts.scope.map(str::to_string),
+                delta,
+                in_place: opts.in_place,
+                ..Default::default()
+            },
+            &[],
+        )?;
+        Ok(response.count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_round_trip() {
+        let path = std::env::temp_dir().join(format!("kv-daemon-test-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let server_path = path.clone();
+        thread::spawn(move || serve(Database::open_in_memory().unwrap(), &server_path));
+        let mut client = loop {
+            if let Ok(c) = Client::connect_to(&path) {
+                break c;
+            }
+            thread::sleep(std::time::Duration::from_millis(10));
+        };
+
+        client.ping().unwrap();
+        let binary = [0u8, 159, 146, 150, b'\n'];
+        let scoped = GetOptions {
+            scope: Some("s"),
+            ..Default::default()
+        };
+        let set = SetOptions {
+            scope: Some("s"),
+            ..Default::default()
+        };
+        let result = client.set("k", &binary, &set).unwrap();
+        assert_eq!((result.version, result.was_saved), (1, true));
+        assert_eq!(client.get("k", &scoped).unwrap().value, binary);
+        assert_eq!(client.incr("n", 3, &IncrOptions::default()).unwrap(), 3);
+        let delete = DeleteOptions {
+            scope: Some("s"),
+            ..Default::default()
+        };
+        assert_eq!(client.delete("k", &delete).unwrap(), 1);
+
+        // Errors come back as the same variants the store raised
+        let err = client.get("k", &scoped).unwrap_err();
+        assert_eq!(err.to_string(), "key not found: k");
+        assert!(matches!(err, KvError::KeyNotFound(key) if key == "k"));
+        let old = GetOptions { version: Some(9), ..Default::default() };
+        assert!(matches!(client.get("n", &old), Err(KvError::VersionNotFound { version: 9, .. })));
+        client.set("text", b"abc", &SetOptions::default()).unwrap();
+        assert!(matches!(client.incr("text", 1, &IncrOptions::default()), Err(KvError::NotAnInteger(_))));
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_read_message_rejects_oversized_payloads() {
+        let header = format!("{{\"op\":\"set\",\"key\":\"k\",\"len\":{}}}\n", usize::MAX);
+        let err = read_message::<Request, _>(&mut header.as_bytes(), SIZE_LIMIT).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // A short payload is an error rather than a partial message
+        let mut short = &b"{\"op\":\"set\",\"key\":\"k\",\"len\":4}\nab"[..];
+        let err = read_message::<Request, _>(&mut short, SIZE_LIMIT).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut ok = &b"{\"op\":\"set\",\"key\":\"k\",\"len\":2}\nab"[..];
+        let (_, payload) = read_message::<Request, _>(&mut ok, SIZE_LIMIT).unwrap().unwrap();
+        assert_eq!(payload, b"ab");
+    }
+}
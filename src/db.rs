@@ -1,7 +1,18 @@
 use crate::error::KvError;
+use crate::patch::Patch;
+use crate::query::{Filter, Op};
+pub use crate::store::{Entry, GcResult, KeySummary, ScopeStats, Stats};
+use crate::store::{
+    DeleteOptions, GcOptions, GetOptions, HistoryOptions, IncrOptions, KvStore, ListOptions, SetOptions, SetResult,
+};
 use chrono::{DateTime, Utc};
-use rusqlite::{params, Connection, OptionalExtension};
-use std::path::PathBuf;
+use rusqlite::{params, Connection, OptionalExtension, Transaction, TransactionBehavior};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Stored in `PRAGMA user_version`. Bump it whenever a schema constant or
+/// migration below changes, so existing databases pick the change up.
+const SCHEMA_VERSION: i64 = 2;
 
 const SCHEMA_V1: &str = r#"
 CREATE TABLE IF NOT EXISTS entries (
@@ -19,6 +30,87 @@ CREATE INDEX IF NOT EXISTS idx_key_active ON entries(key) WHERE deleted_at IS NU
 CREATE INDEX IF NOT EXISTS idx_created ON entries(created_at);
 "#;
 
+const SCHEMA_LISTS: &str = r#"
+CREATE TABLE IF NOT EXISTS list_items (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    key TEXT NOT NULL,
+    scope TEXT,
+    value BLOB NOT NULL,
+    size_bytes INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    expires_at TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_list_key_scope ON list_items(key, scope, id);
+"#;
+
+const SCHEMA_HASHES: &str = r#"
+CREATE TABLE IF NOT EXISTS hash_fields (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    key TEXT NOT NULL,
+    scope TEXT,
+    field TEXT NOT NULL,
+    value BLOB NOT NULL,
+    version INTEGER NOT NULL,
+    size_bytes INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    deleted_at TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_hash_key_field ON hash_fields(key, scope, field, version);
+"#;
+
+const SCHEMA_CHANGES: &str = r#"
+CREATE TABLE IF NOT EXISTS changes (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    event TEXT NOT NULL,
+    key TEXT NOT NULL,
+    scope TEXT,
+    version INTEGER,
+    created_at TEXT NOT NULL
+);
+"#;
+
+const SCHEMA_LOCKS: &str = r#"
+CREATE TABLE IF NOT EXISTS locks (
+    name TEXT NOT NULL,
+    scope TEXT,
+    owner TEXT NOT NULL,
+    acquired_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_locks_name ON locks(name, scope);
+"#;
+
+const SCHEMA_VALUE_SCHEMAS: &str = r#"
+CREATE TABLE IF NOT EXISTS value_schemas (
+    pattern TEXT NOT NULL,
+    scope TEXT,
+    schema TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_value_schemas_scope ON value_schemas(scope);
+"#;
+
+const SCHEMA_POLICIES: &str = r#"
+CREATE TABLE IF NOT EXISTS policies (
+    pattern TEXT NOT NULL,
+    scope TEXT,
+    default_ttl INTEGER,
+    max_versions INTEGER,
+    created_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_policies_scope ON policies(scope);
+"#;
+
+const SCHEMA_META: &str = r#"
+CREATE TABLE IF NOT EXISTS meta (
+    name TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+"#;
+
+/// Writes check whether an automatic GC is due at most this often
+const AUTO_GC_CHECK_INTERVAL: chrono::Duration = chrono::Duration::minutes(1);
+
 const SCHEMA_V2_MIGRATIONS: &[&str] = &[
     "ALTER TABLE entries ADD COLUMN scope TEXT",
     "ALTER TABLE entries ADD COLUMN expires_at TEXT",
@@ -28,75 +120,222 @@ const SCHEMA_V2_MIGRATIONS: &[&str] = &[
     "CREATE INDEX IF NOT EXISTS idx_expires ON entries(expires_at) WHERE expires_at IS NOT NULL",
 ];
 
-#[derive(Debug, Clone)]
-#[allow(dead_code)]
-pub struct Entry {
-    pub id: i64,
+/// Newest row of a key, used to detect changes between snapshots
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyHead {
     pub key: String,
+    pub version: i64,
+    pub deleted: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Latest version of a single field in a hash-typed key
+#[derive(Debug, Clone)]
+pub struct HashField {
+    pub field: String,
     pub value: Vec<u8>,
     pub version: i64,
-    pub content_type: Option<String>,
-    pub original_filename: Option<String>,
-    pub size_bytes: i64,
     pub created_at: DateTime<Utc>,
-    pub deleted_at: Option<DateTime<Utc>>,
-    pub scope: Option<String>,
-    pub expires_at: Option<DateTime<Utc>>,
 }
 
+/// One row of the append-only change log
 #[derive(Debug, Clone)]
-pub struct KeySummary {
+pub struct Change {
+    pub seq: i64,
+    pub event: String,
     pub key: String,
-    pub versions: i64,
-    pub total_size: i64,
-    pub last_updated: DateTime<Utc>,
     pub scope: Option<String>,
+    pub version: Option<i64>,
+    /// Set when the event is for one field of a hash
+    pub field: Option<String>,
+    pub created_at: DateTime<Utc>,
 }
 
-/// Statistics about the key-value store
+/// A JSON Schema attached to a key or `prefix*` pattern
 #[derive(Debug, Clone)]
-pub struct Stats {
-    pub total_size: i64,
-    pub total_entries: i64,
-    pub active_keys: i64,
-    pub deleted_keys: i64,
-    pub expired_keys: i64,
-    pub oldest_key: Option<String>,
-    pub oldest_date: Option<DateTime<Utc>>,
-    pub largest_key: Option<String>,
-    pub largest_size: i64,
-    pub scopes: Vec<ScopeStats>,
+pub struct SchemaRule {
+    pub pattern: String,
+    pub scope: Option<String>,
+    pub schema: serde_json::Value,
+    pub created_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone)]
-pub struct ScopeStats {
+/// Write defaults for keys matching a key, `prefix*` or `*` (whole scope) pattern
+#[derive(Debug, Clone, PartialEq)]
+pub struct Policy {
+    pub pattern: String,
     pub scope: Option<String>,
-    pub size: i64,
-    pub keys: i64,
+    /// Expiry given to new versions written without one
+    pub default_ttl: Option<chrono::Duration>,
+    /// Older versions beyond this many are removed after each write
+    pub max_versions: Option<usize>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// When writes trigger an automatic GC of expired and soft-deleted entries.
+/// Any threshold that is reached triggers one; `None` disables that threshold.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutoGcConfig {
+    pub enabled: bool,
+    /// Database file size in bytes
+    pub max_size: Option<u64>,
+    /// Rows in the entries table, including old versions
+    pub max_entries: Option<u64>,
+    /// Time since the last GC, manual or automatic
+    pub interval: Option<chrono::Duration>,
+    /// Soft-deleted entries stay restorable for this long before auto-GC removes them
+    pub deleted_retention: chrono::Duration,
+}
+
+impl Default for AutoGcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_size: None,
+            max_entries: None,
+            interval: Some(chrono::Duration::days(1)),
+            deleted_retention: chrono::Duration::days(30),
+        }
+    }
+}
+
+/// Options for opening a `Database`, from `Database::builder()`
+#[derive(Debug, Clone)]
+pub struct DatabaseBuilder {
+    path: Option<PathBuf>,
+    in_memory: bool,
+    busy_timeout: Duration,
+    auto_gc: bool,
+}
+
+impl Default for DatabaseBuilder {
+    fn default() -> Self {
+        Self {
+            path: None,
+            in_memory: false,
+            busy_timeout: Duration::from_secs(5),
+            auto_gc: true,
+        }
+    }
+}
+
+impl DatabaseBuilder {
+    /// Database file to use instead of the default location
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Keep everything in memory; nothing is written to disk
+    pub fn in_memory(mut self) -> Self {
+        self.in_memory = true;
+        self
+    }
+
+    /// How long to wait on a lock held by another connection before failing
+    pub fn busy_timeout(mut self, timeout: Duration) -> Self {
+        self.busy_timeout = timeout;
+        self
+    }
+
+    /// Let writes run an automatic GC when one is due (on by default)
+    pub fn auto_gc(mut self, enabled: bool) -> Self {
+        self.auto_gc = enabled;
+        self
+    }
+
+    pub fn open(self) -> Result<Database, KvError> {
+        if self.in_memory {
+            return Database::init(Connection::open_in_memory()?, self.busy_timeout, self.auto_gc);
+        }
+
+        let db_path = match self.path {
+            Some(path) => path,
+            None => Database::db_path()?,
+        };
+
+        // Ensure parent directory exists
+        if let Some(parent) = db_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        Database::init(Connection::open(&db_path)?, self.busy_timeout, self.auto_gc)
+    }
 }
 
 pub struct Database {
     conn: Connection,
+    auto_gc: bool,
 }
 
 impl Database {
+    /// Open the default database under the user's config directory
     pub fn open() -> Result<Self, KvError> {
-        let db_path = Self::db_path()?;
+        Self::builder().open()
+    }
 
-        // Ensure parent directory exists
-        if let Some(parent) = db_path.parent() {
-            std::fs::create_dir_all(parent)?;
+    /// Open (or create) a database at an explicit path
+    pub fn open_at(path: &Path) -> Result<Self, KvError> {
+        Self::builder().path(path).open()
+    }
+
+    /// Open a throwaway database that lives only as long as the connection
+    pub fn open_in_memory() -> Result<Self, KvError> {
+        Self::builder().in_memory().open()
+    }
+
+    pub fn builder() -> DatabaseBuilder {
+        DatabaseBuilder::default()
+    }
+
+    fn init(conn: Connection, busy_timeout: Duration, auto_gc: bool) -> Result<Self, KvError> {
+        // Wait for concurrent writers instead of failing with SQLITE_BUSY
+        conn.busy_timeout(busy_timeout)?;
+
+        // An up-to-date database opens with this one read
+        if Self::schema_version(&conn)? < SCHEMA_VERSION {
+            Self::migrate(&conn)?;
         }
 
-        let conn = Connection::open(&db_path)?;
+        Ok(Self { conn, auto_gc })
+    }
+
+    fn schema_version(conn: &Connection) -> Result<i64, KvError> {
+        conn.query_row("PRAGMA user_version", [], |row| row.get(0)).map_err(Into::into)
+    }
+
+    /// Bring the schema up to `SCHEMA_VERSION`. Every step is idempotent, so a
+    /// database created before versioning is migrated the same way.
+    fn migrate(conn: &Connection) -> Result<(), KvError> {
+        // Lets GC hand freed pages back to the filesystem. Only takes effect on a new
+        // database; an existing one switches over on its next VACUUM.
+        conn.execute_batch("PRAGMA auto_vacuum = INCREMENTAL")?;
+
+        // Another process may have migrated while we waited for the lock
+        let tx = Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+        if Self::schema_version(conn)? >= SCHEMA_VERSION {
+            return Ok(());
+        }
 
         // Run initial schema
         conn.execute_batch(SCHEMA_V1)?;
 
         // Run migrations for v2
-        Self::migrate_v2(&conn)?;
-
-        Ok(Self { conn })
+        Self::migrate_v2(conn)?;
+        Self::migrate_sliding_ttl(conn)?;
+
+        conn.execute_batch(SCHEMA_LISTS)?;
+        conn.execute_batch(SCHEMA_HASHES)?;
+        conn.execute_batch(SCHEMA_LOCKS)?;
+        conn.execute_batch(SCHEMA_CHANGES)?;
+        Self::migrate_change_fields(conn)?;
+        conn.execute_batch(SCHEMA_VALUE_SCHEMAS)?;
+        conn.execute_batch(SCHEMA_POLICIES)?;
+        conn.execute_batch(SCHEMA_META)?;
+
+        conn.execute_batch(&format!("PRAGMA user_version = {}", SCHEMA_VERSION))?;
+        tx.commit()?;
+        Ok(())
     }
 
     fn migrate_v2(conn: &Connection) -> Result<(), KvError> {
@@ -119,41 +358,320 @@ impl Database {
         Ok(())
     }
 
+    fn migrate_sliding_ttl(conn: &Connection) -> Result<(), KvError> {
+        let has_column: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('entries') WHERE name = 'sliding_ttl'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_column {
+            conn.execute("ALTER TABLE entries ADD COLUMN sliding_ttl INTEGER", [])?;
+        }
+        Ok(())
+    }
+
+    fn migrate_change_fields(conn: &Connection) -> Result<(), KvError> {
+        let has_column: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('changes') WHERE name = 'field'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_column {
+            conn.execute("ALTER TABLE changes ADD COLUMN field TEXT", [])?;
+        }
+        Ok(())
+    }
+
+    /// Append the rules shared by entries and hash fields (soft-deleted, older than,
+    /// beyond the newest N versions) to `conditions`, numbering their parameters after
+    /// those already in `values`. Returns the rows to select from, ranked per
+    /// `partition` when keeping N versions.
+    fn gc_rules(
+        opts: &GcOptions,
+        table: &str,
+        partition: &str,
+        deleted_before: Option<&str>,
+        created_before: Option<&str>,
+        conditions: &mut Vec<String>,
+        values: &mut Vec<String>,
+    ) -> String {
+        if opts.deleted {
+            match deleted_before {
+                Some(before) => {
+                    values.push(before.to_string());
+                    conditions.push(format!("(deleted_at IS NOT NULL AND deleted_at < ?{})", values.len()));
+                }
+                None => conditions.push("deleted_at IS NOT NULL".to_string()),
+            }
+        }
+        if let Some(before) = created_before {
+            values.push(before.to_string());
+            conditions.push(format!("created_at < ?{}", values.len()));
+        }
+        match opts.keep_versions {
+            Some(keep) => {
+                conditions.push(format!("rank > {}", keep));
+                format!(
+                    "(SELECT *, ROW_NUMBER() OVER (PARTITION BY {} ORDER BY version DESC) AS rank FROM {})",
+                    partition, table
+                )
+            }
+            None => table.to_string(),
+        }
+    }
+
     fn db_path() -> Result<PathBuf, KvError> {
         let config_dir = dirs::config_dir()
             .ok_or_else(|| KvError::Database("could not find config directory".into()))?;
         Ok(config_dir.join("kv").join("kv.db"))
     }
 
-    /// Returns (version, was_saved) - was_saved is false if value unchanged
-    pub fn set(
+    /// Like `set`, but only if the latest live version equals `expected`
+    /// (0 meaning the key must not exist). Fails with `VersionMismatch` otherwise.
+    pub fn set_if_version(
         &self,
         key: &str,
         value: &[u8],
-        content_type: Option<&str>,
-        original_filename: Option<&str>,
-        scope: Option<&str>,
-        expires_at: Option<DateTime<Utc>>,
-    ) -> Result<(i64, bool), KvError> {
-        // Check if current value is identical - skip save if unchanged
-        if let Ok(Some(existing)) = self.get_latest(key, scope) {
-            if existing.value == value {
-                return Ok((existing.version, false));
+        opts: &SetOptions,
+        expected: i64,
+    ) -> Result<SetResult, KvError> {
+        let tx = self.begin_write()?;
+        self.check_version(key, opts.scope, expected)?;
+        let result = self.set_in_tx(key, value, opts)?;
+        tx.commit()?;
+        self.after_write();
+        Ok(result)
+    }
+
+    /// Like `set`, but only if the key currently has a live version.
+    /// Fails with `KeyNotFound` otherwise.
+    pub fn set_if_exists(&self, key: &str, value: &[u8], opts: &SetOptions) -> Result<SetResult, KvError> {
+        let tx = self.begin_write()?;
+        if self.get_latest(key, opts.scope)?.filter(|e| !e.is_expired()).is_none() {
+            return Err(KvError::KeyNotFound(key.to_string()));
+        }
+        let result = self.set_in_tx(key, value, opts)?;
+        tx.commit()?;
+        self.after_write();
+        Ok(result)
+    }
+
+    fn set_in_tx(&self, key: &str, value: &[u8], opts: &SetOptions) -> Result<SetResult, KvError> {
+        // Skip the save if the latest live version already looks exactly like this one
+        if let Some(existing) = self.get_latest(key, opts.scope)?.filter(|e| !e.is_expired()) {
+            if existing.value == value
+                && existing.expires_at == opts.expires_at
+                && existing.sliding_ttl == opts.sliding_ttl
+                && existing.content_type.as_deref() == opts.content_type
+            {
+                return Ok(SetResult { version: existing.version, was_saved: false });
             }
         }
 
+        let version = self.insert_version(key, value, opts)?;
+        Ok(SetResult { version, was_saved: true })
+    }
+
+    fn check_version(&self, key: &str, scope: Option<&str>, expected: i64) -> Result<(), KvError> {
+        let actual = self
+            .get_latest(key, scope)?
+            .filter(|e| !e.is_expired())
+            .map(|e| e.version)
+            .unwrap_or(0);
+        if actual != expected {
+            return Err(KvError::VersionMismatch {
+                key: key.to_string(),
+                expected,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// Append a new version row for a key and return its version number
+    fn insert_version(&self, key: &str, value: &[u8], opts: &SetOptions) -> Result<i64, KvError> {
+        let scope = opts.scope;
+        self.validate_value(key, scope, value)?;
+        let (default_ttl, max_versions) = self.policy_for(key, scope)?;
         let next_version = self.next_version(key, scope)?;
         let now = Utc::now().to_rfc3339();
         let size = value.len() as i64;
+        let expires_at = opts.expires_at.or_else(|| default_ttl.map(|ttl| Utc::now() + ttl));
         let expires_str = expires_at.map(|dt| dt.to_rfc3339());
+        let sliding_ttl = opts.sliding_ttl.map(|d| d.num_seconds());
+
+        self.conn.execute(
+            "INSERT INTO entries (key, value, version, content_type, original_filename, size_bytes, created_at, scope, expires_at, sliding_ttl)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![key, value, next_version, opts.content_type, opts.original_filename, size, now, scope, expires_str, sliding_ttl],
+        )?;
+        self.record_change("set", key, scope, Some(next_version))?;
+
+        // Versions pushed out by the policy are collected like gc would, with a change event each
+        if let Some(max) = max_versions {
+            let oldest_kept = next_version - max as i64;
+            self.conn.execute(
+                "INSERT INTO changes (event, key, scope, version, created_at)
+                 SELECT 'gc', key, scope, version, ?4 FROM entries
+                 WHERE key = ?1 AND scope IS ?2 AND version <= ?3 ORDER BY version",
+                params![key, scope, oldest_kept, now],
+            )?;
+            self.conn.execute(
+                "DELETE FROM entries WHERE key = ?1 AND scope IS ?2 AND version <= ?3",
+                params![key, scope, oldest_kept],
+            )?;
+        }
+
+        Ok(next_version)
+    }
+
+    /// Append an event to the change log
+    fn record_change(&self, event: &str, key: &str, scope: Option<&str>, version: Option<i64>) -> Result<(), KvError> {
+        self.record_field_change(event, key, None, scope, version)
+    }
+
+    /// Append an event to the change log, for a single hash field when `field` is set
+    fn record_field_change(
+        &self,
+        event: &str,
+        key: &str,
+        field: Option<&str>,
+        scope: Option<&str>,
+        version: Option<i64>,
+    ) -> Result<(), KvError> {
+        self.conn.execute(
+            "INSERT INTO changes (event, key, scope, version, field, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![event, key, scope, version, field, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Start a write transaction that takes the database write lock up front,
+    /// so read-modify-write operations can't interleave across processes
+    fn begin_write(&self) -> Result<Transaction<'_>, KvError> {
+        Transaction::new_unchecked(&self.conn, TransactionBehavior::Immediate).map_err(Into::into)
+    }
 
+    /// Atomically append bytes to the latest value of a key, returning (version, new_size).
+    /// `separator` is only inserted between existing and new data. A missing or expired
+    /// key is created with `data` as its value. With `in_place`, the latest version is
+    /// overwritten instead of appending a new one.
+    pub fn append(
+        &self,
+        key: &str,
+        data: &[u8],
+        separator: Option<&[u8]>,
+        content_type: Option<&str>,
+        scope: Option<&str>,
+        in_place: bool,
+    ) -> Result<(i64, i64), KvError> {
+        let tx = self.begin_write()?;
+
+        let latest = self.get_latest(key, scope)?.filter(|e| !e.is_expired());
+        let result = match latest {
+            Some(mut entry) => {
+                if let Some(sep) = separator {
+                    if !entry.value.is_empty() {
+                        entry.value.extend_from_slice(sep);
+                    }
+                }
+                entry.value.extend_from_slice(data);
+                let size = entry.value.len() as i64;
+
+                if in_place {
+                    self.validate_value(key, scope, &entry.value)?;
+                    self.conn.execute(
+                        "UPDATE entries SET value = ?1, size_bytes = ?2, created_at = ?3 WHERE id = ?4",
+                        params![entry.value, size, Utc::now().to_rfc3339(), entry.id],
+                    )?;
+                    self.record_change("set", key, scope, Some(entry.version))?;
+                    (entry.version, size)
+                } else {
+                    let version = self.insert_version(key, &entry.value, &next_version_options(&entry))?;
+                    (version, size)
+                }
+            }
+            None => {
+                let opts = SetOptions {
+                    scope,
+                    content_type,
+                    ..Default::default()
+                };
+                let version = self.insert_version(key, data, &opts)?;
+                (version, data.len() as i64)
+            }
+        };
+
+        tx.commit()?;
+        self.after_write();
+        Ok(result)
+    }
+
+    /// Atomically apply a JSON Patch or merge patch to the latest version of a
+    /// key and store the result as a new version. Fails with `Decode` if the
+    /// current value is not JSON; a patch that changes nothing is not saved.
+    pub fn patch(&self, key: &str, patch: &Patch, scope: Option<&str>) -> Result<SetResult, KvError> {
+        let tx = self.begin_write()?;
+
+        let entry = self
+            .get_latest(key, scope)?
+            .filter(|e| !e.is_expired())
+            .ok_or_else(|| KvError::KeyNotFound(key.to_string()))?;
+        let doc: serde_json::Value = serde_json::from_slice(&entry.value).map_err(|e| KvError::Decode {
+            key: key.to_string(),
+            format: "json",
+            message: e.to_string(),
+        })?;
+
+        let patched = patch.apply(&doc)?;
+        if patched == doc {
+            return Ok(SetResult { version: entry.version, was_saved: false });
+        }
+
+        // Keep pretty-printed documents pretty, and a trailing newline if there was one
+        let trailing_newline = entry.value.ends_with(b"\n");
+        let pretty = entry.value.trim_ascii_end().contains(&b'\n');
+        let mut value = if pretty {
+            serde_json::to_vec_pretty(&patched)
+        } else {
+            serde_json::to_vec(&patched)
+        }
+        .map_err(|e| KvError::Encode { format: "json", message: e.to_string() })?;
+        if trailing_newline {
+            value.push(b'\n');
+        }
+
+        let version = self.insert_version(key, &value, &next_version_options(&entry))?;
+
+        tx.commit()?;
+        self.after_write();
+        Ok(SetResult { version, was_saved: true })
+    }
+
+    /// Latest live version of a key, like `get` but without restarting a
+    /// sliding TTL. Used to inspect expiry.
+    pub fn peek_entry(&self, key: &str, scope: Option<&str>) -> Result<Entry, KvError> {
+        self.get_latest(key, scope)?
+            .filter(|e| !e.is_expired())
+            .ok_or_else(|| KvError::KeyNotFound(key.to_string()))
+    }
+
+    /// Change when the latest version of a key expires (None to never expire)
+    /// without writing a new version. This also ends sliding expiration.
+    /// Returns the version that was updated.
+    pub fn set_expiry(&self, key: &str, expires_at: Option<DateTime<Utc>>, scope: Option<&str>) -> Result<i64, KvError> {
+        let tx = self.begin_write()?;
+
+        let entry = self.peek_entry(key, scope)?;
         self.conn.execute(
-            "INSERT INTO entries (key, value, version, content_type, original_filename, size_bytes, created_at, scope, expires_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-            params![key, value, next_version, content_type, original_filename, size, now, scope, expires_str],
+            "UPDATE entries SET expires_at = ?1, sliding_ttl = NULL WHERE id = ?2",
+            params![expires_at.map(|dt| dt.to_rfc3339()), entry.id],
         )?;
+        self.record_change("expire", key, scope, Some(entry.version))?;
 
-        Ok((next_version, true))
+        tx.commit()?;
+        self.after_write();
+        Ok(entry.version)
     }
 
     fn next_version(&self, key: &str, scope: Option<&str>) -> Result<i64, KvError> {
@@ -173,39 +691,15 @@ impl Database {
         Ok(max.unwrap_or(0) + 1)
     }
 
-    pub fn get(&self, key: &str, version: Option<i64>, scope: Option<&str>) -> Result<Entry, KvError> {
-        let entry = match version {
-            Some(v) => self.get_version(key, v, scope)?,
-            None => self.get_latest(key, scope)?,
-        };
-
-        // Check for expiration
-        if let Some(ref e) = entry {
-            if let Some(expires) = e.expires_at {
-                if expires < Utc::now() {
-                    return Err(KvError::KeyNotFound(key.to_string()));
-                }
-            }
-        }
-
-        entry.ok_or_else(|| {
-            if let Some(v) = version {
-                KvError::VersionNotFound { key: key.to_string(), version: v }
-            } else {
-                KvError::KeyNotFound(key.to_string())
-            }
-        })
-    }
-
     fn get_latest(&self, key: &str, scope: Option<&str>) -> Result<Option<Entry>, KvError> {
         let sql = if scope.is_some() {
-            "SELECT id, key, value, version, content_type, original_filename, size_bytes, created_at, deleted_at, scope, expires_at
+            "SELECT id, key, value, version, content_type, original_filename, size_bytes, created_at, deleted_at, scope, expires_at, sliding_ttl
              FROM entries
              WHERE key = ?1 AND scope = ?2 AND deleted_at IS NULL
              ORDER BY version DESC
              LIMIT 1"
         } else {
-            "SELECT id, key, value, version, content_type, original_filename, size_bytes, created_at, deleted_at, scope, expires_at
+            "SELECT id, key, value, version, content_type, original_filename, size_bytes, created_at, deleted_at, scope, expires_at, sliding_ttl
              FROM entries
              WHERE key = ?1 AND scope IS NULL AND deleted_at IS NULL
              ORDER BY version DESC
@@ -229,11 +723,11 @@ impl Database {
 
     fn get_version(&self, key: &str, version: i64, scope: Option<&str>) -> Result<Option<Entry>, KvError> {
         let sql = if scope.is_some() {
-            "SELECT id, key, value, version, content_type, original_filename, size_bytes, created_at, deleted_at, scope, expires_at
+            "SELECT id, key, value, version, content_type, original_filename, size_bytes, created_at, deleted_at, scope, expires_at, sliding_ttl
              FROM entries
              WHERE key = ?1 AND version = ?2 AND scope = ?3"
         } else {
-            "SELECT id, key, value, version, content_type, original_filename, size_bytes, created_at, deleted_at, scope, expires_at
+            "SELECT id, key, value, version, content_type, original_filename, size_bytes, created_at, deleted_at, scope, expires_at, sliding_ttl
              FROM entries
              WHERE key = ?1 AND version = ?2 AND scope IS NULL"
         };
@@ -285,59 +779,10 @@ impl Database {
             deleted_at,
             scope: row.get(9).ok().unwrap_or(None),
             expires_at,
+            sliding_ttl: row.get::<_, Option<i64>>(11).ok().flatten().map(chrono::Duration::seconds),
         })
     }
 
-    /// List keys, optionally filtering by scope
-    /// If scope is Some, filter to that scope
-    /// If scope is None and all is false, show only global keys
-    /// If all is true, show all keys regardless of scope
-    pub fn list_keys(&self, limit: Option<usize>, scope: Option<&str>, all: bool) -> Result<Vec<KeySummary>, KvError> {
-        let now = Utc::now().to_rfc3339();
-        let limit_clause = limit.map(|l| format!(" LIMIT {}", l)).unwrap_or_default();
-
-        let sql = if all {
-            format!(
-                "SELECT key, COUNT(*) as versions, SUM(size_bytes) as total_size, MAX(created_at) as last_updated, scope
-                 FROM entries
-                 WHERE deleted_at IS NULL AND (expires_at IS NULL OR expires_at > ?1)
-                 GROUP BY key, scope
-                 ORDER BY last_updated DESC{}",
-                limit_clause
-            )
-        } else if scope.is_some() {
-            format!(
-                "SELECT key, COUNT(*) as versions, SUM(size_bytes) as total_size, MAX(created_at) as last_updated, scope
-                 FROM entries
-                 WHERE deleted_at IS NULL AND scope = ?2 AND (expires_at IS NULL OR expires_at > ?1)
-                 GROUP BY key
-                 ORDER BY last_updated DESC{}",
-                limit_clause
-            )
-        } else {
-            format!(
-                "SELECT key, COUNT(*) as versions, SUM(size_bytes) as total_size, MAX(created_at) as last_updated, scope
-                 FROM entries
-                 WHERE deleted_at IS NULL AND scope IS NULL AND (expires_at IS NULL OR expires_at > ?1)
-                 GROUP BY key
-                 ORDER BY last_updated DESC{}",
-                limit_clause
-            )
-        };
-
-        let mut stmt = self.conn.prepare(&sql)?;
-
-        let rows = if all {
-            stmt.query_map([&now], Self::row_to_key_summary)?
-        } else if scope.is_some() {
-            stmt.query_map(params![&now, scope], Self::row_to_key_summary)?
-        } else {
-            stmt.query_map([&now], Self::row_to_key_summary)?
-        };
-
-        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
-    }
-
     fn row_to_key_summary(row: &rusqlite::Row) -> rusqlite::Result<KeySummary> {
         let last_updated_str: String = row.get(3)?;
         let last_updated = DateTime::parse_from_rfc3339(&last_updated_str)
@@ -353,41 +798,90 @@ impl Database {
         })
     }
 
-    pub fn list_key_history(&self, key: &str, limit: Option<usize>, scope: Option<&str>) -> Result<Vec<Entry>, KvError> {
+    /// Latest live version of every key whose name or text value contains `query`
+    pub fn search(&self, query: &str, limit: Option<usize>, scope: Option<&str>) -> Result<Vec<Entry>, KvError> {
         let limit_clause = limit.map(|l| format!(" LIMIT {}", l)).unwrap_or_default();
+        let sql = format!(
+            "SELECT id, key, value, version, content_type, original_filename, size_bytes, created_at, deleted_at, scope, expires_at, sliding_ttl
+             FROM entries e
+             WHERE scope IS ?2 AND deleted_at IS NULL AND (expires_at IS NULL OR expires_at > ?3)
+               AND version = (
+                   SELECT MAX(version) FROM entries
+                   WHERE key = e.key AND scope IS e.scope AND deleted_at IS NULL
+               )
+               AND (instr(key, ?1) > 0 OR instr(CAST(value AS TEXT), ?1) > 0)
+             ORDER BY key{}",
+            limit_clause
+        );
 
-        let entries: Vec<Entry> = if scope.is_some() {
-            let sql = format!(
-                "SELECT id, key, value, version, content_type, original_filename, size_bytes, created_at, deleted_at, scope, expires_at
-                 FROM entries
-                 WHERE key = ?1 AND scope = ?2
-                 ORDER BY version DESC{}",
-                limit_clause
-            );
-            let mut stmt = self.conn.prepare(&sql)?;
-            let rows = stmt.query_map(params![key, scope], |row| Ok(Self::row_to_entry(row)))?;
-            rows.filter_map(|r| r.ok().flatten()).collect()
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(params![query, scope, Utc::now().to_rfc3339()], |row| {
+            Ok(Self::row_to_entry(row))
+        })?;
+        Ok(rows.filter_map(|r| r.ok().flatten()).collect())
+    }
+
+    /// Like `list_keys`, but only keys whose latest live value is a JSON
+    /// document matching `filter`. The filter is evaluated by SQLite.
+    pub fn list_keys_where(&self, opts: &ListOptions, filter: &Filter) -> Result<Vec<KeySummary>, KvError> {
+        filter.validate()?;
+        let prefix = opts.prefix.unwrap_or("");
+        let limit_clause = opts.limit.map(|l| format!(" LIMIT {}", l)).unwrap_or_default();
+        let (scope_clause, group_by) = if opts.all_scopes {
+            ("", "key, scope")
         } else {
-            let sql = format!(
-                "SELECT id, key, value, version, content_type, original_filename, size_bytes, created_at, deleted_at, scope, expires_at
+            (" AND scope IS ?2", "key")
+        };
+
+        let mut values: Vec<rusqlite::types::Value> = vec![
+            Utc::now().to_rfc3339().into(),
+            opts.scope.map(str::to_string).into(),
+            prefix.to_string().into(),
+            filter.path.to_sqlite().into(),
+        ];
+        let condition = filter_condition(filter, &mut values);
+
+        // Live rows are numbered newest first per key so the filter only sees
+        // the latest version; CAST keeps SQLite from reading the BLOB as JSONB
+        let sql = format!(
+            "WITH live AS (
+                 SELECT key, scope, value, size_bytes, created_at,
+                        ROW_NUMBER() OVER (PARTITION BY key, scope ORDER BY version DESC) AS rn
                  FROM entries
-                 WHERE key = ?1 AND scope IS NULL
-                 ORDER BY version DESC{}",
-                limit_clause
-            );
-            let mut stmt = self.conn.prepare(&sql)?;
-            let rows = stmt.query_map([key], |row| Ok(Self::row_to_entry(row)))?;
-            rows.filter_map(|r| r.ok().flatten()).collect()
-        };
+                 WHERE deleted_at IS NULL{} AND (expires_at IS NULL OR expires_at > ?1)
+                   AND substr(key, 1, length(?3)) = ?3
+             ),
+             matched AS (
+                 SELECT key, scope FROM (
+                     SELECT key, scope, CAST(value AS TEXT) AS doc FROM live WHERE rn = 1
+                 )
+                 WHERE json_valid(doc) AND {}
+             )
+             SELECT key, COUNT(*) as versions, SUM(size_bytes) as total_size, MAX(created_at) as last_updated, scope
+             FROM live l
+             WHERE EXISTS (SELECT 1 FROM matched m WHERE m.key = l.key AND m.scope IS l.scope)
+             GROUP BY {}
+             ORDER BY last_updated DESC{}",
+            scope_clause, condition, group_by, limit_clause
+        );
 
-        if entries.is_empty() {
-            return Err(KvError::KeyNotFound(key.to_string()));
-        }
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(rusqlite::params_from_iter(values), Self::row_to_key_summary)?;
 
-        Ok(entries)
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    /// Like `delete`, but only if the latest live version equals `expected`
+    pub fn delete_if_version(&self, key: &str, opts: &DeleteOptions, expected: i64) -> Result<u64, KvError> {
+        let tx = self.begin_write()?;
+        self.check_version(key, opts.scope, expected)?;
+        let affected = self.delete_in_tx(key, opts.hard, opts.scope)?;
+        tx.commit()?;
+        self.after_write();
+        Ok(affected)
     }
 
-    pub fn delete(&self, key: &str, hard: bool, scope: Option<&str>) -> Result<u64, KvError> {
+    fn delete_in_tx(&self, key: &str, hard: bool, scope: Option<&str>) -> Result<u64, KvError> {
         // First check if key exists
         let exists: bool = if scope.is_some() {
             self.conn.query_row(
@@ -428,11 +922,913 @@ impl Database {
             }
         };
 
+        let version = self.next_version(key, scope)? - 1;
+        self.record_change("delete", key, scope, (version > 0).then_some(version))?;
+
+        Ok(affected as u64)
+    }
+
+    /// Change log events after `since`, oldest first.
+    /// Scope filtering follows `list_keys`.
+    pub fn changes(
+        &self,
+        since: i64,
+        limit: Option<usize>,
+        scope: Option<&str>,
+        all: bool,
+    ) -> Result<Vec<Change>, KvError> {
+        let limit_clause = limit.map(|l| format!(" LIMIT {}", l)).unwrap_or_default();
+        let scope_clause = if all { "" } else { " AND scope IS ?2" };
+        let sql = format!(
+            "SELECT seq, event, key, scope, version, created_at, field FROM changes
+             WHERE seq > ?1{}
+             ORDER BY seq ASC{}",
+            scope_clause, limit_clause
+        );
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let map_row = |row: &rusqlite::Row| -> rusqlite::Result<Change> {
+            let created_at_str: String = row.get(5)?;
+            Ok(Change {
+                seq: row.get(0)?,
+                event: row.get(1)?,
+                key: row.get(2)?,
+                scope: row.get(3)?,
+                version: row.get(4)?,
+                field: row.get(6)?,
+                created_at: DateTime::parse_from_rfc3339(&created_at_str)
+                    .map(|dt| dt.with_timezone(&Utc))
+                    .unwrap_or_else(|_| Utc::now()),
+            })
+        };
+        let rows = if all {
+            stmt.query_map([since], map_row)?
+        } else {
+            stmt.query_map(params![since, scope], map_row)?
+        };
+
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    /// Sequence number of the newest change log event, or 0 if there are none
+    pub fn last_change_seq(&self) -> Result<i64, KvError> {
+        self.conn
+            .query_row("SELECT COALESCE(MAX(seq), 0) FROM changes", [], |row| row.get(0))
+            .map_err(Into::into)
+    }
+
+    /// Push a value onto the head of a list and return the new length
+    pub fn push(
+        &self,
+        key: &str,
+        value: &[u8],
+        scope: Option<&str>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<i64, KvError> {
+        let tx = self.begin_write()?;
+
+        self.conn.execute(
+            "INSERT INTO list_items (key, scope, value, size_bytes, created_at, expires_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                key,
+                scope,
+                value,
+                value.len() as i64,
+                Utc::now().to_rfc3339(),
+                expires_at.map(|dt| dt.to_rfc3339())
+            ],
+        )?;
+        let len = self.list_len(key, scope)?;
+        self.record_change("push", key, scope, None)?;
+
+        tx.commit()?;
+        self.after_write();
+        Ok(len)
+    }
+
+    /// Atomically remove and return the oldest live item of a list (queue order).
+    /// Two concurrent callers never receive the same item.
+    pub fn pop(&self, key: &str, scope: Option<&str>) -> Result<Option<Vec<u8>>, KvError> {
+        let tx = self.begin_write()?;
+        let value: Option<Vec<u8>> = self
+            .conn
+            .query_row(
+                "DELETE FROM list_items WHERE id = (
+                     SELECT id FROM list_items
+                     WHERE key = ?1 AND scope IS ?2 AND (expires_at IS NULL OR expires_at > ?3)
+                     ORDER BY id ASC
+                     LIMIT 1
+                 )
+                 RETURNING value",
+                params![key, scope, Utc::now().to_rfc3339()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if value.is_some() {
+            self.record_change("pop", key, scope, None)?;
+        }
+
+        tx.commit()?;
+        self.after_write();
+        Ok(value)
+    }
+
+    /// Return the item `pop` would return without removing it
+    pub fn peek(&self, key: &str, scope: Option<&str>) -> Result<Option<Vec<u8>>, KvError> {
+        self.conn
+            .query_row(
+                "SELECT value FROM list_items
+                 WHERE key = ?1 AND scope IS ?2 AND (expires_at IS NULL OR expires_at > ?3)
+                 ORDER BY id ASC
+                 LIMIT 1",
+                params![key, scope, Utc::now().to_rfc3339()],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(Into::into)
+    }
+
+    /// Number of live (unexpired) items in a list
+    pub fn list_len(&self, key: &str, scope: Option<&str>) -> Result<i64, KvError> {
+        self.conn
+            .query_row(
+                "SELECT COUNT(*) FROM list_items
+                 WHERE key = ?1 AND scope IS ?2 AND (expires_at IS NULL OR expires_at > ?3)",
+                params![key, scope, Utc::now().to_rfc3339()],
+                |row| row.get(0),
+            )
+            .map_err(Into::into)
+    }
+
+    /// Set a field of a hash-typed key. Each field is versioned independently.
+    /// Returns (version, was_saved) - was_saved is false if value unchanged
+    pub fn hset(&self, key: &str, field: &str, value: &[u8], scope: Option<&str>) -> Result<(i64, bool), KvError> {
+        let tx = self.begin_write()?;
+
+        if let Some(existing) = self.hget_latest(key, field, scope)? {
+            if existing.value == value {
+                return Ok((existing.version, false));
+            }
+        }
+
+        let max: Option<i64> = self.conn.query_row(
+            "SELECT MAX(version) FROM hash_fields WHERE key = ?1 AND scope IS ?2 AND field = ?3",
+            params![key, scope, field],
+            |row| row.get(0),
+        )?;
+        let version = max.unwrap_or(0) + 1;
+
+        self.conn.execute(
+            "INSERT INTO hash_fields (key, scope, field, value, version, size_bytes, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![key, scope, field, value, version, value.len() as i64, Utc::now().to_rfc3339()],
+        )?;
+        self.record_field_change("set", key, Some(field), scope, Some(version))?;
+
+        tx.commit()?;
+        self.after_write();
+        Ok((version, true))
+    }
+
+    pub fn hget(&self, key: &str, field: &str, scope: Option<&str>) -> Result<HashField, KvError> {
+        self.hget_latest(key, field, scope)?.ok_or_else(|| KvError::FieldNotFound {
+            key: key.to_string(),
+            field: field.to_string(),
+        })
+    }
+
+    fn hget_latest(&self, key: &str, field: &str, scope: Option<&str>) -> Result<Option<HashField>, KvError> {
+        self.conn
+            .query_row(
+                "SELECT field, value, version, created_at FROM hash_fields
+                 WHERE key = ?1 AND scope IS ?2 AND field = ?3 AND deleted_at IS NULL
+                 ORDER BY version DESC
+                 LIMIT 1",
+                params![key, scope, field],
+                Self::row_to_hash_field,
+            )
+            .optional()
+            .map_err(Into::into)
+    }
+
+    /// Latest version of every live field, ordered by field name
+    pub fn hgetall(&self, key: &str, scope: Option<&str>) -> Result<Vec<HashField>, KvError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT field, value, version, created_at FROM hash_fields h
+             WHERE key = ?1 AND scope IS ?2 AND deleted_at IS NULL
+               AND version = (
+                   SELECT MAX(version) FROM hash_fields
+                   WHERE key = h.key AND scope IS h.scope AND field = h.field AND deleted_at IS NULL
+               )
+             ORDER BY field",
+        )?;
+        let rows = stmt.query_map(params![key, scope], Self::row_to_hash_field)?;
+        let fields = rows.collect::<Result<Vec<_>, _>>()?;
+
+        if fields.is_empty() {
+            return Err(KvError::KeyNotFound(key.to_string()));
+        }
+
+        Ok(fields)
+    }
+
+    /// Soft-delete every version of a hash field
+    pub fn hdel(&self, key: &str, field: &str, scope: Option<&str>) -> Result<u64, KvError> {
+        let tx = self.begin_write()?;
+        let version = self.hget_latest(key, field, scope)?.map(|f| f.version);
+        let affected = self.conn.execute(
+            "UPDATE hash_fields SET deleted_at = ?1
+             WHERE key = ?2 AND scope IS ?3 AND field = ?4 AND deleted_at IS NULL",
+            params![Utc::now().to_rfc3339(), key, scope, field],
+        )?;
+
+        if affected == 0 {
+            return Err(KvError::FieldNotFound {
+                key: key.to_string(),
+                field: field.to_string(),
+            });
+        }
+        self.record_field_change("delete", key, Some(field), scope, version)?;
+
+        tx.commit()?;
+        self.after_write();
         Ok(affected as u64)
     }
 
-    /// Get statistics about the store
-    pub fn stats(&self) -> Result<Stats, KvError> {
+    fn row_to_hash_field(row: &rusqlite::Row) -> rusqlite::Result<HashField> {
+        let created_at_str: String = row.get(3)?;
+        let created_at = DateTime::parse_from_rfc3339(&created_at_str)
+            .map(|dt| dt.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now());
+
+        Ok(HashField {
+            field: row.get(0)?,
+            value: row.get(1)?,
+            version: row.get(2)?,
+            created_at,
+        })
+    }
+
+    /// Try to take a lease on a named lock for `owner`. Expired leases are
+    /// reclaimed; re-acquiring a lock you already hold extends it.
+    /// Returns false if another owner holds a live lease.
+    pub fn acquire_lock(
+        &self,
+        name: &str,
+        scope: Option<&str>,
+        owner: &str,
+        ttl: chrono::Duration,
+    ) -> Result<bool, KvError> {
+        let tx = self.begin_write()?;
+        let now = Utc::now();
+
+        self.conn.execute(
+            "DELETE FROM locks WHERE name = ?1 AND scope IS ?2 AND expires_at <= ?3",
+            params![name, scope, now.to_rfc3339()],
+        )?;
+
+        let holder: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT owner FROM locks WHERE name = ?1 AND scope IS ?2",
+                params![name, scope],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        let expires = (now + ttl).to_rfc3339();
+        match holder {
+            Some(h) if h != owner => return Ok(false),
+            Some(_) => {
+                self.conn.execute(
+                    "UPDATE locks SET expires_at = ?1 WHERE name = ?2 AND scope IS ?3",
+                    params![expires, name, scope],
+                )?;
+            }
+            None => {
+                self.conn.execute(
+                    "INSERT INTO locks (name, scope, owner, acquired_at, expires_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![name, scope, owner, now.to_rfc3339(), expires],
+                )?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// Extend a live lease held by `owner`. Returns false if the lease was lost.
+    pub fn renew_lock(
+        &self,
+        name: &str,
+        scope: Option<&str>,
+        owner: &str,
+        ttl: chrono::Duration,
+    ) -> Result<bool, KvError> {
+        let now = Utc::now();
+        let affected = self.conn.execute(
+            "UPDATE locks SET expires_at = ?1
+             WHERE name = ?2 AND scope IS ?3 AND owner = ?4 AND expires_at > ?5",
+            params![(now + ttl).to_rfc3339(), name, scope, owner, now.to_rfc3339()],
+        )?;
+        Ok(affected > 0)
+    }
+
+    /// Release a lock. With `owner` set, only that owner's lease is released;
+    /// with None the lock is broken regardless of who holds it.
+    /// Returns false if there was nothing to release.
+    pub fn release_lock(&self, name: &str, scope: Option<&str>, owner: Option<&str>) -> Result<bool, KvError> {
+        let affected = match owner {
+            Some(owner) => self.conn.execute(
+                "DELETE FROM locks WHERE name = ?1 AND scope IS ?2 AND owner = ?3",
+                params![name, scope, owner],
+            )?,
+            None => self.conn.execute(
+                "DELETE FROM locks WHERE name = ?1 AND scope IS ?2",
+                params![name, scope],
+            )?,
+        };
+        Ok(affected > 0)
+    }
+
+    /// Counter that changes whenever another connection commits to the database
+    pub fn data_version(&self) -> Result<i64, KvError> {
+        self.conn
+            .query_row("PRAGMA data_version", [], |row| row.get(0))
+            .map_err(Into::into)
+    }
+
+    /// Attach a JSON Schema to `pattern`, replacing any schema it already had.
+    /// Existing values are not checked; the schema applies to later writes.
+    pub fn set_schema(&self, pattern: &str, scope: Option<&str>, schema: &serde_json::Value) -> Result<(), KvError> {
+        crate::schema::check_schema(schema)?;
+
+        let tx = self.begin_write()?;
+        self.conn.execute(
+            "DELETE FROM value_schemas WHERE pattern = ?1 AND scope IS ?2",
+            params![pattern, scope],
+        )?;
+        self.conn.execute(
+            "INSERT INTO value_schemas (pattern, scope, schema, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![pattern, scope, schema.to_string(), Utc::now().to_rfc3339()],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Detach the schema from `pattern`. Returns false if it had none.
+    pub fn remove_schema(&self, pattern: &str, scope: Option<&str>) -> Result<bool, KvError> {
+        let affected = self.conn.execute(
+            "DELETE FROM value_schemas WHERE pattern = ?1 AND scope IS ?2",
+            params![pattern, scope],
+        )?;
+        Ok(affected > 0)
+    }
+
+    /// Every schema in a scope, ordered by pattern
+    pub fn schemas(&self, scope: Option<&str>) -> Result<Vec<SchemaRule>, KvError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT pattern, scope, schema, created_at FROM value_schemas WHERE scope IS ?1 ORDER BY pattern",
+        )?;
+        let rows = stmt.query_map([scope], |row| {
+            let schema: String = row.get(2)?;
+            let created_at: String = row.get(3)?;
+            Ok(SchemaRule {
+                pattern: row.get(0)?,
+                scope: row.get(1)?,
+                schema: serde_json::from_str(&schema).unwrap_or(serde_json::Value::Bool(true)),
+                created_at: DateTime::parse_from_rfc3339(&created_at)
+                    .map(|dt| dt.with_timezone(&Utc))
+                    .unwrap_or_else(|_| Utc::now()),
+            })
+        })?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    /// Check `value` against every schema whose pattern matches `key`
+    fn validate_value(&self, key: &str, scope: Option<&str>, value: &[u8]) -> Result<(), KvError> {
+        let rules: Vec<SchemaRule> = self
+            .schemas(scope)?
+            .into_iter()
+            .filter(|rule| crate::scope::pattern_matches(&rule.pattern, key))
+            .collect();
+        if rules.is_empty() {
+            return Ok(());
+        }
+
+        let doc: serde_json::Value = serde_json::from_slice(value).map_err(|e| KvError::ValidationFailed {
+            key: key.to_string(),
+            violations: vec![format!("value is not JSON: {}", e)],
+        })?;
+        let violations: Vec<String> = rules
+            .iter()
+            .flat_map(|rule| crate::schema::validate(&rule.schema, &doc))
+            .collect();
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(KvError::ValidationFailed {
+                key: key.to_string(),
+                violations,
+            })
+        }
+    }
+
+    /// Set the write policy for `pattern`, replacing any it already had
+    pub fn set_policy(
+        &self,
+        pattern: &str,
+        scope: Option<&str>,
+        default_ttl: Option<chrono::Duration>,
+        max_versions: Option<usize>,
+    ) -> Result<(), KvError> {
+        let tx = self.begin_write()?;
+        self.conn.execute(
+            "DELETE FROM policies WHERE pattern = ?1 AND scope IS ?2",
+            params![pattern, scope],
+        )?;
+        self.conn.execute(
+            "INSERT INTO policies (pattern, scope, default_ttl, max_versions, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                pattern,
+                scope,
+                default_ttl.map(|d| d.num_seconds()),
+                max_versions.map(|m| m as i64),
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Remove the policy for `pattern`. Returns false if it had none.
+    pub fn remove_policy(&self, pattern: &str, scope: Option<&str>) -> Result<bool, KvError> {
+        let affected = self.conn.execute(
+            "DELETE FROM policies WHERE pattern = ?1 AND scope IS ?2",
+            params![pattern, scope],
+        )?;
+        Ok(affected > 0)
+    }
+
+    /// Every policy in a scope, ordered by pattern
+    pub fn policies(&self, scope: Option<&str>) -> Result<Vec<Policy>, KvError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT pattern, scope, default_ttl, max_versions, created_at FROM policies WHERE scope IS ?1 ORDER BY pattern",
+        )?;
+        let rows = stmt.query_map([scope], |row| {
+            let created_at: String = row.get(4)?;
+            Ok(Policy {
+                pattern: row.get(0)?,
+                scope: row.get(1)?,
+                default_ttl: row.get::<_, Option<i64>>(2)?.map(chrono::Duration::seconds),
+                max_versions: row.get::<_, Option<i64>>(3)?.map(|m| m.max(1) as usize),
+                created_at: DateTime::parse_from_rfc3339(&created_at)
+                    .map(|dt| dt.with_timezone(&Utc))
+                    .unwrap_or_else(|_| Utc::now()),
+            })
+        })?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    /// The default TTL and max versions that apply to `key`. Each comes from the
+    /// most specific matching pattern that sets it: the exact key, then the
+    /// longest prefix.
+    fn policy_for(&self, key: &str, scope: Option<&str>) -> Result<(Option<chrono::Duration>, Option<usize>), KvError> {
+        let mut matching: Vec<Policy> = self
+            .policies(scope)?
+            .into_iter()
+            .filter(|policy| crate::scope::pattern_matches(&policy.pattern, key))
+            .collect();
+        matching.sort_by_key(|p| std::cmp::Reverse((!p.pattern.ends_with('*'), p.pattern.len())));
+
+        Ok((
+            matching.iter().find_map(|p| p.default_ttl),
+            matching.iter().find_map(|p| p.max_versions),
+        ))
+    }
+
+    fn meta(&self, name: &str) -> Result<Option<String>, KvError> {
+        self.conn
+            .query_row("SELECT value FROM meta WHERE name = ?1", [name], |row| row.get(0))
+            .optional()
+            .map_err(Into::into)
+    }
+
+    fn set_meta(&self, name: &str, value: &str) -> Result<(), KvError> {
+        self.conn.execute(
+            "INSERT INTO meta (name, value) VALUES (?1, ?2) ON CONFLICT(name) DO UPDATE SET value = ?2",
+            params![name, value],
+        )?;
+        Ok(())
+    }
+
+    fn meta_time(&self, name: &str) -> Result<Option<DateTime<Utc>>, KvError> {
+        Ok(self
+            .meta(name)?
+            .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+            .map(|dt| dt.with_timezone(&Utc)))
+    }
+
+    pub fn auto_gc_config(&self) -> Result<AutoGcConfig, KvError> {
+        let number = |name: &str| -> Result<Option<Option<u64>>, KvError> {
+            Ok(self.meta(name)?.map(|s| s.parse().ok()))
+        };
+        let defaults = AutoGcConfig::default();
+        Ok(AutoGcConfig {
+            enabled: self.meta("auto_gc.enabled")?.map_or(defaults.enabled, |s| s == "1"),
+            max_size: number("auto_gc.max_size")?.unwrap_or(defaults.max_size),
+            max_entries: number("auto_gc.max_entries")?.unwrap_or(defaults.max_entries),
+            interval: number("auto_gc.interval")?
+                .map(|secs| secs.map(|secs| chrono::Duration::seconds(secs as i64)))
+                .unwrap_or(defaults.interval),
+            deleted_retention: number("auto_gc.deleted_retention")?
+                .flatten()
+                .map_or(defaults.deleted_retention, |secs| chrono::Duration::seconds(secs as i64)),
+        })
+    }
+
+    /// Store the automatic GC thresholds; an empty value records a disabled threshold
+    pub fn set_auto_gc_config(&self, config: &AutoGcConfig) -> Result<(), KvError> {
+        let number = |n: Option<u64>| n.map(|n| n.to_string()).unwrap_or_default();
+        let tx = self.begin_write()?;
+        self.set_meta("auto_gc.enabled", if config.enabled { "1" } else { "0" })?;
+        self.set_meta("auto_gc.max_size", &number(config.max_size))?;
+        self.set_meta("auto_gc.max_entries", &number(config.max_entries))?;
+        self.set_meta("auto_gc.interval", &number(config.interval.map(|d| d.num_seconds().max(0) as u64)))?;
+        self.set_meta(
+            "auto_gc.deleted_retention",
+            &number(Some(config.deleted_retention.num_seconds().max(0) as u64)),
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// When GC last deleted anything for real, manually or automatically
+    pub fn last_gc(&self) -> Result<Option<DateTime<Utc>>, KvError> {
+        self.meta_time("auto_gc.last_run")
+    }
+
+    /// Size of the database file in bytes
+    pub fn file_size(&self) -> Result<u64, KvError> {
+        let (pages, page_size): (i64, i64) = self.conn.query_row(
+            "SELECT page_count, page_size FROM pragma_page_count(), pragma_page_size()",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        Ok((pages * page_size) as u64)
+    }
+
+    /// Rewrite the database file to return free pages to the filesystem, and
+    /// switch it to incremental vacuuming. Returns the bytes reclaimed.
+    pub fn vacuum(&self) -> Result<u64, KvError> {
+        let before = self.file_size()?;
+        self.conn.execute_batch("PRAGMA auto_vacuum = INCREMENTAL; VACUUM")?;
+        Ok(before.saturating_sub(self.file_size()?))
+    }
+
+    /// Run a GC of expired entries, and of soft-deleted ones past their retention,
+    /// if a threshold from `auto_gc_config` has been reached. The check itself runs at most once a minute.
+    pub fn maybe_gc(&self) -> Result<Option<GcResult>, KvError> {
+        let config = self.auto_gc_config()?;
+        if !config.enabled {
+            return Ok(None);
+        }
+
+        let now = Utc::now();
+        if self.meta_time("auto_gc.last_check")?.is_some_and(|t| now - t < AUTO_GC_CHECK_INTERVAL) {
+            return Ok(None);
+        }
+        self.set_meta("auto_gc.last_check", &now.to_rfc3339())?;
+
+        // A store that has never been collected starts its interval now
+        let last_run = match self.last_gc()? {
+            Some(t) => t,
+            None => {
+                self.set_meta("auto_gc.last_run", &now.to_rfc3339())?;
+                now
+            }
+        };
+
+        let due = config.interval.is_some_and(|interval| now - last_run >= interval)
+            || match config.max_size {
+                Some(max) => self.file_size()? > max,
+                None => false,
+            }
+            || match config.max_entries {
+                Some(max) => {
+                    let count: i64 = self.conn.query_row("SELECT COUNT(*) FROM entries", [], |row| row.get(0))?;
+                    count as u64 > max
+                }
+                None => false,
+            };
+        if !due {
+            return Ok(None);
+        }
+
+        let result = self.gc(&GcOptions {
+            deleted_older_than: Some(config.deleted_retention),
+            ..Default::default()
+        })?;
+        self.conn.execute_batch("PRAGMA incremental_vacuum")?;
+        Ok(Some(result))
+    }
+
+    /// Give auto-GC its chance after a write. Inside a transaction this waits
+    /// for the caller's own commit, and a failed GC never fails the write.
+    fn after_write(&self) {
+        if self.auto_gc && self.conn.is_autocommit() {
+            let _ = self.maybe_gc();
+        }
+    }
+
+    /// Newest row of every key matching `pattern` exactly, or starting with it when `prefix` is set
+    pub fn heads(&self, pattern: &str, prefix: bool, scope: Option<&str>) -> Result<Vec<KeyHead>, KvError> {
+        let key_filter = if prefix {
+            "substr(key, 1, length(?1)) = ?1"
+        } else {
+            "key = ?1"
+        };
+        let sql = format!(
+            "SELECT key, version, deleted_at IS NOT NULL, expires_at FROM entries e
+             WHERE {} AND scope IS ?2
+               AND version = (SELECT MAX(version) FROM entries WHERE key = e.key AND scope IS e.scope)
+             ORDER BY key",
+            key_filter
+        );
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(params![pattern, scope], |row| {
+            let expires_at: Option<String> = row.get(3)?;
+            Ok(KeyHead {
+                key: row.get(0)?,
+                version: row.get(1)?,
+                deleted: row.get(2)?,
+                expires_at: expires_at.and_then(|s| {
+                    DateTime::parse_from_rfc3339(&s)
+                        .ok()
+                        .map(|dt| dt.with_timezone(&Utc))
+                }),
+            })
+        })?;
+
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+}
+
+/// Options that carry an entry's metadata and expiry over to its next version
+fn next_version_options(entry: &Entry) -> SetOptions<'_> {
+    SetOptions {
+        scope: entry.scope.as_deref(),
+        content_type: entry.content_type.as_deref(),
+        original_filename: entry.original_filename.as_deref(),
+        expires_at: entry.expires_at,
+        sliding_ttl: entry.sliding_ttl,
+    }
+}
+
+/// SQL condition over `doc` for a filter whose path is bound to ?4. The
+/// literal, if any, is appended to `values` as ?5. Types are checked
+/// explicitly because json_extract turns `true` into 1. The filter must
+/// already have passed `Filter::validate`.
+fn filter_condition(filter: &Filter, values: &mut Vec<rusqlite::types::Value>) -> String {
+    let json_type = "ifnull(json_type(doc, ?4), 'null')";
+    let Some((op, literal)) = &filter.comparison else {
+        return "json_type(doc, ?4) IS NOT NULL".into();
+    };
+
+    let (type_check, literal) = match literal {
+        serde_json::Value::Bool(b) => (format!("{} = '{}'", json_type, b), None),
+        serde_json::Value::Null => (format!("{} = 'null'", json_type), None),
+        serde_json::Value::String(s) => (format!("{} = 'text'", json_type), Some(s.clone().into())),
+        serde_json::Value::Number(n) => (
+            format!("{} IN ('integer', 'real')", json_type),
+            Some(match n.as_i64() {
+                Some(i) => i.into(),
+                None => n.as_f64().unwrap_or(f64::NAN).into(),
+            }),
+        ),
+        serde_json::Value::Array(_) | serde_json::Value::Object(_) => {
+            unreachable!("container literals are rejected by Filter::validate")
+        }
+    };
+
+    let matches = match literal {
+        Some(literal) => {
+            values.push(literal);
+            let sql_op = match op {
+                Op::Eq | Op::Ne => "=",
+                Op::Lt => "<",
+                Op::Le => "<=",
+                Op::Gt => ">",
+                Op::Ge => ">=",
+            };
+            format!("({} AND json_extract(doc, ?4) {} ?5)", type_check, sql_op)
+        }
+        None => format!("({})", type_check),
+    };
+
+    match op {
+        Op::Ne => format!("NOT {}", matches),
+        _ => matches,
+    }
+}
+
+impl KvStore for Database {
+    fn set(&self, key: &str, value: &[u8], opts: &SetOptions) -> Result<SetResult, KvError> {
+        let tx = self.begin_write()?;
+        let result = self.set_in_tx(key, value, opts)?;
+        tx.commit()?;
+        self.after_write();
+        Ok(result)
+    }
+
+    fn get(&self, key: &str, opts: &GetOptions) -> Result<Entry, KvError> {
+        let (version, scope) = (opts.version, opts.scope);
+
+        let entry = match version {
+            Some(v) => self.get_version(key, v, scope)?,
+            None => self.get_latest(key, scope)?,
+        };
+
+        // Check for expiration
+        if let Some(ref e) = entry {
+            if let Some(expires) = e.expires_at {
+                if expires < Utc::now() {
+                    return Err(KvError::KeyNotFound(key.to_string()));
+                }
+            }
+        }
+
+        let mut entry = entry.ok_or_else(|| {
+            if let Some(v) = version {
+                KvError::VersionNotFound { key: key.to_string(), version: v }
+            } else {
+                KvError::KeyNotFound(key.to_string())
+            }
+        })?;
+
+        // Reading a sliding key restarts its TTL
+        if let (None, Some(window)) = (version, entry.sliding_ttl) {
+            let expires_at = Utc::now() + window;
+            self.conn.execute(
+                "UPDATE entries SET expires_at = ?1 WHERE id = ?2",
+                params![expires_at.to_rfc3339(), entry.id],
+            )?;
+            entry.expires_at = Some(expires_at);
+        }
+
+        Ok(entry)
+    }
+
+    fn delete(&self, key: &str, opts: &DeleteOptions) -> Result<u64, KvError> {
+        let (hard, scope) = (opts.hard, opts.scope);
+
+        let tx = self.begin_write()?;
+        let affected = self.delete_in_tx(key, hard, scope)?;
+        tx.commit()?;
+        self.after_write();
+        Ok(affected)
+    }
+
+    fn restore(&self, key: &str, scope: Option<&str>) -> Result<u64, KvError> {
+        let tx = self.begin_write()?;
+
+        let affected = self.conn.execute(
+            "UPDATE entries SET deleted_at = NULL WHERE key = ?1 AND scope IS ?2 AND deleted_at IS NOT NULL",
+            params![key, scope],
+        )?;
+
+        if affected == 0 {
+            return Err(KvError::KeyNotFound(key.to_string()));
+        }
+
+        let version = self.next_version(key, scope)? - 1;
+        self.record_change("restore", key, scope, Some(version))?;
+
+        tx.commit()?;
+        self.after_write();
+        Ok(affected as u64)
+    }
+
+    fn incr(&self, key: &str, delta: i64, opts: &IncrOptions) -> Result<i64, KvError> {
+        let (scope, in_place) = (opts.scope, opts.in_place);
+
+        let tx = self.begin_write()?;
+
+        let latest = self.get_latest(key, scope)?.filter(|e| !e.is_expired());
+        let current = match &latest {
+            Some(entry) => std::str::from_utf8(&entry.value)
+                .ok()
+                .and_then(|s| s.trim().parse::<i64>().ok())
+                .ok_or_else(|| KvError::NotAnInteger(key.to_string()))?,
+            None => 0,
+        };
+        let result = current
+            .checked_add(delta)
+            .ok_or_else(|| KvError::IntegerOverflow(key.to_string()))?;
+        let value = result.to_string();
+
+        match latest {
+            Some(entry) if in_place => {
+                self.validate_value(key, scope, value.as_bytes())?;
+                self.conn.execute(
+                    "UPDATE entries SET value = ?1, size_bytes = ?2, content_type = 'text/plain', created_at = ?3
+                     WHERE id = ?4",
+                    params![value.as_bytes(), value.len() as i64, Utc::now().to_rfc3339(), entry.id],
+                )?;
+                self.record_change("set", key, scope, Some(entry.version))?;
+            }
+            Some(entry) => {
+                let opts = SetOptions {
+                    content_type: Some("text/plain"),
+                    original_filename: None,
+                    ..next_version_options(&entry)
+                };
+                self.insert_version(key, value.as_bytes(), &opts)?;
+            }
+            None => {
+                let opts = SetOptions {
+                    scope,
+                    content_type: Some("text/plain"),
+                    ..Default::default()
+                };
+                self.insert_version(key, value.as_bytes(), &opts)?;
+            }
+        }
+
+        tx.commit()?;
+        self.after_write();
+        Ok(result)
+    }
+
+    fn list_keys(&self, opts: &ListOptions) -> Result<Vec<KeySummary>, KvError> {
+        let prefix = opts.prefix.unwrap_or("");
+        let (limit, scope, all) = (opts.limit, opts.scope, opts.all_scopes);
+
+        let now = Utc::now().to_rfc3339();
+        let limit_clause = limit.map(|l| format!(" LIMIT {}", l)).unwrap_or_default();
+        let (scope_clause, group_by) = if all {
+            ("", "key, scope")
+        } else {
+            (" AND scope IS ?2", "key")
+        };
+
+        let sql = format!(
+            "SELECT key, COUNT(*) as versions, SUM(size_bytes) as total_size, MAX(created_at) as last_updated, scope
+             FROM entries
+             WHERE deleted_at IS NULL{} AND (expires_at IS NULL OR expires_at > ?1)
+               AND substr(key, 1, length(?3)) = ?3
+             GROUP BY {}
+             ORDER BY last_updated DESC{}",
+            scope_clause, group_by, limit_clause
+        );
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(params![&now, scope, prefix], Self::row_to_key_summary)?;
+
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    fn history(&self, key: &str, opts: &HistoryOptions) -> Result<Vec<Entry>, KvError> {
+        let (limit, scope) = (opts.limit, opts.scope);
+
+        let limit_clause = limit.map(|l| format!(" LIMIT {}", l)).unwrap_or_default();
+
+        let entries: Vec<Entry> = if scope.is_some() {
+            let sql = format!(
+                "SELECT id, key, value, version, content_type, original_filename, size_bytes, created_at, deleted_at, scope, expires_at, sliding_ttl
+                 FROM entries
+                 WHERE key = ?1 AND scope = ?2
+                 ORDER BY version DESC{}",
+                limit_clause
+            );
+            let mut stmt = self.conn.prepare(&sql)?;
+            let rows = stmt.query_map(params![key, scope], |row| Ok(Self::row_to_entry(row)))?;
+            rows.filter_map(|r| r.ok().flatten()).collect()
+        } else {
+            let sql = format!(
+                "SELECT id, key, value, version, content_type, original_filename, size_bytes, created_at, deleted_at, scope, expires_at, sliding_ttl
+                 FROM entries
+                 WHERE key = ?1 AND scope IS NULL
+                 ORDER BY version DESC{}",
+                limit_clause
+            );
+            let mut stmt = self.conn.prepare(&sql)?;
+            let rows = stmt.query_map([key], |row| Ok(Self::row_to_entry(row)))?;
+            rows.filter_map(|r| r.ok().flatten()).collect()
+        };
+
+        if entries.is_empty() {
+            return Err(KvError::KeyNotFound(key.to_string()));
+        }
+
+        Ok(entries)
+    }
+
+    fn stats(&self) -> Result<Stats, KvError> {
         let now = Utc::now().to_rfc3339();
 
         // Total size and entries
@@ -482,9 +1878,9 @@ impl Database {
             None => (None, None),
         };
 
-        // Largest key (by total size across versions)
+        // Largest key (by total size across versions, ties going to the first key)
         let largest: Option<(String, i64)> = self.conn.query_row(
-            "SELECT key, SUM(size_bytes) as total FROM entries GROUP BY key ORDER BY total DESC LIMIT 1",
+            "SELECT key, SUM(size_bytes) as total FROM entries GROUP BY key ORDER BY total DESC, key ASC LIMIT 1",
             [],
             |row| Ok((row.get(0)?, row.get(1)?)),
         ).optional()?;
@@ -526,123 +1922,821 @@ impl Database {
         })
     }
 
-    /// Garbage collect entries based on filters
-    /// Returns count of entries that would be (or were) deleted
-    pub fn gc(
-        &self,
-        run: bool,
-        older_than_days: Option<u64>,
-        keep_versions: Option<i64>,
-        expired_only: bool,
-        deleted_only: bool,
-    ) -> Result<GcResult, KvError> {
-        let now = Utc::now();
-        let mut total_bytes = 0i64;
+    fn gc(&self, opts: &GcOptions) -> Result<GcResult, KvError> {
+        let now = Utc::now().to_rfc3339();
+        let tx = if opts.dry_run {
+            self.conn.unchecked_transaction()?
+        } else {
+            self.begin_write()?
+        };
 
-        // Collect IDs to delete
-        let mut ids_to_delete: Vec<i64> = Vec::new();
+        // Every rule becomes one condition over a single scan of entries, so an
+        // entry matched by several rules is still only counted once
+        let deleted_before = opts.deleted_older_than.map(|age| (Utc::now() - age).to_rfc3339());
+        let created_before = opts.older_than.map(|age| (Utc::now() - age).to_rfc3339());
+        let mut conditions = Vec::new();
+        let mut values = vec![now.clone()];
+        if opts.expired {
+            conditions.push("(expires_at IS NOT NULL AND expires_at <= ?1)".to_string());
+        }
+        let source = Self::gc_rules(
+            opts,
+            "entries",
+            "key, scope",
+            deleted_before.as_deref(),
+            created_before.as_deref(),
+            &mut conditions,
+            &mut values,
+        );
+
+        self.conn.execute_batch(
+            "DROP TABLE IF EXISTS temp.gc_doomed;
+             DROP TABLE IF EXISTS temp.gc_doomed_fields;
+             CREATE TEMP TABLE gc_doomed (id INTEGER PRIMARY KEY, event TEXT NOT NULL, size_bytes INTEGER NOT NULL);
+             CREATE TEMP TABLE gc_doomed_fields (id INTEGER PRIMARY KEY, size_bytes INTEGER NOT NULL);",
+        )?;
+        if !conditions.is_empty() {
+            let sql = format!(
+                "INSERT INTO gc_doomed (id, event, size_bytes)
+                 SELECT id, CASE WHEN expires_at IS NOT NULL AND expires_at <= ?1 THEN 'expire' ELSE 'gc' END, size_bytes
+                 FROM {} WHERE {}",
+                source,
+                conditions.join(" OR ")
+            );
+            self.conn.execute(&sql, rusqlite::params_from_iter(&values))?;
+        }
 
-        // Expired entries
-        if expired_only || (!deleted_only && !expired_only) {
-            let now_str = now.to_rfc3339();
-            let mut stmt = self.conn.prepare(
-                "SELECT id, size_bytes FROM entries WHERE expires_at IS NOT NULL AND expires_at <= ?1"
-            )?;
-            let rows = stmt.query_map([&now_str], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))?;
-            for row in rows.flatten() {
-                ids_to_delete.push(row.0);
-                total_bytes += row.1;
-            }
+        // Hash fields keep their own history per field and are collected by the same rules
+        let mut field_conditions = Vec::new();
+        let mut field_values = Vec::new();
+        let field_source = Self::gc_rules(
+            opts,
+            "hash_fields",
+            "key, scope, field",
+            deleted_before.as_deref(),
+            created_before.as_deref(),
+            &mut field_conditions,
+            &mut field_values,
+        );
+        if !field_conditions.is_empty() {
+            let sql = format!(
+                "INSERT INTO gc_doomed_fields (id, size_bytes) SELECT id, size_bytes FROM {} WHERE {}",
+                field_source,
+                field_conditions.join(" OR ")
+            );
+            self.conn.execute(&sql, rusqlite::params_from_iter(&field_values))?;
         }
 
-        // Deleted entries
-        if deleted_only || (!deleted_only && !expired_only) {
-            let mut stmt = self.conn.prepare(
-                "SELECT id, size_bytes FROM entries WHERE deleted_at IS NOT NULL"
+        let (mut total_deleted, mut total_bytes): (i64, i64) = self.conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(size_bytes), 0)
+             FROM (SELECT size_bytes FROM gc_doomed UNION ALL SELECT size_bytes FROM gc_doomed_fields)",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+
+        // Expired list items live in their own table
+        if opts.expired {
+            let condition = "expires_at IS NOT NULL AND expires_at <= ?1";
+            let (count, bytes): (i64, i64) = self.conn.query_row(
+                &format!("SELECT COUNT(*), COALESCE(SUM(size_bytes), 0) FROM list_items WHERE {}", condition),
+                [&now],
+                |row| Ok((row.get(0)?, row.get(1)?)),
             )?;
-            let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))?;
-            for row in rows.flatten() {
-                if !ids_to_delete.contains(&row.0) {
-                    ids_to_delete.push(row.0);
-                    total_bytes += row.1;
-                }
+            total_deleted += count;
+            total_bytes += bytes;
+            if !opts.dry_run {
+                self.conn.execute(&format!("DELETE FROM list_items WHERE {}", condition), [&now])?;
             }
         }
 
-        // Older than N days
-        if let Some(days) = older_than_days {
-            let cutoff = now - chrono::Duration::days(days as i64);
-            let cutoff_str = cutoff.to_rfc3339();
-            let mut stmt = self.conn.prepare(
-                "SELECT id, size_bytes FROM entries WHERE created_at < ?1"
+        if !opts.dry_run {
+            self.conn.execute(
+                "INSERT INTO changes (event, key, scope, version, created_at)
+                 SELECT d.event, e.key, e.scope, e.version, ?1
+                 FROM gc_doomed d JOIN entries e ON e.id = d.id ORDER BY d.id",
+                [&now],
             )?;
-            let rows = stmt.query_map([&cutoff_str], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))?;
-            for row in rows.flatten() {
-                if !ids_to_delete.contains(&row.0) {
-                    ids_to_delete.push(row.0);
-                    total_bytes += row.1;
-                }
-            }
+            self.conn.execute(
+                "INSERT INTO changes (event, key, field, scope, version, created_at)
+                 SELECT 'gc', f.key, f.field, f.scope, f.version, ?1
+                 FROM gc_doomed_fields d JOIN hash_fields f ON f.id = d.id ORDER BY d.id",
+                [&now],
+            )?;
+            self.conn.execute("DELETE FROM entries WHERE id IN (SELECT id FROM gc_doomed)", [])?;
+            self.conn.execute("DELETE FROM hash_fields WHERE id IN (SELECT id FROM gc_doomed_fields)", [])?;
+            self.set_meta("auto_gc.last_run", &now)?;
         }
+        self.conn.execute_batch("DROP TABLE temp.gc_doomed; DROP TABLE temp.gc_doomed_fields;")?;
+        tx.commit()?;
 
-        // Keep only N versions per key
-        if let Some(keep) = keep_versions {
-            // Get all key+scope combinations
-            let mut stmt = self.conn.prepare(
-                "SELECT DISTINCT key, scope FROM entries"
-            )?;
-            let key_scopes: Vec<(String, Option<String>)> = stmt
-                .query_map([], |row| Ok((row.get(0)?, row.get(1).ok().unwrap_or(None))))?
-                .filter_map(|r| r.ok())
+        Ok(GcResult {
+            entries_count: total_deleted,
+            bytes_freed: total_bytes,
+            was_run: !opts.dry_run,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incr_creates_and_versions() {
+        let db = Database::open_in_memory().unwrap();
+        assert_eq!(db.incr("n", 1, &IncrOptions::default()).unwrap(), 1);
+        assert_eq!(db.incr("n", 5, &IncrOptions::default()).unwrap(), 6);
+        assert_eq!(db.incr("n", -10, &IncrOptions::default()).unwrap(), -4);
+
+        let entry = db.get("n", &GetOptions::default()).unwrap();
+        assert_eq!(entry.value, b"-4");
+        assert_eq!(entry.version, 3);
+    }
+
+    #[test]
+    fn test_incr_in_place_keeps_version() {
+        let db = Database::open_in_memory().unwrap();
+        db.incr("n", 1, &IncrOptions { scope: Some("s"), in_place: true }).unwrap();
+        db.incr("n", 1, &IncrOptions { scope: Some("s"), in_place: true }).unwrap();
+        db.incr("n", 1, &IncrOptions { scope: Some("s"), in_place: true }).unwrap();
+
+        let history = db.history("n", &HistoryOptions { scope: Some("s"), ..Default::default() }).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].value, b"3");
+    }
+
+    #[test]
+    fn test_incr_rejects_non_integer() {
+        let db = Database::open_in_memory().unwrap();
+        db.set("n", b"hello", &SetOptions::default()).unwrap();
+        assert!(matches!(db.incr("n", 1, &IncrOptions::default()), Err(KvError::NotAnInteger(_))));
+
+        db.set("n", i64::MAX.to_string().as_bytes(), &SetOptions::default()).unwrap();
+        assert!(matches!(db.incr("n", 1, &IncrOptions::default()), Err(KvError::IntegerOverflow(_))));
+    }
+
+    #[test]
+    fn test_append_with_separator() {
+        let db = Database::open_in_memory().unwrap();
+        assert_eq!(db.append("log", b"one", Some(b"\n"), None, None, false).unwrap(), (1, 3));
+        assert_eq!(db.append("log", b"two", Some(b"\n"), None, None, false).unwrap(), (2, 7));
+        assert_eq!(db.append("log", b"3", None, None, None, true).unwrap(), (2, 8));
+
+        assert_eq!(db.get("log", &GetOptions::default()).unwrap().value, b"one\ntwo3");
+        assert_eq!(db.get("log", &GetOptions { version: Some(1), ..Default::default() }).unwrap().value, b"one");
+    }
+
+    #[test]
+    fn test_queue_fifo_and_scoping() {
+        let db = Database::open_in_memory().unwrap();
+        assert_eq!(db.push("q", b"a", None, None).unwrap(), 1);
+        assert_eq!(db.push("q", b"b", None, None).unwrap(), 2);
+        db.push("q", b"other", Some("s"), None).unwrap();
+
+        assert_eq!(db.peek("q", None).unwrap(), Some(b"a".to_vec()));
+        assert_eq!(db.pop("q", None).unwrap(), Some(b"a".to_vec()));
+        assert_eq!(db.pop("q", None).unwrap(), Some(b"b".to_vec()));
+        assert_eq!(db.pop("q", None).unwrap(), None);
+        assert_eq!(db.list_len("q", Some("s")).unwrap(), 1);
+
+        let events: Vec<_> = db.changes(0, None, None, false).unwrap().into_iter().map(|c| c.event).collect();
+        assert_eq!(events, ["push", "push", "pop", "pop"]);
+    }
+
+    #[test]
+    fn test_queue_skips_expired_items() {
+        let db = Database::open_in_memory().unwrap();
+        let past = Utc::now() - chrono::Duration::seconds(1);
+        db.push("q", b"stale", None, Some(past)).unwrap();
+        db.push("q", b"fresh", None, None).unwrap();
+
+        assert_eq!(db.list_len("q", None).unwrap(), 1);
+        assert_eq!(db.pop("q", None).unwrap(), Some(b"fresh".to_vec()));
+    }
+
+    #[test]
+    fn test_hash_fields_versioned_independently() {
+        let db = Database::open_in_memory().unwrap();
+        assert_eq!(db.hset("h", "a", b"1", None).unwrap(), (1, true));
+        assert_eq!(db.hset("h", "a", b"2", None).unwrap(), (2, true));
+        assert_eq!(db.hset("h", "a", b"2", None).unwrap(), (2, false));
+        assert_eq!(db.hset("h", "b", b"x", None).unwrap(), (1, true));
+
+        let all = db.hgetall("h", None).unwrap();
+        let pairs: Vec<_> = all.iter().map(|f| (f.field.as_str(), f.value.as_slice(), f.version)).collect();
+        assert_eq!(pairs, vec![("a", &b"2"[..], 2), ("b", &b"x"[..], 1)]);
+
+        db.hdel("h", "a", None).unwrap();
+        assert!(matches!(db.hget("h", "a", None), Err(KvError::FieldNotFound { .. })));
+        assert_eq!(db.hgetall("h", None).unwrap().len(), 1);
+        assert!(db.hgetall("h", Some("s")).is_err());
+
+        // Field writes show up in the change log, unchanged ones don't
+        let events: Vec<_> = db
+            .changes(0, None, None, false)
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.event, c.key, c.field, c.version))
+            .collect();
+        let event = |e: &str, f: &str, v: i64| (e.to_string(), "h".to_string(), Some(f.to_string()), Some(v));
+        assert_eq!(events, [event("set", "a", 1), event("set", "a", 2), event("set", "b", 1), event("delete", "a", 2)]);
+    }
+
+    #[test]
+    fn test_lock_exclusion_and_expiry() {
+        let db = Database::open_in_memory().unwrap();
+        let ttl = chrono::Duration::seconds(30);
+        assert!(db.acquire_lock("m", None, "a", ttl).unwrap());
+        assert!(!db.acquire_lock("m", None, "b", ttl).unwrap());
+        assert!(db.acquire_lock("m", Some("s"), "b", ttl).unwrap());
+        assert!(db.renew_lock("m", None, "a", ttl).unwrap());
+        assert!(!db.renew_lock("m", None, "b", ttl).unwrap());
+
+        assert!(!db.release_lock("m", None, Some("b")).unwrap());
+        assert!(db.release_lock("m", None, Some("a")).unwrap());
+        assert!(db.acquire_lock("m", None, "b", chrono::Duration::seconds(-1)).unwrap());
+        assert!(db.acquire_lock("m", None, "a", ttl).unwrap());
+    }
+
+    #[test]
+    fn test_heads_by_key_and_prefix() {
+        let db = Database::open_in_memory().unwrap();
+        db.set("job/1", b"a", &SetOptions::default()).unwrap();
+        db.set("job/1", b"b", &SetOptions::default()).unwrap();
+        db.set("job/2", b"c", &SetOptions::default()).unwrap();
+        db.set("jobs", b"d", &SetOptions::default()).unwrap();
+        db.delete("job/2", &DeleteOptions::default()).unwrap();
+
+        let heads = db.heads("job/", true, None).unwrap();
+        let summary: Vec<_> = heads.iter().map(|h| (h.key.as_str(), h.version, h.deleted)).collect();
+        assert_eq!(summary, vec![("job/1", 2, false), ("job/2", 1, true)]);
+
+        assert_eq!(db.heads("jobs", false, None).unwrap().len(), 1);
+        assert!(db.heads("job/", true, Some("s")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_change_log_sequence() {
+        let db = Database::open_in_memory().unwrap();
+        db.set("k", b"a", &SetOptions::default()).unwrap();
+        db.set("k", b"a", &SetOptions::default()).unwrap();
+        db.incr("n", 1, &IncrOptions::default()).unwrap();
+        db.delete("k", &DeleteOptions::default()).unwrap();
+        db.restore("k", None).unwrap();
+        db.set("other", b"x", &SetOptions { scope: Some("s"), ..Default::default() }).unwrap();
+        db.delete("k", &DeleteOptions::default()).unwrap();
+        db.gc(&GcOptions { expired: false, ..Default::default() }).unwrap();
+
+        let changes = db.changes(0, None, None, false).unwrap();
+        let events: Vec<_> = changes.iter().map(|c| (c.event.as_str(), c.key.as_str(), c.version)).collect();
+        assert_eq!(
+            events,
+            vec![
+                ("set", "k", Some(1)),
+                ("set", "n", Some(1)),
+                ("delete", "k", Some(1)),
+                ("restore", "k", Some(1)),
+                ("delete", "k", Some(1)),
+                ("gc", "k", Some(1)),
+            ]
+        );
+
+        let since = changes[2].seq;
+        assert_eq!(db.changes(since, Some(1), None, false).unwrap()[0].event, "restore");
+        assert_eq!(db.changes(0, None, None, true).unwrap().len(), 7);
+    }
+
+    #[test]
+    fn test_conditional_set_and_delete() {
+        let db = Database::open_in_memory().unwrap();
+        assert_eq!(db.set_if_version("k", b"a", &SetOptions::default(), 0).unwrap().version, 1);
+        assert!(matches!(
+            db.set_if_version("k", b"b", &SetOptions::default(), 0),
+            Err(KvError::VersionMismatch { actual: 1, .. })
+        ));
+        assert_eq!(db.set_if_version("k", b"b", &SetOptions::default(), 1).unwrap().version, 2);
+        assert!(db.delete_if_version("k", &DeleteOptions::default(), 1).is_err());
+        assert_eq!(db.delete_if_version("k", &DeleteOptions::default(), 2).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_list_keys_with_prefix() {
+        let db = Database::open_in_memory().unwrap();
+        db.set("a/1", b"x", &SetOptions { scope: Some("s"), ..Default::default() }).unwrap();
+        db.set("a/2", b"x", &SetOptions { scope: Some("s"), ..Default::default() }).unwrap();
+        db.set("b/1", b"x", &SetOptions { scope: Some("s"), ..Default::default() }).unwrap();
+        db.set("a/3", b"x", &SetOptions::default()).unwrap();
+
+        assert_eq!(db.list_keys(&ListOptions { scope: Some("s"), prefix: Some("a/"), ..Default::default() }).unwrap().len(), 2);
+        assert_eq!(db.list_keys(&ListOptions { all_scopes: true, prefix: Some("a/"), ..Default::default() }).unwrap().len(), 3);
+        assert_eq!(db.list_keys(&ListOptions { scope: Some("s"), ..Default::default() }).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_search_matches_key_or_latest_value() {
+        let db = Database::open_in_memory().unwrap();
+        db.set("model", b"opus", &SetOptions::default()).unwrap();
+        db.set("notes", b"uses the opus model", &SetOptions::default()).unwrap();
+        db.set("old", b"opus", &SetOptions::default()).unwrap();
+        db.set("old", b"sonnet", &SetOptions::default()).unwrap();
+
+        let hits: Vec<_> = db.search("opus", None, None).unwrap().into_iter().map(|e| e.key).collect();
+        assert_eq!(hits, vec!["model", "notes"]);
+        assert_eq!(db.search("model", Some(1), None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_builder_and_trait_object() {
+        let db = Database::builder()
+            .in_memory()
+            .busy_timeout(Duration::from_millis(100))
+            .open()
+            .unwrap();
+        let store: &dyn KvStore = &db;
+
+        let first = store.set("k", b"a", &SetOptions::default()).unwrap();
+        let again = store.set("k", b"a", &SetOptions::default()).unwrap();
+        assert_eq!(first, SetResult { version: 1, was_saved: true });
+        assert_eq!(again, SetResult { version: 1, was_saved: false });
+
+        store.delete("k", &DeleteOptions::default()).unwrap();
+        let dry = store.gc(&GcOptions { dry_run: true, ..Default::default() }).unwrap();
+        assert_eq!((dry.entries_count, dry.was_run), (1, false));
+        assert_eq!(store.restore("k", None).unwrap(), 1);
+        assert_eq!(store.get("k", &GetOptions::default()).unwrap().value, b"a");
+    }
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Task {
+        id: u32,
+        tags: Vec<String>,
+    }
+
+    #[test]
+    fn test_typed_values() {
+        let db = Database::open_in_memory().unwrap();
+        let task = Task { id: 7, tags: vec!["urgent".into()] };
+
+        db.set_json("task", &task, &SetOptions::default()).unwrap();
+        let entry = db.get("task", &GetOptions::default()).unwrap();
+        assert_eq!(entry.content_type.as_deref(), Some("application/json"));
+        assert_eq!(db.get_json::<Task>("task", &GetOptions::default()).unwrap(), task);
+
+        db.set("task", b"not json", &SetOptions::default()).unwrap();
+        let err = db.get_json::<Task>("task", &GetOptions::default()).unwrap_err();
+        assert!(matches!(err, KvError::Decode { format: "json", .. }));
+
+        #[cfg(feature = "msgpack")]
+        {
+            db.set_msgpack("packed", &task, &SetOptions::default()).unwrap();
+            assert_eq!(db.get_msgpack::<Task>("packed", &GetOptions::default()).unwrap(), task);
+            assert!(db.get_msgpack::<Task>("task", &GetOptions::default()).is_err());
+        }
+
+        #[cfg(feature = "cbor")]
+        {
+            db.set_cbor("cbor", &task, &SetOptions::default()).unwrap();
+            assert_eq!(db.get_cbor::<Task>("cbor", &GetOptions::default()).unwrap(), task);
+            assert!(db.get_cbor::<Task>("task", &GetOptions::default()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_list_keys_where() {
+        let db = Database::open_in_memory().unwrap();
+        let set = |key: &str, value: &str| {
+            db.set(key, value.as_bytes(), &SetOptions::default()).unwrap();
+        };
+        set("task/1", r#"{"status": "done", "retries": 0, "urgent": true}"#);
+        set("task/2", r#"{"status": "open", "retries": 4, "urgent": 1}"#);
+        set("task/3", r#"{"status": "open", "retries": 2.5, "owner": null}"#);
+        set("task/4", "not json");
+        set("task/5", r#"{"status": "done"}"#);
+        set("task/5", r#"{"status": "open"}"#);
+
+        let keys = |filter: &str| -> Vec<String> {
+            let filter = Filter::parse(filter).unwrap();
+            let mut keys: Vec<String> = db
+                .list_keys_where(&ListOptions::default(), &filter)
+                .unwrap()
+                .into_iter()
+                .map(|k| k.key)
                 .collect();
+            keys.sort();
+            keys
+        };
 
-            for (key, scope) in key_scopes {
-                // Get IDs to delete (versions beyond the keep limit)
-                let version_rows: Vec<(i64, i64)> = if scope.is_some() {
-                    let sql = "SELECT id, size_bytes FROM entries WHERE key = ?1 AND scope = ?2 ORDER BY version DESC";
-                    let mut stmt = self.conn.prepare(sql)?;
-                    let result = stmt.query_map(params![&key, &scope], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))?
-                        .flatten()
-                        .collect();
-                    result
-                } else {
-                    let sql = "SELECT id, size_bytes FROM entries WHERE key = ?1 AND scope IS NULL ORDER BY version DESC";
-                    let mut stmt = self.conn.prepare(sql)?;
-                    let result = stmt.query_map([&key], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))?
-                        .flatten()
-                        .collect();
-                    result
-                };
+        // Only the latest version of task/5 is considered
+        assert_eq!(keys(r#".status == "done""#), ["task/1"]);
+        assert_eq!(keys(r#".status != "done""#), ["task/2", "task/3", "task/5"]);
+        assert_eq!(keys(".retries > 1"), ["task/2", "task/3"]);
+        assert_eq!(keys(".retries <= 2.5"), ["task/1", "task/3"]);
+        assert_eq!(keys(".urgent == true"), ["task/1"]);
+        assert_eq!(keys(".owner == null"), ["task/1", "task/2", "task/3", "task/5"]);
+        assert_eq!(keys(".owner"), ["task/3"]);
+
+        let versions = db
+            .list_keys_where(&ListOptions::default(), &Filter::parse(".status").unwrap())
+            .unwrap();
+        assert_eq!(versions.iter().find(|k| k.key == "task/5").unwrap().versions, 2);
+
+        // Containers can't be compared reliably as JSON text, even when built directly
+        let filter = Filter {
+            comparison: Some((Op::Eq, serde_json::json!([1]))),
+            ..Filter::parse(".tags").unwrap()
+        };
+        assert!(matches!(db.list_keys_where(&ListOptions::default(), &filter), Err(KvError::InvalidQuery(_))));
+    }
 
-                for (i, (id, size)) in version_rows.into_iter().enumerate() {
-                    if i >= keep as usize && !ids_to_delete.contains(&id) {
-                        ids_to_delete.push(id);
-                        total_bytes += size;
-                    }
-                }
+    #[test]
+    fn test_patch() {
+        let db = Database::open_in_memory().unwrap();
+        let opts = SetOptions {
+            scope: Some("s"),
+            content_type: Some("application/json"),
+            ..Default::default()
+        };
+        db.set("cfg", br#"{"model":"opus","retries":1}"#, &opts).unwrap();
+
+        let merge = Patch::parse(br#"{"retries": 2, "extra": true}"#).unwrap();
+        let result = db.patch("cfg", &merge, Some("s")).unwrap();
+        assert_eq!(result, SetResult { version: 2, was_saved: true });
+        let entry = db.get("cfg", &GetOptions { scope: Some("s"), version: None }).unwrap();
+        assert_eq!(entry.value, br#"{"model":"opus","retries":2,"extra":true}"#);
+        assert_eq!(entry.content_type.as_deref(), Some("application/json"));
+
+        // Reapplying changes nothing
+        assert!(!db.patch("cfg", &merge, Some("s")).unwrap().was_saved);
+
+        // A failing operation leaves the key untouched
+        let failing = Patch::parse(br#"[{"op":"remove","path":"/extra"},{"op":"test","path":"/model","value":"x"}]"#).unwrap();
+        assert!(matches!(db.patch("cfg", &failing, Some("s")), Err(KvError::PatchFailed(_))));
+        assert_eq!(db.history("cfg", &HistoryOptions { scope: Some("s"), limit: None }).unwrap().len(), 2);
+
+        db.set("text", b"plain", &SetOptions::default()).unwrap();
+        assert!(matches!(db.patch("text", &merge, None), Err(KvError::Decode { format: "json", .. })));
+        assert!(matches!(db.patch("missing", &merge, None), Err(KvError::KeyNotFound(_))));
+    }
+
+    #[test]
+    fn test_schema_validation() {
+        let db = Database::open_in_memory().unwrap();
+        let schema = serde_json::json!({
+            "type": "object",
+            "required": ["model"],
+            "properties": { "model": { "type": "string" }, "retries": { "type": "integer" } }
+        });
+        db.set_schema("config/*", None, &schema).unwrap();
+        db.set_schema("config/*", None, &schema).unwrap();
+        assert_eq!(db.schemas(None).unwrap().len(), 1);
+        assert!(db.schemas(Some("other")).unwrap().is_empty());
+
+        let set = |key: &str, value: &[u8]| db.set(key, value, &SetOptions::default());
+        set("config/agent", br#"{"model": "opus", "retries": 2}"#).unwrap();
+
+        match set("config/agent", br#"{"retries": "two"}"#) {
+            Err(KvError::ValidationFailed { key, violations }) => {
+                assert_eq!(key, "config/agent");
+                assert_eq!(
+                    violations,
+                    ["/: missing required property 'model'", "/retries: expected integer, found string"]
+                );
             }
+            other => panic!("expected ValidationFailed, got {:?}", other),
+        }
+        assert!(matches!(set("config/agent", b"not json"), Err(KvError::ValidationFailed { .. })));
+
+        // Other write paths are checked too, and rejected writes leave no version behind
+        let patch = Patch::parse(br#"{"model": null}"#).unwrap();
+        assert!(matches!(db.patch("config/agent", &patch, None), Err(KvError::ValidationFailed { .. })));
+        assert_eq!(db.history("config/agent", &HistoryOptions::default()).unwrap().len(), 1);
+
+        // Keys outside the pattern and other scopes are unaffected
+        set("configs", b"anything").unwrap();
+        db.set("config/agent", b"anything", &SetOptions { scope: Some("s"), ..Default::default() }).unwrap();
+
+        assert!(matches!(
+            db.set_schema("x", None, &serde_json::json!({ "pattern": "^a" })),
+            Err(KvError::InvalidSchema(_))
+        ));
+
+        assert!(db.remove_schema("config/*", None).unwrap());
+        assert!(!db.remove_schema("config/*", None).unwrap());
+        set("config/agent", b"not json").unwrap();
+    }
+
+    #[test]
+    fn test_set_expiry() {
+        let db = Database::open_in_memory().unwrap();
+        let opts = SetOptions { scope: Some("s"), ..Default::default() };
+        db.set("session", b"abc", &opts).unwrap();
+        let get = GetOptions { scope: Some("s"), version: None };
+
+        let soon = Utc::now() + chrono::Duration::hours(1);
+        assert_eq!(db.set_expiry("session", Some(soon), Some("s")).unwrap(), 1);
+        let entry = db.get("session", &get).unwrap();
+        assert_eq!((entry.version, entry.expires_at.map(|e| e.timestamp())), (1, Some(soon.timestamp())));
+
+        assert_eq!(db.set_expiry("session", None, Some("s")).unwrap(), 1);
+        assert_eq!(db.get("session", &get).unwrap().expires_at, None);
+        assert_eq!(db.history("session", &HistoryOptions { scope: Some("s"), limit: None }).unwrap().len(), 1);
+
+        // Expiring in the past hides the key, after which it can't be revived
+        db.set_expiry("session", Some(Utc::now() - chrono::Duration::seconds(1)), Some("s")).unwrap();
+        assert!(matches!(db.get("session", &get), Err(KvError::KeyNotFound(_))));
+        assert!(matches!(db.set_expiry("session", None, Some("s")), Err(KvError::KeyNotFound(_))));
+        assert!(matches!(db.set_expiry("missing", None, None), Err(KvError::KeyNotFound(_))));
+
+        let events: Vec<String> = db.changes(0, None, Some("s"), false).unwrap().into_iter().map(|c| c.event).collect();
+        assert_eq!(events, ["set", "expire", "expire", "expire"]);
+
+        // Peeking leaves a sliding TTL alone, and an explicit expiry ends sliding
+        let sliding = SetOptions {
+            expires_at: Some(Utc::now() + chrono::Duration::seconds(30)),
+            sliding_ttl: Some(chrono::Duration::hours(1)),
+            ..Default::default()
+        };
+        db.set("cache", b"x", &sliding).unwrap();
+        assert!(db.peek_entry("cache", None).unwrap().expires_at < Some(Utc::now() + chrono::Duration::minutes(1)));
+        db.set_expiry("cache", Some(Utc::now() + chrono::Duration::seconds(30)), None).unwrap();
+        let entry = db.get("cache", &GetOptions::default()).unwrap();
+        assert_eq!(entry.sliding_ttl, None);
+        assert!(entry.expires_at < Some(Utc::now() + chrono::Duration::minutes(1)));
+    }
+
+    #[test]
+    fn test_set_same_value_makes_key_sliding() {
+        let db = Database::open_in_memory().unwrap();
+        db.set("cache", b"x", &SetOptions::default()).unwrap();
+
+        let sliding = SetOptions {
+            expires_at: Some(Utc::now() + chrono::Duration::hours(1)),
+            sliding_ttl: Some(chrono::Duration::hours(1)),
+            ..Default::default()
+        };
+        let result = db.set("cache", b"x", &sliding).unwrap();
+        assert_eq!((result.version, result.was_saved), (2, true));
+
+        let entry = db.peek_entry("cache", None).unwrap();
+        assert_eq!(entry.sliding_ttl, Some(chrono::Duration::hours(1)));
+        assert!(entry.expires_at.is_some());
+
+        // Repeating the exact same write is still a no-op
+        assert!(!db.set("cache", b"x", &sliding).unwrap().was_saved);
+    }
+
+    #[test]
+    fn test_policies() {
+        let db = Database::open_in_memory().unwrap();
+        db.set_policy("*", Some("s"), Some(chrono::Duration::days(7)), None).unwrap();
+        db.set_policy("tmp/*", Some("s"), Some(chrono::Duration::hours(1)), Some(2)).unwrap();
+        db.set_policy("tmp/keep", Some("s"), None, Some(3)).unwrap();
+        assert_eq!(db.policies(Some("s")).unwrap().len(), 3);
+        assert!(db.policies(None).unwrap().is_empty());
+
+        let scoped = SetOptions { scope: Some("s"), ..Default::default() };
+        let get = |key: &str| db.get(key, &GetOptions { scope: Some("s"), version: None }).unwrap();
+        let hours_left = |key: &str| (get(key).expires_at.unwrap() - Utc::now()).num_minutes() / 60;
+        let versions = |key: &str| {
+            let history = db.history(key, &HistoryOptions { scope: Some("s"), limit: None }).unwrap();
+            history.iter().map(|e| e.version).collect::<Vec<_>>()
+        };
+
+        for value in ["1", "2", "3", "4"] {
+            db.set("tmp/a", value.as_bytes(), &scoped).unwrap();
+            db.set("tmp/keep", value.as_bytes(), &scoped).unwrap();
         }
+        db.set("notes", b"x", &scoped).unwrap();
+
+        // The most specific pattern wins, setting by setting
+        assert_eq!(hours_left("tmp/a"), 0);
+        assert_eq!(hours_left("tmp/keep"), 0);
+        assert_eq!(hours_left("notes"), 7 * 24 - 1);
+        assert_eq!(versions("tmp/a"), [4, 3]);
+        let pruned: Vec<_> = db
+            .changes(0, None, Some("s"), false)
+            .unwrap()
+            .into_iter()
+            .filter(|c| c.key == "tmp/a" && c.event == "gc")
+            .map(|c| c.version)
+            .collect();
+        assert_eq!(pruned, [Some(1), Some(2)]);
+        assert_eq!(versions("tmp/keep"), [4, 3, 2]);
+
+        // An explicit expiry beats the default, and other scopes are untouched
+        let explicit = SetOptions { expires_at: Some(Utc::now() + chrono::Duration::days(30)), ..scoped.clone() };
+        db.set("notes", b"y", &explicit).unwrap();
+        assert!(hours_left("notes") > 7 * 24);
+        db.set("tmp/a", b"x", &SetOptions::default()).unwrap();
+        assert_eq!(db.get("tmp/a", &GetOptions::default()).unwrap().expires_at, None);
+
+        assert!(db.remove_policy("tmp/*", Some("s")).unwrap());
+        assert!(!db.remove_policy("tmp/*", Some("s")).unwrap());
+        db.set("tmp/a", b"5", &scoped).unwrap();
+        assert_eq!(versions("tmp/a"), [5, 4, 3]);
+        assert_eq!(hours_left("tmp/a"), 7 * 24 - 1);
+    }
+
+    #[test]
+    fn test_auto_gc() {
+        let db = Database::open_in_memory().unwrap();
+        assert_eq!(db.auto_gc_config().unwrap(), AutoGcConfig::default());
+        let config = AutoGcConfig { max_entries: Some(3), interval: None, ..Default::default() };
+        db.set_auto_gc_config(&config).unwrap();
+        assert_eq!(db.auto_gc_config().unwrap(), config);
+
+        let expired = SetOptions { expires_at: Some(Utc::now() - chrono::Duration::seconds(1)), ..Default::default() };
+        db.set("old", b"1", &expired).unwrap();
+        db.set("gone", b"2", &SetOptions::default()).unwrap();
+        db.delete("gone", &DeleteOptions::default()).unwrap();
+        db.set("keep", b"3", &SetOptions::default()).unwrap();
+        let count = || db.conn.query_row("SELECT COUNT(*) FROM entries", [], |row| row.get::<_, i64>(0)).unwrap();
+        assert_eq!(count(), 3);
+
+        // Over the threshold, but the last check was under a minute ago
+        db.set("more", b"4", &SetOptions::default()).unwrap();
+        assert_eq!(count(), 4);
+
+        db.conn.execute("DELETE FROM meta WHERE name = 'auto_gc.last_check'", []).unwrap();
+        db.set("more", b"5", &SetOptions::default()).unwrap();
+        assert_eq!(count(), 4);
+        assert!(db.get("keep", &GetOptions::default()).is_ok());
+        assert!(db.last_gc().unwrap().is_some());
+
+        // A recently deleted key survived the pass and can still be restored
+        assert_eq!(db.restore("gone", None).unwrap(), 1);
+        assert_eq!(db.get("gone", &GetOptions::default()).unwrap().value, b"2");
+
+        // An elapsed interval triggers a GC too, and deletes past their retention are collected
+        let interval = AutoGcConfig {
+            interval: Some(chrono::Duration::zero()),
+            deleted_retention: chrono::Duration::zero(),
+            ..Default::default()
+        };
+        db.set_auto_gc_config(&interval).unwrap();
+        assert_eq!(db.auto_gc_config().unwrap(), interval);
+        db.delete("gone", &DeleteOptions::default()).unwrap();
+        db.set("old", b"1", &expired).unwrap();
+        db.conn.execute("DELETE FROM meta WHERE name = 'auto_gc.last_check'", []).unwrap();
+        assert_eq!(db.maybe_gc().unwrap().unwrap().entries_count, 2);
+        db.set_auto_gc_config(&AutoGcConfig { enabled: false, ..interval }).unwrap();
+        db.set("old", b"1", &expired).unwrap();
+        db.conn.execute("DELETE FROM meta WHERE name = 'auto_gc.last_check'", []).unwrap();
+        assert!(db.maybe_gc().unwrap().is_none());
+
+        let manual = Database::builder().in_memory().auto_gc(false).open().unwrap();
+        manual.set_auto_gc_config(&AutoGcConfig { max_entries: Some(0), ..Default::default() }).unwrap();
+        manual.set("old", b"1", &expired).unwrap();
+        manual.set("old", b"1", &expired).unwrap();
+        assert!(manual.last_gc().unwrap().is_none());
+        manual.vacuum().unwrap();
+    }
+
+    #[test]
+    fn test_gc_counts_entry_matched_by_several_rules_once() {
+        let db = Database::open_in_memory().unwrap();
+        let expired = SetOptions { expires_at: Some(Utc::now() - chrono::Duration::seconds(1)), ..Default::default() };
+        db.set("k", b"1", &expired).unwrap();
+        db.delete("k", &DeleteOptions::default()).unwrap();
 
-        let total_deleted = ids_to_delete.len() as i64;
+        let opts = GcOptions { older_than: Some(chrono::Duration::zero()), keep_versions: Some(0), ..Default::default() };
+        let result = db.gc(&opts).unwrap();
+        assert_eq!((result.entries_count, result.bytes_freed), (1, 1));
+    }
 
-        // Actually delete if run is true
-        if run && !ids_to_delete.is_empty() {
-            for id in &ids_to_delete {
-                self.conn.execute("DELETE FROM entries WHERE id = ?1", [id])?;
+    #[test]
+    fn test_gc_keep_versions_per_key_and_scope() {
+        let db = Database::open_in_memory().unwrap();
+        for scope in [None, Some("s")] {
+            for value in [b"1", b"2", b"3"] {
+                db.set("k", value, &SetOptions { scope, ..Default::default() }).unwrap();
             }
         }
+        db.set("other", b"x", &SetOptions::default()).unwrap();
 
-        Ok(GcResult {
-            entries_count: total_deleted,
-            bytes_freed: total_bytes,
-            was_run: run,
-        })
+        assert_eq!(db.gc(&GcOptions { keep_versions: Some(1), ..Default::default() }).unwrap().entries_count, 4);
+        for scope in [None, Some("s")] {
+            let history = db.history("k", &HistoryOptions { scope, ..Default::default() }).unwrap();
+            assert_eq!(history.iter().map(|e| e.version).collect::<Vec<_>>(), [3]);
+        }
+        assert!(db.get("other", &GetOptions::default()).is_ok());
     }
-}
 
-#[derive(Debug, Clone)]
-pub struct GcResult {
-    pub entries_count: i64,
-    pub bytes_freed: i64,
-    pub was_run: bool,
+    #[test]
+    fn test_gc_dry_run_changes_nothing() {
+        let db = Database::open_in_memory().unwrap();
+        db.set("k", b"1", &SetOptions::default()).unwrap();
+        db.set("k", b"2", &SetOptions::default()).unwrap();
+        db.hset("h", "f", b"1", None).unwrap();
+        db.hset("h", "f", b"2", None).unwrap();
+        let changes = db.changes(0, None, None, true).unwrap().len();
+        let last_gc = db.last_gc().unwrap();
+
+        let result = db.gc(&GcOptions { dry_run: true, keep_versions: Some(1), ..Default::default() }).unwrap();
+        assert_eq!((result.entries_count, result.was_run), (2, false));
+        assert_eq!(db.history("k", &HistoryOptions::default()).unwrap().len(), 2);
+        assert_eq!(db.hget("h", "f", None).unwrap().version, 2);
+        assert_eq!(db.changes(0, None, None, true).unwrap().len(), changes);
+        assert_eq!(db.last_gc().unwrap(), last_gc);
+    }
+
+    #[test]
+    fn test_gc_labels_expire_and_gc_events() {
+        let db = Database::open_in_memory().unwrap();
+        db.set("gone", b"1", &SetOptions::default()).unwrap();
+        db.delete("gone", &DeleteOptions::default()).unwrap();
+        let expired = SetOptions { expires_at: Some(Utc::now() - chrono::Duration::seconds(1)), ..Default::default() };
+        db.set("old", b"2", &expired).unwrap();
+        let since = db.changes(0, None, None, true).unwrap().last().unwrap().seq;
+
+        assert_eq!(db.gc(&GcOptions::default()).unwrap().entries_count, 2);
+        let events: Vec<_> = db
+            .changes(since, None, None, true)
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.event, c.key))
+            .collect();
+        assert_eq!(events, [("gc".to_string(), "gone".to_string()), ("expire".to_string(), "old".to_string())]);
+    }
+
+    #[test]
+    fn test_gc_collects_hash_field_versions() {
+        let db = Database::open_in_memory().unwrap();
+        for value in [b"1", b"2", b"3"] {
+            db.hset("h", "a", value, None).unwrap();
+        }
+        db.hset("h", "b", b"x", None).unwrap();
+        db.hset("h", "c", b"y", None).unwrap();
+        db.hdel("h", "c", None).unwrap();
+
+        // Superseded versions of "a" plus the deleted "c"; "b" only has its latest version
+        assert_eq!(db.gc(&GcOptions { keep_versions: Some(1), ..Default::default() }).unwrap().entries_count, 3);
+        assert_eq!(db.hget("h", "a", None).unwrap().value, b"3");
+        assert_eq!(db.hget("h", "b", None).unwrap().value, b"x");
+        let remaining: i64 = db.conn.query_row("SELECT COUNT(*) FROM hash_fields", [], |row| row.get(0)).unwrap();
+        assert_eq!(remaining, 2);
+
+        let gc_events = db.changes(0, None, None, true).unwrap().into_iter().filter(|c| c.event == "gc").count();
+        assert_eq!(gc_events, 3);
+
+        db.hset("h", "b", b"z", None).unwrap();
+        let old = GcOptions { older_than: Some(chrono::Duration::zero()), ..Default::default() };
+        assert_eq!(db.gc(&old).unwrap().entries_count, 3);
+    }
+
+    #[test]
+    fn test_concurrent_sets() {
+        let dir = std::env::temp_dir().join(format!("kv-test-concurrent-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("kv.db");
+        Database::open_at(&path).unwrap();
+
+        let writers: Vec<_> = (0..4)
+            .map(|writer| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let db = Database::open_at(&path).unwrap();
+                    for i in 0..25 {
+                        db.set("shared", format!("{}-{}", writer, i).as_bytes(), &SetOptions::default()).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        // Every version landed exactly once, each with its change-log event
+        let db = Database::open_at(&path).unwrap();
+        let history = db.history("shared", &HistoryOptions::default()).unwrap();
+        assert_eq!(history.len(), 100);
+        assert_eq!(history[0].version, 100);
+        assert_eq!(db.changes(0, None, None, false).unwrap().len(), 100);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_schema_version() {
+        let dir = std::env::temp_dir().join(format!("kv-test-schema-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("kv.db");
+
+        let db = Database::open_at(&path).unwrap();
+        assert_eq!(Database::schema_version(&db.conn).unwrap(), SCHEMA_VERSION);
+
+        // A database from before versioning is migrated on its next open
+        db.conn.execute_batch("DROP TABLE policies; PRAGMA user_version = 0").unwrap();
+        drop(db);
+        let db = Database::open_at(&path).unwrap();
+        assert_eq!(Database::schema_version(&db.conn).unwrap(), SCHEMA_VERSION);
+        assert!(db.policies(None).unwrap().is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_conformance() {
+        crate::store::conformance::run(|| Box::new(Database::open_in_memory().unwrap()));
+    }
 }
@@ -26,12 +26,14 @@ impl InputSource {
         }
     }
 
+    /// MIME type from the file extension when there is a known one,
+    /// otherwise sniffed from the content
     pub fn content_type(&self) -> Option<&'static str> {
-        match self {
-            InputSource::Literal(_) => Some("text/plain"),
+        let from_extension = match self {
             InputSource::File { path, .. } => detect_content_type(path),
-            InputSource::Stdin(_) => None,
-        }
+            _ => None,
+        };
+        Some(from_extension.unwrap_or_else(|| sniff_content_type(self.content())))
     }
 }
 
@@ -60,6 +62,76 @@ fn detect_content_type(path: &str) -> Option<&'static str> {
     }
 }
 
+/// Guess a MIME type from magic bytes, falling back to the structure of text
+pub fn sniff_content_type(data: &[u8]) -> &'static str {
+    const MAGIC: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"PK\x05\x06", "application/zip"),
+    ];
+
+    if let Some((_, mime)) = MAGIC.iter().find(|(magic, _)| data.starts_with(magic)) {
+        return mime;
+    }
+
+    // NUL never shows up in text, but valid UTF-8 allows it
+    let text = match std::str::from_utf8(data) {
+        Ok(text) if !text.contains('\0') => text,
+        _ => return "application/octet-stream",
+    };
+
+    let trimmed = text.trim_start();
+    if (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && serde_json::from_str::<serde::de::IgnoredAny>(text).is_ok()
+    {
+        return "application/json";
+    }
+
+    if looks_like_yaml(text) {
+        return "application/yaml";
+    }
+
+    "text/plain"
+}
+
+/// A document marker, or at least two lines that are all mapping entries,
+/// list items, indented continuations or comments. A single `key: value`
+/// line is too common in prose to count.
+fn looks_like_yaml(text: &str) -> bool {
+    if text.starts_with("---\n") || text.starts_with("---\r\n") {
+        return true;
+    }
+
+    let lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+    if lines.len() < 2 {
+        return false;
+    }
+
+    let mut has_mapping = false;
+    let structured = lines.iter().all(|line| {
+        if line.starts_with([' ', '\t', '#']) || line.starts_with("- ") || *line == "-" {
+            return true;
+        }
+        match line.split_once(':') {
+            Some((key, rest)) if (rest.is_empty() || rest.starts_with(' ')) && is_yaml_key(key) => {
+                has_mapping = true;
+                true
+            }
+            _ => false,
+        }
+    });
+    structured && has_mapping
+}
+
+fn is_yaml_key(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
 pub fn detect_input(value: Option<&str>, literal: bool) -> io::Result<InputSource> {
     let stdin = io::stdin();
 
@@ -93,3 +165,39 @@ pub fn detect_input(value: Option<&str>, literal: bool) -> io::Result<InputSourc
     // Default to literal string
     Ok(InputSource::Literal(value.to_string()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_content_type() {
+        assert_eq!(sniff_content_type(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), "image/png");
+        assert_eq!(sniff_content_type(b"\xff\xd8\xff\xe0\0\x10JFIF"), "image/jpeg");
+        assert_eq!(sniff_content_type(b"%PDF-1.7\n"), "application/pdf");
+        assert_eq!(sniff_content_type(b"\x1f\x8b\x08\0"), "application/gzip");
+        assert_eq!(sniff_content_type(b"PK\x03\x04\x14\0"), "application/zip");
+        assert_eq!(sniff_content_type(b"  {\"a\": [1, 2]}\n"), "application/json");
+        assert_eq!(sniff_content_type(b"{not json"), "text/plain");
+        assert_eq!(sniff_content_type(b"42"), "text/plain");
+        assert_eq!(sniff_content_type(b"name: kv\ntags:\n  - cli\n"), "application/yaml");
+        assert_eq!(sniff_content_type(b"---\nfoo"), "application/yaml");
+        assert_eq!(sniff_content_type(b"note: buy milk"), "text/plain");
+        assert_eq!(sniff_content_type(b"Dear team: hello\nregards"), "text/plain");
+        assert_eq!(sniff_content_type("héllo".as_bytes()), "text/plain");
+        assert_eq!(sniff_content_type(b"\0\x01\x02"), "application/octet-stream");
+        assert_eq!(sniff_content_type(b"\xc3\x28"), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_extension_wins_over_sniffing() {
+        let file = |path: &str, content: &[u8]| InputSource::File {
+            path: path.into(),
+            content: content.to_vec(),
+        };
+        assert_eq!(file("notes.txt", b"{}").content_type(), Some("text/plain"));
+        assert_eq!(file("logo", b"\x89PNG\r\n\x1a\n").content_type(), Some("image/png"));
+        assert_eq!(InputSource::Stdin(b"[1]".to_vec()).content_type(), Some("application/json"));
+        assert_eq!(InputSource::Literal("plain".into()).content_type(), Some("text/plain"));
+    }
+}
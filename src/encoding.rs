@@ -0,0 +1,113 @@
+//! Text-safe representation of stored values for JSON output and input.
+//!
+//! Values that are valid UTF-8 are emitted as-is; anything else is base64
+//! encoded, and the `value_encoding` field next to the value says which.
+
+use crate::error::KvError;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueEncoding {
+    /// UTF-8 text, falling back to base64 for binary values
+    Utf8,
+    /// Always base64
+    Base64,
+}
+
+/// Encode a value for JSON, honoring `preferred` when the value allows it
+pub fn encode_value(value: &[u8], preferred: ValueEncoding) -> (String, ValueEncoding) {
+    match (preferred, std::str::from_utf8(value)) {
+        (ValueEncoding::Utf8, Ok(text)) => (text.to_string(), ValueEncoding::Utf8),
+        _ => (base64::encode(value), ValueEncoding::Base64),
+    }
+}
+
+pub fn decode_value(key: &str, value: &str, encoding: ValueEncoding) -> Result<Vec<u8>, KvError> {
+    match encoding {
+        ValueEncoding::Utf8 => Ok(value.as_bytes().to_vec()),
+        ValueEncoding::Base64 => base64::decode(value).ok_or_else(|| KvError::Decode {
+            key: key.to_string(),
+            format: "base64",
+            message: "invalid base64 value".into(),
+        }),
+    }
+}
+
+/// Standard padded base64 (to avoid adding a base64 crate)
+mod base64 {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    pub fn encode(bytes: &[u8]) -> String {
+        let mut s = String::with_capacity(bytes.len().div_ceil(3) * 4);
+        for chunk in bytes.chunks(3) {
+            let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+            let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    s.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+                } else {
+                    s.push('=');
+                }
+            }
+        }
+        s
+    }
+
+    pub fn decode(s: &str) -> Option<Vec<u8>> {
+        let s = s.as_bytes();
+        if !s.len().is_multiple_of(4) {
+            return None;
+        }
+
+        let mut out = Vec::with_capacity(s.len() / 4 * 3);
+        for (i, chunk) in s.chunks(4).enumerate() {
+            let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+            if padding > 2 || (padding > 0 && i != s.len() / 4 - 1) {
+                return None;
+            }
+
+            let mut n = 0u32;
+            for &c in &chunk[..4 - padding] {
+                let v = ALPHABET.iter().position(|&a| a == c)? as u32;
+                n = n << 6 | v;
+            }
+            n <<= 6 * padding as u32;
+
+            let bytes = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+            out.extend_from_slice(&bytes[..3 - padding]);
+        }
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_round_trip() {
+        for (plain, encoded) in [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foobar", "Zm9vYmFy")] {
+            assert_eq!(base64::encode(plain.as_bytes()), encoded);
+            assert_eq!(base64::decode(encoded).unwrap(), plain.as_bytes());
+        }
+
+        let binary: Vec<u8> = (0..=255).collect();
+        assert_eq!(base64::decode(&base64::encode(&binary)).unwrap(), binary);
+        assert!(base64::decode("Zm9").is_none());
+        assert!(base64::decode("Zg==Zm9v").is_none());
+        assert!(base64::decode("Z!==").is_none());
+    }
+
+    #[test]
+    fn test_encode_value_falls_back_to_base64() {
+        assert_eq!(encode_value(b"hi", ValueEncoding::Utf8), ("hi".into(), ValueEncoding::Utf8));
+        assert_eq!(encode_value(b"hi", ValueEncoding::Base64), ("aGk=".into(), ValueEncoding::Base64));
+        assert_eq!(encode_value(&[0xff, 0x00], ValueEncoding::Utf8), ("/wA=".into(), ValueEncoding::Base64));
+        assert_eq!(decode_value("k", "/wA=", ValueEncoding::Base64).unwrap(), vec![0xff, 0x00]);
+        assert!(matches!(
+            decode_value("k", "@@@@", ValueEncoding::Base64),
+            Err(KvError::Decode { format: "base64", .. })
+        ));
+    }
+}
@@ -8,6 +8,23 @@ pub enum KvError {
     Io(std::io::Error),
     SizeLimitExceeded { size: u64, limit: u64 },
     InvalidTtl(String),
+    NotAnInteger(String),
+    IntegerOverflow(String),
+    ListEmpty(String),
+    FieldNotFound { key: String, field: String },
+    LockHeld(String),
+    LockNotHeld(String),
+    WatchTimeout,
+    VersionMismatch { key: String, expected: i64, actual: i64 },
+    Daemon(String),
+    Encode { format: &'static str, message: String },
+    Decode { key: String, format: &'static str, message: String },
+    InvalidQuery(String),
+    PatchFailed(String),
+    InvalidSchema(String),
+    SchemaNotFound(String),
+    PolicyNotFound(String),
+    ValidationFailed { key: String, violations: Vec<String> },
 }
 
 impl fmt::Display for KvError {
@@ -27,6 +44,36 @@ impl fmt::Display for KvError {
                 )
             }
             KvError::InvalidTtl(msg) => write!(f, "invalid TTL: {}", msg),
+            KvError::NotAnInteger(key) => write!(f, "value is not an integer: {}", key),
+            KvError::IntegerOverflow(key) => write!(f, "integer overflow for key: {}", key),
+            KvError::ListEmpty(key) => write!(f, "list is empty: {}", key),
+            KvError::FieldNotFound { key, field } => {
+                write!(f, "field {} not found for key: {}", field, key)
+            }
+            KvError::LockHeld(name) => write!(f, "lock is held by another owner: {}", name),
+            KvError::LockNotHeld(name) => write!(f, "lock not held: {}", name),
+            KvError::WatchTimeout => write!(f, "timed out waiting for a change"),
+            KvError::VersionMismatch { key, expected, actual } => {
+                write!(f, "version mismatch for key {}: expected {}, found {}", key, expected, actual)
+            }
+            // Errors relayed from the daemon are already formatted
+            KvError::Daemon(msg) => write!(f, "{}", msg),
+            KvError::Encode { format, message } => write!(f, "failed to encode value as {}: {}", format, message),
+            KvError::Decode { key, format, message } => {
+                write!(f, "failed to decode {} value for key {}: {}", format, key, message)
+            }
+            KvError::InvalidQuery(msg) => write!(f, "invalid query: {}", msg),
+            KvError::PatchFailed(msg) => write!(f, "patch failed: {}", msg),
+            KvError::InvalidSchema(msg) => write!(f, "invalid schema: {}", msg),
+            KvError::SchemaNotFound(pattern) => write!(f, "no schema for pattern: {}", pattern),
+            KvError::PolicyNotFound(pattern) => write!(f, "no policy for pattern: {}", pattern),
+            KvError::ValidationFailed { key, violations } => {
+                write!(f, "value for key {} does not match its schema:", key)?;
+                for violation in violations {
+                    write!(f, "\n  {}", violation)?;
+                }
+                Ok(())
+            }
         }
     }
 }
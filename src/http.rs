@@ -0,0 +1,676 @@
+//! Minimal HTTP/1.1 server exposing the store as a REST API.
+//!
+//! Routes (scope is a scope hash, or `global`):
+//!   GET    /v1/_stats
+//!   GET    /v1/{scope}?prefix=p&limit=n      list keys
+//!   GET    /v1/{scope}/{key}?version=n       raw value, ETag = version
+//!   GET    /v1/{scope}/{key}?history         version history
+//!   PUT    /v1/{scope}/{key}?ttl=1h          set (honors If-Match)
+//!   DELETE /v1/{scope}/{key}?hard            delete (honors If-Match)
+
+use crate::commands::set::SIZE_LIMIT;
+use crate::db::Database;
+use crate::error::KvError;
+use crate::store::{DeleteOptions, GetOptions, HistoryOptions, KvStore, ListOptions, SetOptions, SetResult};
+use crate::ttl::parse_ttl;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Longest request or header line accepted, including the trailing CRLF
+const MAX_LINE_LEN: u64 = 8 * 1024;
+
+/// Most header lines accepted in one request
+const MAX_HEADERS: usize = 100;
+
+/// How long a connection may sit idle mid-request before it is dropped
+const READ_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+#[derive(Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_ascii_lowercase()).map(String::as_str)
+    }
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    fn json<T: Serialize>(status: u16, value: &T) -> Self {
+        Response {
+            status,
+            headers: vec![("Content-Type".into(), "application/json".into())],
+            body: serde_json::to_vec(value).unwrap(),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self::json(status, &ErrorJson { error: message.to_string() })
+    }
+
+    fn empty(status: u16) -> Self {
+        Response {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+}
+
+/// A request that could not be read, with the status to reply with
+#[derive(Debug)]
+struct RequestError {
+    status: u16,
+    message: String,
+}
+
+impl RequestError {
+    fn new(status: u16, message: &str) -> Self {
+        RequestError {
+            status,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<io::Error> for RequestError {
+    fn from(err: io::Error) -> Self {
+        let status = match err.kind() {
+            // The read timeout expired before the client finished sending
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => 408,
+            _ => 400,
+        };
+        RequestError {
+            status,
+            message: err.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorJson {
+    error: String,
+}
+
+#[derive(Serialize)]
+struct WriteJson {
+    key: String,
+    version: i64,
+    saved: bool,
+}
+
+#[derive(Serialize)]
+struct DeleteJson {
+    key: String,
+    deleted: u64,
+}
+
+#[derive(Serialize)]
+struct KeyJson {
+    key: String,
+    versions: i64,
+    size: i64,
+    last_updated: String,
+}
+
+#[derive(Serialize)]
+struct HistoryJson {
+    version: i64,
+    size: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_type: Option<String>,
+    created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deleted_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<String>,
+}
+
+#[derive(Serialize)]
+struct StatsJson {
+    total_size: i64,
+    total_entries: i64,
+    active_keys: i64,
+    deleted_keys: i64,
+    expired_keys: i64,
+}
+
+/// Serve requests until the process is killed. Each connection gets its own
+/// thread; all of them share one `Database` behind a mutex.
+pub fn serve(db: Database, bind: &str, token: Option<String>) -> Result<(), KvError> {
+    let listener = TcpListener::bind(bind)?;
+    eprintln!("listening on http://{}", listener.local_addr()?);
+
+    let db = Arc::new(Mutex::new(db));
+    let token = Arc::new(token);
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("warning: accept failed: {}", e);
+                continue;
+            }
+        };
+        if let Err(e) = stream.set_read_timeout(Some(READ_TIMEOUT)) {
+            eprintln!("warning: could not set read timeout: {}", e);
+            continue;
+        }
+        let db = Arc::clone(&db);
+        let token = Arc::clone(&token);
+        thread::spawn(move || {
+            if let Err(e) = handle_connection(stream, &db, token.as_deref()) {
+                eprintln!("warning: connection error: {}", e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+fn handle_connection(stream: TcpStream, db: &Mutex<Database>, token: Option<&str>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let response = match read_request(&mut reader) {
+        Ok(req) => {
+            let db = db.lock().unwrap_or_else(|e| e.into_inner());
+            handle(&db, &req, token)
+        }
+        Err(e) => Response::error(e.status, &e.message),
+    };
+    write_response(stream, &response)
+}
+
+/// Read one CRLF-terminated line, failing with `status` if it exceeds `MAX_LINE_LEN`
+fn read_line<R: BufRead>(reader: &mut R, status: u16, message: &str) -> Result<String, RequestError> {
+    let mut line = String::new();
+    let n = reader.take(MAX_LINE_LEN).read_line(&mut line)?;
+    if n as u64 == MAX_LINE_LEN && !line.ends_with('\n') {
+        return Err(RequestError::new(status, message));
+    }
+    Ok(line)
+}
+
+fn read_request<R: BufRead>(reader: &mut R) -> Result<Request, RequestError> {
+    let invalid = |msg: &str| RequestError::new(400, msg);
+
+    let line = read_line(reader, 414, "request line too long")?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().ok_or_else(|| invalid("missing method"))?.to_string();
+    let target = parts.next().ok_or_else(|| invalid("missing request target"))?;
+
+    let (path, query_str) = target.split_once('?').unwrap_or((target, ""));
+    let query = query_str
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|pair| {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(k), percent_decode(v))
+        })
+        .collect();
+
+    let mut headers = HashMap::new();
+    for count in 0.. {
+        let line = read_line(reader, 431, "header line too long")?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if count == MAX_HEADERS {
+            return Err(RequestError::new(431, "too many headers"));
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    if headers.contains_key("transfer-encoding") {
+        return Err(invalid("chunked bodies are not supported, send Content-Length"));
+    }
+
+    let length: u64 = headers
+        .get("content-length")
+        .map(|v| v.parse().map_err(|_| invalid("invalid Content-Length")))
+        .transpose()?
+        .unwrap_or(0);
+    if length > SIZE_LIMIT {
+        return Err(RequestError::new(413, "request body too large"));
+    }
+
+    let mut body = vec![0; length as usize];
+    reader.read_exact(&mut body)?;
+
+    Ok(Request {
+        method,
+        path: path.to_string(),
+        query,
+        headers,
+        body,
+    })
+}
+
+fn write_response(mut stream: TcpStream, response: &Response) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        reason(response.status),
+        response.body.len()
+    )?;
+    for (name, value) in &response.headers {
+        write!(stream, "{}: {}\r\n", name, value)?;
+    }
+    stream.write_all(b"\r\n")?;
+    stream.write_all(&response.body)?;
+    stream.flush()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        412 => "Precondition Failed",
+        413 => "Payload Too Large",
+        414 => "URI Too Long",
+        422 => "Unprocessable Content",
+        431 => "Request Header Fields Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Route a parsed request. Kept free of I/O so it can be exercised directly in tests.
+pub fn handle(db: &Database, req: &Request, token: Option<&str>) -> Response {
+    if let Some(expected) = token {
+        let presented = req.header("authorization").and_then(|h| h.strip_prefix("Bearer "));
+        if !presented.is_some_and(|p| constant_time_eq(p.as_bytes(), expected.as_bytes())) {
+            return Response::error(401, "missing or invalid bearer token");
+        }
+    }
+
+    let Some(rest) = req.path.strip_prefix("/v1/") else {
+        return Response::error(404, "not found");
+    };
+
+    let result = match rest.split_once('/') {
+        None if rest == "_stats" && req.method == "GET" => stats(db),
+        None if req.method == "GET" => list(db, req, scope_from(rest)),
+        Some((scope, key)) if !key.is_empty() => {
+            let key = percent_decode(key);
+            let scope = scope_from(scope);
+            match req.method.as_str() {
+                "GET" if req.query.contains_key("history") => history(db, &key, scope),
+                "GET" => get(db, req, &key, scope),
+                "PUT" => put(db, req, &key, scope),
+                "DELETE" => delete(db, req, &key, scope),
+                _ => return Response::error(405, "method not allowed"),
+            }
+        }
+        _ => return Response::error(404, "not found"),
+    };
+
+    result.unwrap_or_else(|e| Response::error(status_for(&e), &e.to_string()))
+}
+
+/// Compare secrets without exiting early on the first differing byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn scope_from(segment: &str) -> Option<&str> {
+    if segment == "global" {
+        None
+    } else {
+        Some(segment)
+    }
+}
+
+fn status_for(err: &KvError) -> u16 {
+    match err {
+        KvError::KeyNotFound(_)
+        | KvError::VersionNotFound { .. }
+        | KvError::FieldNotFound { .. }
+        | KvError::SchemaNotFound(_)
+        | KvError::PolicyNotFound(_) => 404,
+        KvError::VersionMismatch { .. } => 412,
+        KvError::SizeLimitExceeded { .. } => 413,
+        KvError::InvalidTtl(_)
+        | KvError::InvalidQuery(_)
+        | KvError::InvalidSchema(_)
+        | KvError::Decode { .. } => 400,
+        KvError::ValidationFailed { .. }
+        | KvError::PatchFailed(_)
+        | KvError::NotAnInteger(_)
+        | KvError::IntegerOverflow(_) => 422,
+        _ => 500,
+    }
+}
+
+/// Parse an `If-Match` / `If-None-Match` value into a version. `*` means "any existing version".
+fn parse_etag(value: &str) -> Option<Option<i64>> {
+    let value = value.trim();
+    if value == "*" {
+        return Some(None);
+    }
+    let value = value.strip_prefix("W/").unwrap_or(value);
+    value.trim_matches('"').parse().ok().map(Some)
+}
+
+/// The `If-Match` precondition, if one was sent. A value that isn't `*` or a
+/// single version tag can never match, so it fails the request instead of
+/// being ignored.
+fn if_match(req: &Request) -> Result<Option<Option<i64>>, Response> {
+    match req.header("if-match") {
+        None => Ok(None),
+        Some(value) => parse_etag(value)
+            .map(Some)
+            .ok_or_else(|| Response::error(412, "If-Match must be * or a single version tag")),
+    }
+}
+
+fn etag(version: i64) -> String {
+    format!("\"{}\"", version)
+}
+
+fn stats(db: &Database) -> Result<Response, KvError> {
+    let s = db.stats()?;
+    Ok(Response::json(
+        200,
+        &StatsJson {
+            total_size: s.total_size,
+            total_entries: s.total_entries,
+            active_keys: s.active_keys,
+            deleted_keys: s.deleted_keys,
+            expired_keys: s.expired_keys,
+        },
+    ))
+}
+
+fn list(db: &Database, req: &Request, scope: Option<&str>) -> Result<Response, KvError> {
+    let prefix = req.query.get("prefix").map(String::as_str);
+    let limit = req.query.get("limit").and_then(|l| l.parse().ok());
+    let keys = db.list_keys(&ListOptions {
+        scope,
+        prefix,
+        limit,
+        ..Default::default()
+    })?;
+
+    let output: Vec<KeyJson> = keys
+        .into_iter()
+        .map(|s| KeyJson {
+            key: s.key,
+            versions: s.versions,
+            size: s.total_size,
+            last_updated: s.last_updated.to_rfc3339(),
+        })
+        .collect();
+    Ok(Response::json(200, &output))
+}
+
+fn history(db: &Database, key: &str, scope: Option<&str>) -> Result<Response, KvError> {
+    let entries = db.history(key, &HistoryOptions { scope, limit: None })?;
+    let output: Vec<HistoryJson> = entries
+        .into_iter()
+        .map(|e| HistoryJson {
+            version: e.version,
+            size: e.size_bytes,
+            content_type: e.content_type,
+            created_at: e.created_at.to_rfc3339(),
+            deleted_at: e.deleted_at.map(|dt| dt.to_rfc3339()),
+            expires_at: e.expires_at.map(|dt| dt.to_rfc3339()),
+        })
+        .collect();
+    Ok(Response::json(200, &output))
+}
+
+fn get(db: &Database, req: &Request, key: &str, scope: Option<&str>) -> Result<Response, KvError> {
+    let version = req.query.get("version").and_then(|v| v.parse().ok());
+    let entry = db.get(key, &GetOptions { scope, version })?;
+
+    if let Some(Some(v)) = req.header("if-none-match").and_then(parse_etag) {
+        if v == entry.version {
+            let mut response = Response::empty(304);
+            response.headers.push(("ETag".into(), etag(entry.version)));
+            return Ok(response);
+        }
+    }
+
+    let content_type = entry
+        .content_type
+        .clone()
+        .unwrap_or_else(|| "application/octet-stream".into());
+    Ok(Response {
+        status: 200,
+        headers: vec![
+            ("Content-Type".into(), content_type),
+            ("ETag".into(), etag(entry.version)),
+        ],
+        body: entry.value,
+    })
+}
+
+fn put(db: &Database, req: &Request, key: &str, scope: Option<&str>) -> Result<Response, KvError> {
+    let expires_at = req.query.get("ttl").map(|t| parse_ttl(t)).transpose()?;
+    let content_type = req.header("content-type");
+
+    let opts = SetOptions {
+        scope,
+        content_type,
+        expires_at,
+        ..Default::default()
+    };
+
+    let condition = match if_match(req) {
+        Ok(condition) => condition,
+        Err(response) => return Ok(response),
+    };
+    let SetResult { version, was_saved: saved } = match condition {
+        Some(Some(expected)) => db.set_if_version(key, &req.body, &opts, expected)?,
+        // If-Match: * only requires that the key exists
+        Some(None) => match db.set_if_exists(key, &req.body, &opts) {
+            Err(KvError::KeyNotFound(_)) => return Ok(Response::error(412, "precondition failed: key does not exist")),
+            result => result?,
+        },
+        None => db.set(key, &req.body, &opts)?,
+    };
+
+    let mut response = Response::json(
+        if saved { 201 } else { 200 },
+        &WriteJson {
+            key: key.to_string(),
+            version,
+            saved,
+        },
+    );
+    response.headers.push(("ETag".into(), etag(version)));
+    Ok(response)
+}
+
+fn delete(db: &Database, req: &Request, key: &str, scope: Option<&str>) -> Result<Response, KvError> {
+    let opts = DeleteOptions {
+        scope,
+        hard: req.query.contains_key("hard"),
+    };
+    let condition = match if_match(req) {
+        Ok(condition) => condition,
+        Err(response) => return Ok(response),
+    };
+    let deleted = match condition {
+        Some(Some(expected)) => db.delete_if_version(key, &opts, expected)?,
+        Some(None) => match db.delete(key, &opts) {
+            Err(KvError::KeyNotFound(_)) => return Ok(Response::error(412, "precondition failed: key does not exist")),
+            result => result?,
+        },
+        None => db.delete(key, &opts)?,
+    };
+
+    Ok(Response::json(
+        200,
+        &DeleteJson {
+            key: key.to_string(),
+            deleted,
+        },
+    ))
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(b) => {
+                        out.push(b);
+                        i += 3;
+                        continue;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, target: &str, headers: &[(&str, &str)], body: &[u8]) -> Request {
+        let mut raw = format!("{} {} HTTP/1.1\r\nContent-Length: {}\r\n", method, target, body.len());
+        for (name, value) in headers {
+            raw.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        raw.push_str("\r\n");
+        let mut raw = raw.into_bytes();
+        raw.extend_from_slice(body);
+        read_request(&mut &raw[..]).unwrap()
+    }
+
+    #[test]
+    fn test_put_get_with_etags() {
+        let db = Database::open_in_memory().unwrap();
+
+        let res = handle(&db, &request("PUT", "/v1/global/a%2Fb", &[("Content-Type", "text/plain")], b"hi"), None);
+        assert_eq!(res.status, 201);
+
+        let res = handle(&db, &request("GET", "/v1/global/a/b", &[], b""), None);
+        assert_eq!(res.status, 200);
+        assert_eq!(res.body, b"hi");
+        assert!(res.headers.contains(&("ETag".into(), "\"1\"".into())));
+
+        let res = handle(&db, &request("GET", "/v1/global/a/b", &[("If-None-Match", "\"1\"")], b""), None);
+        assert_eq!(res.status, 304);
+
+        let res = handle(&db, &request("PUT", "/v1/global/a/b", &[("If-Match", "\"7\"")], b"x"), None);
+        assert_eq!(res.status, 412);
+
+        let res = handle(&db, &request("PUT", "/v1/global/a/b", &[("If-Match", "\"1\"")], b"x"), None);
+        assert_eq!(res.status, 201);
+
+        // An If-Match that can't be understood never falls back to an unconditional write
+        for value in ["\"abc\"", "\"2\", \"3\""] {
+            let res = handle(&db, &request("PUT", "/v1/global/a/b", &[("If-Match", value)], b"y"), None);
+            assert_eq!(res.status, 412);
+            let res = handle(&db, &request("DELETE", "/v1/global/a/b", &[("If-Match", value)], b""), None);
+            assert_eq!(res.status, 412);
+        }
+        assert_eq!(db.get("a/b", &GetOptions::default()).unwrap().value, b"x");
+
+        let res = handle(&db, &request("GET", "/v1/global/a/b?version=1", &[], b""), None);
+        assert_eq!(res.body, b"hi");
+
+        let res = handle(&db, &request("PUT", "/v1/global/a/b", &[("If-Match", "*")], b"y"), None);
+        assert_eq!(res.status, 201);
+        let res = handle(&db, &request("PUT", "/v1/global/missing", &[("If-Match", "*")], b"y"), None);
+        assert_eq!(res.status, 412);
+        let res = handle(&db, &request("DELETE", "/v1/global/missing", &[("If-Match", "*")], b""), None);
+        assert_eq!(res.status, 412);
+        let res = handle(&db, &request("DELETE", "/v1/global/missing", &[], b""), None);
+        assert_eq!(res.status, 404);
+    }
+
+    #[test]
+    fn test_rejects_oversized_requests() {
+        let raw = format!("PUT /v1/global/k HTTP/1.1\r\nContent-Length: {}\r\n\r\n", SIZE_LIMIT + 1);
+        assert_eq!(read_request(&mut raw.as_bytes()).unwrap_err().status, 413);
+
+        let raw = format!("GET /v1/global/k HTTP/1.1\r\nX-Long: {}\r\n\r\n", "a".repeat(MAX_LINE_LEN as usize));
+        assert_eq!(read_request(&mut raw.as_bytes()).unwrap_err().status, 431);
+
+        let raw = format!("GET /v1/global/{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE_LEN as usize));
+        assert_eq!(read_request(&mut raw.as_bytes()).unwrap_err().status, 414);
+
+        let raw = format!("GET /v1/global/k HTTP/1.1\r\n{}\r\n", "X-A: b\r\n".repeat(MAX_HEADERS + 1));
+        assert_eq!(read_request(&mut raw.as_bytes()).unwrap_err().status, 431);
+
+        assert_eq!(RequestError::from(io::Error::from(io::ErrorKind::WouldBlock)).status, 408);
+    }
+
+    #[test]
+    fn test_client_errors_are_4xx() {
+        let decode = KvError::Decode {
+            key: "k".into(),
+            format: "msgpack",
+            message: "bad".into(),
+        };
+        assert_eq!(status_for(&decode), 400);
+        assert_eq!(status_for(&KvError::InvalidQuery("q".into())), 400);
+        assert_eq!(status_for(&KvError::InvalidSchema("s".into())), 400);
+        assert_eq!(status_for(&KvError::NotAnInteger("k".into())), 422);
+        assert_eq!(status_for(&KvError::SchemaNotFound("s".into())), 404);
+        assert_eq!(status_for(&KvError::Database("locked".into())), 500);
+    }
+
+    #[test]
+    fn test_list_history_delete_and_auth() {
+        let db = Database::open_in_memory().unwrap();
+        db.set("p/1", b"a", &SetOptions { scope: Some("s"), ..Default::default() }).unwrap();
+        db.set("p/1", b"b", &SetOptions { scope: Some("s"), ..Default::default() }).unwrap();
+        db.set("q", b"c", &SetOptions { scope: Some("s"), ..Default::default() }).unwrap();
+
+        let res = handle(&db, &request("GET", "/v1/s?prefix=p/", &[], b""), None);
+        let keys: serde_json::Value = serde_json::from_slice(&res.body).unwrap();
+        assert_eq!(keys.as_array().unwrap().len(), 1);
+
+        let res = handle(&db, &request("GET", "/v1/s/p/1?history", &[], b""), None);
+        let history: serde_json::Value = serde_json::from_slice(&res.body).unwrap();
+        assert_eq!(history[0]["version"], 2);
+
+        let res = handle(&db, &request("DELETE", "/v1/s/q", &[], b""), Some("secret"));
+        assert_eq!(res.status, 401);
+        let res = handle(&db, &request("DELETE", "/v1/s/q", &[("Authorization", "Bearer secreT")], b""), Some("secret"));
+        assert_eq!(res.status, 401);
+        let res = handle(&db, &request("DELETE", "/v1/s/q", &[("Authorization", "Bearer secret")], b""), Some("secret"));
+        assert_eq!(res.status, 200);
+
+        let res = handle(&db, &request("GET", "/v1/s/q", &[], b""), None);
+        assert_eq!(res.status, 404);
+    }
+}
@@ -1,10 +1,31 @@
+#[cfg(feature = "async")]
+pub mod async_db;
 pub mod commands;
+#[cfg(unix)]
+pub mod daemon;
 pub mod db;
 pub mod detection;
+pub mod encoding;
 pub mod error;
+pub mod http;
+pub mod mcp;
+pub mod memory;
+pub mod patch;
+pub mod query;
+pub mod resp;
+pub mod schema;
 pub mod scope;
+pub mod store;
+pub mod ttl;
 
-pub use db::{Database, Entry, KeySummary};
+#[cfg(feature = "async")]
+pub use async_db::AsyncDatabase;
+pub use db::{Database, DatabaseBuilder};
 pub use detection::{detect_input, InputSource};
 pub use error::KvError;
+pub use memory::MemoryStore;
 pub use scope::current_scope;
+pub use store::{
+    DeleteOptions, Entry, GcOptions, GcResult, GetOptions, HistoryOptions, IncrOptions, KeySummary, KvStore,
+    ListOptions, ScopeStats, SetOptions, SetResult, Stats,
+};
@@ -1,11 +1,8 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
-
-mod commands;
-mod db;
-mod detection;
-mod error;
-mod scope;
+use douglance_kv::commands;
+use douglance_kv::commands::gc::Threshold;
+use douglance_kv::encoding::ValueEncoding;
 
 #[derive(Parser)]
 #[command(name = "kv")]
@@ -38,9 +35,64 @@ enum Commands {
         #[arg(short, long)]
         global: bool,
 
-        /// Time-to-live (e.g., 30s, 5m, 1h, 7d)
+        /// Time-to-live (e.g., 30s, 1h30m, 2w, PT1H30M)
         #[arg(long)]
         ttl: Option<String>,
+
+        /// Expire at an absolute time (e.g., 2026-12-01T00:00:00Z)
+        #[arg(long, value_name = "TIME", conflicts_with = "ttl")]
+        expires_at: Option<String>,
+
+        /// Restart the TTL whenever the key is read
+        #[arg(long, requires = "ttl")]
+        sliding: bool,
+
+        /// Record this content type instead of the detected one
+        #[arg(long = "type", value_name = "MIME")]
+        content_type: Option<String>,
+
+        /// Input is a JSON object as printed by `get --json`
+        #[arg(long)]
+        from_json: bool,
+    },
+
+    /// Append to the value of a key (reads from stdin if piped, detects files)
+    Append {
+        /// The key to append to
+        key: String,
+
+        /// The value (string, file path, or omit for stdin)
+        value: Option<String>,
+
+        /// Insert between existing and new data (supports \n and \t)
+        #[arg(long)]
+        separator: Option<String>,
+
+        /// Treat value as literal string, skip file detection
+        #[arg(long)]
+        literal: bool,
+
+        /// Allow values larger than 100MB
+        #[arg(long)]
+        force: bool,
+
+        /// Update the latest version in place instead of creating a new one
+        #[arg(long)]
+        no_history: bool,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+    },
+
+    /// Apply a JSON Patch (RFC 6902 array) or merge patch (RFC 7386 object) from stdin
+    Patch {
+        /// The key holding the JSON document
+        key: String,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
     },
 
     /// Get the value for a key
@@ -63,6 +115,14 @@ enum Commands {
         /// Output as JSON
         #[arg(short, long)]
         json: bool,
+
+        /// How to encode the value in JSON output; binary values always use base64
+        #[arg(long, value_enum, default_value_t = ValueEncoding::Utf8)]
+        encoding: ValueEncoding,
+
+        /// Print only this field of a JSON value, e.g. '.config.model' or '.items[0]'
+        #[arg(long, value_name = "PATH")]
+        path: Option<String>,
     },
 
     /// List all keys or history of a specific key
@@ -85,6 +145,56 @@ enum Commands {
         /// Output as JSON
         #[arg(short, long)]
         json: bool,
+
+        /// How to encode values in JSON history output; binary values always use base64
+        #[arg(long, value_enum, default_value_t = ValueEncoding::Utf8)]
+        encoding: ValueEncoding,
+
+        /// Only list keys whose JSON value matches, e.g. '.status == "done"' or '.retries > 3'
+        #[arg(long = "where", value_name = "FILTER", conflicts_with = "key")]
+        filter: Option<String>,
+    },
+
+    /// Change when an existing key expires, without writing a new version
+    Expire {
+        /// The key to expire
+        key: String,
+
+        /// Time-to-live from now (e.g., 30s, 1h30m, 2w, PT1H30M)
+        #[arg(required_unless_present = "at")]
+        ttl: Option<String>,
+
+        /// Expire at an absolute time instead (e.g., 2026-12-01T00:00:00Z)
+        #[arg(long, value_name = "TIME", conflicts_with = "ttl")]
+        at: Option<String>,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+    },
+
+    /// Show the seconds a key has left (-1 if it never expires)
+    Ttl {
+        /// The key to inspect
+        key: String,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+
+        /// Output as JSON
+        #[arg(short, long)]
+        json: bool,
+    },
+
+    /// Remove the expiry from an existing key, without writing a new version
+    Persist {
+        /// The key to keep
+        key: String,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
     },
 
     /// Delete a key
@@ -101,6 +211,345 @@ enum Commands {
         global: bool,
     },
 
+    /// Atomically increment an integer value
+    Incr {
+        /// The key to increment
+        key: String,
+
+        /// Amount to add
+        #[arg(long, default_value_t = 1, allow_negative_numbers = true)]
+        by: i64,
+
+        /// Update the latest version in place instead of creating a new one
+        #[arg(long)]
+        no_history: bool,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+    },
+
+    /// Atomically decrement an integer value
+    Decr {
+        /// The key to decrement
+        key: String,
+
+        /// Amount to subtract
+        #[arg(long, default_value_t = 1, allow_negative_numbers = true)]
+        by: i64,
+
+        /// Update the latest version in place instead of creating a new one
+        #[arg(long)]
+        no_history: bool,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+    },
+
+    /// Push a value onto a list (reads from stdin if piped, detects files)
+    Push {
+        /// The list key
+        key: String,
+
+        /// The value (string, file path, or omit for stdin)
+        value: Option<String>,
+
+        /// Treat value as literal string, skip file detection
+        #[arg(long)]
+        literal: bool,
+
+        /// Allow values larger than 100MB
+        #[arg(long)]
+        force: bool,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+
+        /// Time-to-live for this item (e.g., 30s, 1h30m, 2w, PT1H30M)
+        #[arg(long)]
+        ttl: Option<String>,
+    },
+
+    /// Remove and print the oldest item of a list
+    Pop {
+        /// The list key
+        key: String,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+    },
+
+    /// Print the oldest item of a list without removing it
+    Peek {
+        /// The list key
+        key: String,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+    },
+
+    /// Print the number of items in a list
+    Len {
+        /// The list key
+        key: String,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+    },
+
+    /// Set a field of a hash (reads from stdin if piped, detects files)
+    Hset {
+        /// The hash key
+        key: String,
+
+        /// The field to set
+        field: String,
+
+        /// The value (string, file path, or omit for stdin)
+        value: Option<String>,
+
+        /// Treat value as literal string, skip file detection
+        #[arg(long)]
+        literal: bool,
+
+        /// Allow values larger than 100MB
+        #[arg(long)]
+        force: bool,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+    },
+
+    /// Get a field of a hash
+    Hget {
+        /// The hash key
+        key: String,
+
+        /// The field to retrieve
+        field: String,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+    },
+
+    /// Delete a field of a hash
+    Hdel {
+        /// The hash key
+        key: String,
+
+        /// The field to delete
+        field: String,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+    },
+
+    /// Get all fields of a hash
+    Hgetall {
+        /// The hash key
+        key: String,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+
+        /// Output as JSON
+        #[arg(short, long)]
+        json: bool,
+    },
+
+    /// Acquire (or renew) a lease-based lock and print its owner token
+    Lock {
+        /// The lock name
+        name: String,
+
+        /// Lease duration (e.g., 30s, 5m, 1h)
+        #[arg(long, default_value = "30s")]
+        ttl: String,
+
+        /// Keep retrying for up to this long if the lock is held
+        #[arg(long)]
+        wait: Option<String>,
+
+        /// Extend an existing lease instead of acquiring
+        #[arg(long, requires = "token")]
+        renew: bool,
+
+        /// Owner token (generated if omitted)
+        #[arg(long)]
+        token: Option<String>,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+    },
+
+    /// Release a lock
+    Unlock {
+        /// The lock name
+        name: String,
+
+        /// Owner token returned by `kv lock`
+        #[arg(long, required_unless_present = "force")]
+        token: Option<String>,
+
+        /// Release regardless of owner
+        #[arg(long)]
+        force: bool,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+    },
+
+    /// Run a command while holding a lock
+    WithLock {
+        /// The lock name
+        name: String,
+
+        /// Lease duration, renewed while the command runs (e.g., 30s, 5m)
+        #[arg(long, default_value = "30s")]
+        ttl: String,
+
+        /// Keep retrying for up to this long if the lock is held
+        #[arg(long)]
+        wait: Option<String>,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+
+        /// The command to run
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+
+    /// Block until a key (or any key under a prefix) changes
+    Watch {
+        /// The key to watch
+        #[arg(required_unless_present = "prefix", conflicts_with = "prefix")]
+        key: Option<String>,
+
+        /// Watch every key starting with this prefix
+        #[arg(long)]
+        prefix: Option<String>,
+
+        /// Give up after this long (e.g., 30s, 5m)
+        #[arg(long)]
+        timeout: Option<String>,
+
+        /// Return immediately if the key is already newer than this version
+        #[arg(long)]
+        after_version: Option<i64>,
+
+        /// Keep streaming events instead of exiting after the first change
+        #[arg(long)]
+        follow: bool,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+
+        /// Output events as JSON lines
+        #[arg(short, long)]
+        json: bool,
+    },
+
+    /// Restore a soft-deleted key
+    Restore {
+        /// The key to restore
+        key: String,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+    },
+
+    /// Show the change log (set/delete/restore/gc/expire events)
+    Changes {
+        /// Only show events with a sequence number greater than this
+        #[arg(long, default_value_t = 0)]
+        since: i64,
+
+        /// Limit number of results
+        #[arg(long)]
+        limit: Option<usize>,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+
+        /// Show all scopes
+        #[arg(short, long)]
+        all: bool,
+
+        /// Output as JSON
+        #[arg(short, long)]
+        json: bool,
+    },
+
+    /// Serve the store over a local HTTP REST API (or Redis protocol with --resp)
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:7070")]
+        bind: String,
+
+        /// Require this bearer token on every request
+        #[arg(long, conflicts_with = "resp")]
+        token: Option<String>,
+
+        /// Speak the Redis RESP protocol instead of HTTP
+        #[arg(long)]
+        resp: bool,
+
+        /// Scope for RESP commands (defaults to CWD-scoped)
+        #[arg(long, requires = "resp", conflicts_with = "global")]
+        scope: Option<String>,
+
+        /// Use global scope for RESP commands
+        #[arg(short, long, requires = "resp")]
+        global: bool,
+    },
+
+    /// Run a Model Context Protocol server on stdio
+    Mcp {
+        /// Directory whose scope tool calls use (defaults to CWD)
+        #[arg(long, value_name = "DIR")]
+        root: Option<String>,
+
+        /// Default to global scope instead of a directory scope
+        #[arg(short, long, conflicts_with = "root")]
+        global: bool,
+    },
+
+    /// Run a daemon on a Unix socket that the CLI uses when available
+    #[cfg(unix)]
+    Daemon {
+        /// Socket path (defaults to kv.sock next to the database)
+        #[arg(long)]
+        socket: Option<String>,
+    },
+
+    /// Manage JSON Schemas that values must match before they are stored
+    Schema {
+        #[command(subcommand)]
+        action: SchemaAction,
+    },
+
+    /// Manage default TTL and version-limit policies for keys and prefixes
+    Policy {
+        #[command(subcommand)]
+        action: PolicyAction,
+    },
+
     /// Show storage statistics
     Stats {
         /// Output as JSON
@@ -109,7 +558,11 @@ enum Commands {
     },
 
     /// Garbage collect old/expired/deleted entries
+    #[command(args_conflicts_with_subcommands = true)]
     Gc {
+        #[command(subcommand)]
+        action: Option<GcAction>,
+
         /// Actually delete (default is dry run)
         #[arg(long)]
         run: bool,
@@ -120,7 +573,7 @@ enum Commands {
 
         /// Keep only last N versions per key
         #[arg(long, value_name = "N")]
-        keep_versions: Option<i64>,
+        keep_versions: Option<usize>,
 
         /// Only clean expired entries
         #[arg(long)]
@@ -129,6 +582,129 @@ enum Commands {
         /// Only clean soft-deleted entries
         #[arg(long)]
         deleted: bool,
+
+        /// Return freed space to the filesystem afterwards (VACUUM)
+        #[arg(long, requires = "run")]
+        vacuum: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum GcAction {
+    /// Show or set the thresholds at which writes trigger a GC of expired and deleted entries
+    Auto {
+        /// Collect once the database file is larger than this (e.g., 100MB, or 'off')
+        #[arg(long, value_name = "SIZE", value_parser = commands::gc::parse_size_threshold)]
+        max_size: Option<Threshold<u64>>,
+
+        /// Collect once more than N entry versions are stored (or 'off')
+        #[arg(long, value_name = "N", value_parser = commands::gc::parse_count_threshold)]
+        max_entries: Option<Threshold<u64>>,
+
+        /// Collect when this long has passed since the last GC (e.g., 1d, or 'off')
+        #[arg(long, value_name = "DURATION", value_parser = commands::gc::parse_interval_threshold)]
+        interval: Option<Threshold<chrono::Duration>>,
+
+        /// How long deleted keys stay restorable before automatic GC removes them (e.g., 30d)
+        #[arg(long, value_name = "DURATION", value_parser = commands::gc::parse_retention)]
+        deleted_retention: Option<chrono::Duration>,
+
+        /// Turn automatic GC on
+        #[arg(long, conflicts_with = "disable")]
+        enable: bool,
+
+        /// Turn automatic GC off
+        #[arg(long)]
+        disable: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum SchemaAction {
+    /// Attach a schema to a key, or to every key under a prefix with 'prefix/*'
+    Set {
+        /// Key or 'prefix/*' pattern
+        pattern: String,
+
+        /// The schema (file path, inline JSON, or omit for stdin)
+        schema: Option<String>,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+    },
+
+    /// Print the schema attached to a pattern
+    Get {
+        /// Key or 'prefix/*' pattern
+        pattern: String,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+    },
+
+    /// List attached schemas
+    List {
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+
+        /// Output as JSON
+        #[arg(short, long)]
+        json: bool,
+    },
+
+    /// Detach the schema from a pattern
+    Remove {
+        /// Key or 'prefix/*' pattern
+        pattern: String,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum PolicyAction {
+    /// Set a policy for a key, every key under a prefix with 'prefix/*', or the whole scope with '*'
+    Set {
+        /// Key, 'prefix/*' pattern, or '*'
+        pattern: String,
+
+        /// Default time-to-live for writes without one (e.g., 1h, 7d)
+        #[arg(long, required_unless_present = "max_versions")]
+        ttl: Option<String>,
+
+        /// Keep at most N versions of each key
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+        max_versions: Option<u64>,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+    },
+
+    /// List policies
+    List {
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
+
+        /// Output as JSON
+        #[arg(short, long)]
+        json: bool,
+    },
+
+    /// Remove the policy from a pattern
+    Remove {
+        /// Key, 'prefix/*' pattern, or '*'
+        pattern: String,
+
+        /// Use global scope instead of CWD-scoped
+        #[arg(short, long)]
+        global: bool,
     },
 }
 
@@ -143,7 +719,42 @@ fn main() -> Result<()> {
             force,
             global,
             ttl,
-        } => commands::set::execute(&key, value.as_deref(), literal, force, global, ttl.as_deref()),
+            expires_at,
+            sliding,
+            content_type,
+            from_json,
+        } => commands::set::execute(
+            &key,
+            value.as_deref(),
+            literal,
+            force,
+            global,
+            ttl.as_deref(),
+            expires_at.as_deref(),
+            sliding,
+            content_type.as_deref(),
+            from_json,
+        ),
+
+        Commands::Append {
+            key,
+            value,
+            separator,
+            literal,
+            force,
+            no_history,
+            global,
+        } => commands::append::execute(
+            &key,
+            value.as_deref(),
+            separator.as_deref(),
+            literal,
+            force,
+            no_history,
+            global,
+        ),
+
+        Commands::Patch { key, global } => commands::patch::execute(&key, global),
 
         Commands::Get {
             key,
@@ -151,23 +762,178 @@ fn main() -> Result<()> {
             verbose,
             global,
             json,
-        } => commands::get::execute(&key, version, verbose, global, json),
+            encoding,
+            path,
+        } => commands::get::execute(&key, version, verbose, global, json, encoding, path.as_deref()),
+
+        Commands::List {
+            key,
+            limit,
+            global,
+            all,
+            json,
+            encoding,
+            filter,
+        } => {
+            commands::list::execute(key.as_deref(), limit, global, all, json, encoding, filter.as_deref())
+        }
 
-        Commands::List { key, limit, global, all, json } => {
-            commands::list::execute(key.as_deref(), limit, global, all, json)
+        Commands::Expire { key, ttl, at, global } => {
+            commands::expire::execute(&key, ttl.as_deref(), at.as_deref(), global)
         }
 
+        Commands::Ttl { key, global, json } => commands::ttl::execute(&key, global, json),
+
+        Commands::Persist { key, global } => commands::persist::execute(&key, global),
+
         Commands::Delete { key, hard, global } => commands::delete::execute(&key, hard, global),
 
+        Commands::Incr { key, by, no_history, global } => {
+            commands::incr::execute(&key, by, false, no_history, global)
+        }
+
+        Commands::Decr { key, by, no_history, global } => {
+            commands::incr::execute(&key, by, true, no_history, global)
+        }
+
+        Commands::Push {
+            key,
+            value,
+            literal,
+            force,
+            global,
+            ttl,
+        } => commands::push::execute(&key, value.as_deref(), literal, force, global, ttl.as_deref()),
+
+        Commands::Pop { key, global } => commands::pop::execute(&key, false, global),
+
+        Commands::Peek { key, global } => commands::pop::execute(&key, true, global),
+
+        Commands::Len { key, global } => commands::len::execute(&key, global),
+
+        Commands::Hset {
+            key,
+            field,
+            value,
+            literal,
+            force,
+            global,
+        } => commands::hset::execute(&key, &field, value.as_deref(), literal, force, global),
+
+        Commands::Hget { key, field, global } => commands::hget::execute(&key, &field, global),
+
+        Commands::Hdel { key, field, global } => commands::hdel::execute(&key, &field, global),
+
+        Commands::Hgetall { key, global, json } => commands::hgetall::execute(&key, global, json),
+
+        Commands::Lock {
+            name,
+            ttl,
+            wait,
+            renew,
+            token,
+            global,
+        } => commands::lock::execute(&name, &ttl, wait.as_deref(), renew, token.as_deref(), global),
+
+        Commands::Unlock {
+            name,
+            token,
+            force,
+            global,
+        } => commands::unlock::execute(&name, token.as_deref(), force, global),
+
+        Commands::WithLock {
+            name,
+            ttl,
+            wait,
+            global,
+            command,
+        } => commands::with_lock::execute(&name, &ttl, wait.as_deref(), global, &command),
+
+        Commands::Watch {
+            key,
+            prefix,
+            timeout,
+            after_version,
+            follow,
+            global,
+            json,
+        } => commands::watch::execute(
+            key.as_deref(),
+            prefix.as_deref(),
+            timeout.as_deref(),
+            after_version,
+            follow,
+            global,
+            json,
+        ),
+
+        Commands::Restore { key, global } => commands::restore::execute(&key, global),
+
+        Commands::Changes {
+            since,
+            limit,
+            global,
+            all,
+            json,
+        } => commands::changes::execute(since, limit, global, all, json),
+
+        Commands::Serve {
+            bind,
+            token,
+            resp,
+            scope,
+            global,
+        } => commands::serve::execute(&bind, token.as_deref(), resp, scope.as_deref(), global),
+
+        Commands::Mcp { root, global } => commands::mcp::execute(root.as_deref(), global),
+
+        #[cfg(unix)]
+        Commands::Daemon { socket } => commands::daemon::execute(socket.as_deref()),
+
+        Commands::Schema { action } => match action {
+            SchemaAction::Set { pattern, schema, global } => {
+                commands::schema::set(&pattern, schema.as_deref(), global)
+            }
+            SchemaAction::Get { pattern, global } => commands::schema::get(&pattern, global),
+            SchemaAction::List { global, json } => commands::schema::list(global, json),
+            SchemaAction::Remove { pattern, global } => commands::schema::remove(&pattern, global),
+        },
+
+        Commands::Policy { action } => match action {
+            PolicyAction::Set {
+                pattern,
+                ttl,
+                max_versions,
+                global,
+            } => commands::policy::set(&pattern, ttl.as_deref(), max_versions.map(|n| n as usize), global),
+            PolicyAction::List { global, json } => commands::policy::list(global, json),
+            PolicyAction::Remove { pattern, global } => commands::policy::remove(&pattern, global),
+        },
+
         Commands::Stats { json } => commands::stats::execute(json),
 
         Commands::Gc {
+            action: Some(GcAction::Auto {
+                max_size,
+                max_entries,
+                interval,
+                deleted_retention,
+                enable,
+                disable,
+            }),
+            ..
+        } => commands::gc::auto(max_size, max_entries, interval, deleted_retention, enable, disable),
+
+        Commands::Gc {
+            action: None,
             run,
             older_than,
             keep_versions,
             expired,
             deleted,
-        } => commands::gc::execute(run, older_than, keep_versions, expired, deleted),
+            vacuum,
+        } => commands::gc::execute(run, older_than, keep_versions, expired, deleted, vacuum),
     };
 
     if let Err(e) = result {
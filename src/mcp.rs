@@ -0,0 +1,387 @@
+//! Model Context Protocol server over stdio.
+//!
+//! Speaks newline-delimited JSON-RPC 2.0 and exposes the store as tools.
+//! Keys are scoped to the configured working directory unless a tool call
+//! passes `"global": true`.
+
+use crate::db::{Database, Entry};
+use crate::encoding::{encode_value, ValueEncoding};
+use crate::error::KvError;
+use crate::store::{DeleteOptions, GetOptions, HistoryOptions, KvStore, ListOptions, SetOptions};
+use crate::ttl::parse_ttl;
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+pub struct McpServer {
+    db: Database,
+    scope: Option<String>,
+}
+
+impl McpServer {
+    /// `scope` is the scope used for tool calls that don't ask for global keys
+    pub fn new(db: Database, scope: Option<String>) -> Self {
+        Self { db, scope }
+    }
+
+    /// Read JSON-RPC messages from stdin until EOF, answering on stdout
+    pub fn serve_stdio(&self) -> Result<(), KvError> {
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+
+        for line in stdin.lock().lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = match serde_json::from_str::<Value>(&line) {
+                Ok(message) => self.handle_message(&message),
+                Err(e) => Some(error_response(Value::Null, -32700, &format!("parse error: {}", e))),
+            };
+
+            if let Some(response) = response {
+                let mut handle = stdout.lock();
+                writeln!(handle, "{}", response)?;
+                handle.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle one JSON-RPC message. Notifications produce no response.
+    pub fn handle_message(&self, message: &Value) -> Option<Value> {
+        let method = message.get("method").and_then(Value::as_str).unwrap_or_default();
+        let id = message.get("id").cloned()?;
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        let result = match method {
+            "initialize" => Ok(json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": "kv", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": tool_definitions() })),
+            "tools/call" => Ok(self.call_tool(&params)),
+            _ => Err((-32601, format!("method not found: {}", method))),
+        };
+
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error_response(id, code, &message),
+        })
+    }
+
+    fn call_tool(&self, params: &Value) -> Value {
+        let name = params.get("name").and_then(Value::as_str).unwrap_or_default();
+        let args = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+
+        let result = match name {
+            "kv_get" => self.tool_get(&args),
+            "kv_set" => self.tool_set(&args),
+            "kv_list" => self.tool_list(&args),
+            "kv_delete" => self.tool_delete(&args),
+            "kv_history" => self.tool_history(&args),
+            "kv_search" => self.tool_search(&args),
+            _ => Err(format!("unknown tool: {}", name)),
+        };
+
+        match result {
+            Ok(text) => json!({ "content": [{ "type": "text", "text": text }], "isError": false }),
+            Err(text) => json!({ "content": [{ "type": "text", "text": text }], "isError": true }),
+        }
+    }
+
+    fn scope_for(&self, args: &Value) -> Option<&str> {
+        if args.get("global").and_then(Value::as_bool).unwrap_or(false) {
+            None
+        } else {
+            self.scope.as_deref()
+        }
+    }
+
+    fn tool_get(&self, args: &Value) -> Result<String, String> {
+        let key = required_str(args, "key")?;
+        let opts = GetOptions {
+            scope: self.scope_for(args),
+            version: args.get("version").and_then(Value::as_i64),
+        };
+        let entry = self.db.get(key, &opts).map_err(|e| e.to_string())?;
+        // Text comes back as-is; binary values would be mangled, so they come back base64 encoded
+        match encode_value(&entry.value, ValueEncoding::Utf8) {
+            (text, ValueEncoding::Utf8) => Ok(text),
+            _ => Ok(entry_json(&entry).to_string()),
+        }
+    }
+
+    fn tool_set(&self, args: &Value) -> Result<String, String> {
+        let key = required_str(args, "key")?;
+        let value = required_str(args, "value")?;
+        let expires_at = args
+            .get("ttl")
+            .and_then(Value::as_str)
+            .map(parse_ttl)
+            .transpose()
+            .map_err(|e| e.to_string())?;
+
+        let opts = SetOptions {
+            scope: self.scope_for(args),
+            content_type: Some("text/plain"),
+            expires_at,
+            ..Default::default()
+        };
+        let result = self.db.set(key, value.as_bytes(), &opts).map_err(|e| e.to_string())?;
+
+        Ok(if result.was_saved {
+            format!("set {} (version {})", key, result.version)
+        } else {
+            format!("{} unchanged (version {})", key, result.version)
+        })
+    }
+
+    fn tool_list(&self, args: &Value) -> Result<String, String> {
+        let opts = ListOptions {
+            scope: self.scope_for(args),
+            prefix: args.get("prefix").and_then(Value::as_str),
+            limit: args.get("limit").and_then(Value::as_u64).map(|l| l as usize),
+            ..Default::default()
+        };
+        let keys = self.db.list_keys(&opts).map_err(|e| e.to_string())?;
+
+        let output: Vec<Value> = keys
+            .into_iter()
+            .map(|k| {
+                json!({
+                    "key": k.key,
+                    "versions": k.versions,
+                    "size": k.total_size,
+                    "last_updated": k.last_updated.to_rfc3339(),
+                })
+            })
+            .collect();
+        Ok(Value::Array(output).to_string())
+    }
+
+    fn tool_delete(&self, args: &Value) -> Result<String, String> {
+        let key = required_str(args, "key")?;
+        let opts = DeleteOptions {
+            scope: self.scope_for(args),
+            hard: args.get("hard").and_then(Value::as_bool).unwrap_or(false),
+        };
+        let affected = self.db.delete(key, &opts).map_err(|e| e.to_string())?;
+        Ok(format!("deleted {} entries for key '{}'", affected, key))
+    }
+
+    fn tool_history(&self, args: &Value) -> Result<String, String> {
+        let key = required_str(args, "key")?;
+        let opts = HistoryOptions {
+            scope: self.scope_for(args),
+            limit: args.get("limit").and_then(Value::as_u64).map(|l| l as usize),
+        };
+        let entries = self.db.history(key, &opts).map_err(|e| e.to_string())?;
+
+        let output: Vec<Value> = entries
+            .iter()
+            .map(|e| {
+                json!({
+                    "version": e.version,
+                    "size": e.size_bytes,
+                    "content_type": e.content_type,
+                    "created_at": e.created_at.to_rfc3339(),
+                    "deleted_at": e.deleted_at.map(|dt| dt.to_rfc3339()),
+                    "expires_at": e.expires_at.map(|dt| dt.to_rfc3339()),
+                })
+            })
+            .collect();
+        Ok(Value::Array(output).to_string())
+    }
+
+    fn tool_search(&self, args: &Value) -> Result<String, String> {
+        let query = required_str(args, "query")?;
+        let limit = args.get("limit").and_then(Value::as_u64).map(|l| l as usize);
+        let entries = self
+            .db
+            .search(query, limit, self.scope_for(args))
+            .map_err(|e| e.to_string())?;
+
+        let output: Vec<Value> = entries.iter().map(entry_json).collect();
+        Ok(Value::Array(output).to_string())
+    }
+}
+
+fn entry_json(e: &Entry) -> Value {
+    let (value, value_encoding) = encode_value(&e.value, ValueEncoding::Utf8);
+    json!({
+        "key": e.key,
+        "version": e.version,
+        "value": value,
+        "value_encoding": value_encoding,
+    })
+}
+
+fn required_str<'a>(args: &'a Value, name: &str) -> Result<&'a str, String> {
+    args.get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("missing required argument: {}", name))
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn tool_definitions() -> Value {
+    let global = json!({ "type": "boolean", "description": "Use global scope instead of the working directory scope" });
+    json!([
+        {
+            "name": "kv_get",
+            "description": "Get the value stored at a key. Binary values are returned as a JSON object with a base64 value and \"value_encoding\": \"base64\"",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "key": { "type": "string" },
+                    "version": { "type": "integer", "description": "Specific version to fetch" },
+                    "global": global,
+                },
+                "required": ["key"],
+            },
+        },
+        {
+            "name": "kv_set",
+            "description": "Set a key to a text value, creating a new version",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "key": { "type": "string" },
+                    "value": { "type": "string" },
+                    "ttl": { "type": "string", "description": "Time-to-live, e.g. 30s, 1h30m, 2w, PT1H30M" },
+                    "global": global,
+                },
+                "required": ["key", "value"],
+            },
+        },
+        {
+            "name": "kv_list",
+            "description": "List keys, optionally restricted to a prefix",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "prefix": { "type": "string" },
+                    "limit": { "type": "integer", "minimum": 1 },
+                    "global": global,
+                },
+            },
+        },
+        {
+            "name": "kv_delete",
+            "description": "Delete a key (soft delete unless hard is set)",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "key": { "type": "string" },
+                    "hard": { "type": "boolean" },
+                    "global": global,
+                },
+                "required": ["key"],
+            },
+        },
+        {
+            "name": "kv_history",
+            "description": "Show the version history of a key",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "key": { "type": "string" },
+                    "limit": { "type": "integer", "minimum": 1 },
+                    "global": global,
+                },
+                "required": ["key"],
+            },
+        },
+        {
+            "name": "kv_search",
+            "description": "Find keys whose name or text value contains a string",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string" },
+                    "limit": { "type": "integer", "minimum": 1 },
+                    "global": global,
+                },
+                "required": ["query"],
+            },
+        },
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(server: &McpServer, name: &str, arguments: Value) -> Value {
+        let message = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": { "name": name, "arguments": arguments },
+        });
+        server.handle_message(&message).unwrap()["result"].clone()
+    }
+
+    #[test]
+    fn test_initialize_and_list_tools() {
+        let server = McpServer::new(Database::open_in_memory().unwrap(), None);
+        let init = server
+            .handle_message(&json!({ "jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {} }))
+            .unwrap();
+        assert_eq!(init["result"]["protocolVersion"], PROTOCOL_VERSION);
+
+        let notification = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" });
+        assert!(server.handle_message(&notification).is_none());
+
+        let tools = server
+            .handle_message(&json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/list" }))
+            .unwrap();
+        assert_eq!(tools["result"]["tools"].as_array().unwrap().len(), 6);
+    }
+
+    #[test]
+    fn test_tool_calls_honor_scope() {
+        let server = McpServer::new(Database::open_in_memory().unwrap(), Some("proj".into()));
+        call(&server, "kv_set", json!({ "key": "k", "value": "scoped" }));
+        call(&server, "kv_set", json!({ "key": "k", "value": "global", "global": true }));
+
+        assert_eq!(call(&server, "kv_get", json!({ "key": "k" }))["content"][0]["text"], "scoped");
+        assert_eq!(
+            call(&server, "kv_get", json!({ "key": "k", "global": true }))["content"][0]["text"],
+            "global"
+        );
+
+        let missing = call(&server, "kv_get", json!({ "key": "nope" }));
+        assert_eq!(missing["isError"], true);
+
+        let hits = call(&server, "kv_search", json!({ "query": "scop" }));
+        let hits: Value = serde_json::from_str(hits["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(hits[0]["key"], "k");
+        assert_eq!(hits[0]["value_encoding"], "utf8");
+    }
+
+    #[test]
+    fn test_binary_values_are_base64_encoded() {
+        let server = McpServer::new(Database::open_in_memory().unwrap(), None);
+        let binary = [0u8, 159, 146, 150];
+        server.db.set("bin", &binary, &SetOptions::default()).unwrap();
+
+        let got = call(&server, "kv_get", json!({ "key": "bin" }));
+        let got: Value = serde_json::from_str(got["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(got["value_encoding"], "base64");
+        let decoded = crate::encoding::decode_value("bin", got["value"].as_str().unwrap(), ValueEncoding::Base64);
+        assert_eq!(decoded.unwrap(), binary);
+
+        let hits = call(&server, "kv_search", json!({ "query": "bin" }));
+        let hits: Value = serde_json::from_str(hits["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!((&hits[0]["value"], &hits[0]["value_encoding"]), (&got["value"], &got["value_encoding"]));
+    }
+}
@@ -0,0 +1,361 @@
+//! In-memory `KvStore` backend.
+//!
+//! Nothing touches the filesystem, so it suits tests and short-lived agents.
+//! Versioning, scoping, TTL, soft-delete and gc behave exactly like the SQLite
+//! `Database`; both backends run the same conformance suite.
+
+use crate::error::KvError;
+use crate::store::{
+    DeleteOptions, Entry, GcOptions, GcResult, GetOptions, HistoryOptions, IncrOptions, KeySummary, KvStore,
+    ListOptions, ScopeStats, SetOptions, SetResult, Stats,
+};
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Mutex, MutexGuard};
+
+/// (scope, key)
+type Slot = (Option<String>, String);
+
+#[derive(Default)]
+pub struct MemoryStore {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    last_id: i64,
+    /// Every version of each key, oldest first
+    slots: BTreeMap<Slot, Vec<Entry>>,
+}
+
+fn slot(key: &str, scope: Option<&str>) -> Slot {
+    (scope.map(str::to_string), key.to_string())
+}
+
+fn is_live(entry: &Entry, now: DateTime<Utc>) -> bool {
+    entry.deleted_at.is_none() && entry.expires_at.map(|e| e > now).unwrap_or(true)
+}
+
+impl Inner {
+    fn versions(&self, key: &str, scope: Option<&str>) -> &[Entry] {
+        self.slots.get(&slot(key, scope)).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Newest version that is not soft-deleted (it may still be expired)
+    fn latest(&self, key: &str, scope: Option<&str>) -> Option<&Entry> {
+        self.versions(key, scope).iter().rev().find(|e| e.deleted_at.is_none())
+    }
+
+    fn insert(&mut self, key: &str, value: &[u8], opts: &SetOptions) -> i64 {
+        self.last_id += 1;
+        let versions = self.slots.entry(slot(key, opts.scope)).or_default();
+        let version = versions.last().map(|e| e.version).unwrap_or(0) + 1;
+        versions.push(Entry {
+            id: self.last_id,
+            key: key.to_string(),
+            value: value.to_vec(),
+            version,
+            content_type: opts.content_type.map(str::to_string),
+            original_filename: opts.original_filename.map(str::to_string),
+            size_bytes: value.len() as i64,
+            created_at: Utc::now(),
+            deleted_at: None,
+            scope: opts.scope.map(str::to_string),
+            expires_at: opts.expires_at,
+            sliding_ttl: opts.sliding_ttl,
+        });
+        version
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl KvStore for MemoryStore {
+    fn set(&self, key: &str, value: &[u8], opts: &SetOptions) -> Result<SetResult, KvError> {
+        let mut inner = self.lock();
+
+        if let Some(existing) = inner.latest(key, opts.scope).filter(|e| !e.is_expired()) {
+            if existing.value == value
+                && existing.expires_at == opts.expires_at
+                && existing.sliding_ttl == opts.sliding_ttl
+                && existing.content_type.as_deref() == opts.content_type
+            {
+                return Ok(SetResult { version: existing.version, was_saved: false });
+            }
+        }
+
+        let version = inner.insert(key, value, opts);
+        Ok(SetResult { version, was_saved: true })
+    }
+
+    fn get(&self, key: &str, opts: &GetOptions) -> Result<Entry, KvError> {
+        let mut inner = self.lock();
+
+        if opts.version.is_none() {
+            let versions = inner.slots.get_mut(&slot(key, opts.scope));
+            let latest = versions.and_then(|v| v.iter_mut().rev().find(|e| e.deleted_at.is_none()));
+            if let Some(entry) = latest.filter(|e| !e.is_expired()) {
+                if let Some(window) = entry.sliding_ttl {
+                    entry.expires_at = Some(Utc::now() + window);
+                }
+            }
+        }
+
+        let entry = match opts.version {
+            Some(v) => inner.versions(key, opts.scope).iter().find(|e| e.version == v),
+            None => inner.latest(key, opts.scope),
+        };
+
+        match entry {
+            Some(e) if e.is_expired() => Err(KvError::KeyNotFound(key.to_string())),
+            Some(e) => Ok(e.clone()),
+            None => Err(match opts.version {
+                Some(v) => KvError::VersionNotFound { key: key.to_string(), version: v },
+                None => KvError::KeyNotFound(key.to_string()),
+            }),
+        }
+    }
+
+    fn delete(&self, key: &str, opts: &DeleteOptions) -> Result<u64, KvError> {
+        let mut inner = self.lock();
+
+        if inner.latest(key, opts.scope).is_none() {
+            return Err(KvError::KeyNotFound(key.to_string()));
+        }
+
+        let slot = slot(key, opts.scope);
+        if opts.hard {
+            let removed = inner.slots.remove(&slot).unwrap_or_default();
+            return Ok(removed.len() as u64);
+        }
+
+        let now = Utc::now();
+        let mut affected = 0;
+        for entry in inner.slots.get_mut(&slot).into_iter().flatten() {
+            if entry.deleted_at.is_none() {
+                entry.deleted_at = Some(now);
+                affected += 1;
+            }
+        }
+        Ok(affected)
+    }
+
+    fn restore(&self, key: &str, scope: Option<&str>) -> Result<u64, KvError> {
+        let mut inner = self.lock();
+
+        let mut affected = 0;
+        for entry in inner.slots.get_mut(&slot(key, scope)).into_iter().flatten() {
+            if entry.deleted_at.take().is_some() {
+                affected += 1;
+            }
+        }
+
+        if affected == 0 {
+            return Err(KvError::KeyNotFound(key.to_string()));
+        }
+        Ok(affected)
+    }
+
+    fn incr(&self, key: &str, delta: i64, opts: &IncrOptions) -> Result<i64, KvError> {
+        let mut inner = self.lock();
+
+        let latest = inner.latest(key, opts.scope).filter(|e| !e.is_expired());
+        let current = match latest {
+            Some(entry) => std::str::from_utf8(&entry.value)
+                .ok()
+                .and_then(|s| s.trim().parse::<i64>().ok())
+                .ok_or_else(|| KvError::NotAnInteger(key.to_string()))?,
+            None => 0,
+        };
+        let result = current
+            .checked_add(delta)
+            .ok_or_else(|| KvError::IntegerOverflow(key.to_string()))?;
+        let value = result.to_string();
+
+        match latest.map(|e| (e.version, e.expires_at, e.sliding_ttl)) {
+            Some((version, _, _)) if opts.in_place => {
+                let versions = inner.slots.get_mut(&slot(key, opts.scope));
+                if let Some(entry) = versions.and_then(|v| v.iter_mut().find(|e| e.version == version)) {
+                    entry.value = value.into_bytes();
+                    entry.size_bytes = entry.value.len() as i64;
+                    entry.content_type = Some("text/plain".into());
+                    entry.created_at = Utc::now();
+                }
+            }
+            Some((_, expires_at, sliding_ttl)) => {
+                let set = SetOptions {
+                    scope: opts.scope,
+                    content_type: Some("text/plain"),
+                    expires_at,
+                    sliding_ttl,
+                    ..Default::default()
+                };
+                inner.insert(key, value.as_bytes(), &set);
+            }
+            None => {
+                let set = SetOptions {
+                    scope: opts.scope,
+                    content_type: Some("text/plain"),
+                    ..Default::default()
+                };
+                inner.insert(key, value.as_bytes(), &set);
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn list_keys(&self, opts: &ListOptions) -> Result<Vec<KeySummary>, KvError> {
+        let inner = self.lock();
+        let now = Utc::now();
+        let prefix = opts.prefix.unwrap_or("");
+
+        let mut keys: Vec<KeySummary> = inner
+            .slots
+            .iter()
+            .filter(|((scope, key), _)| {
+                (opts.all_scopes || scope.as_deref() == opts.scope) && key.starts_with(prefix)
+            })
+            .filter_map(|((scope, key), versions)| {
+                let live: Vec<&Entry> = versions.iter().filter(|e| is_live(e, now)).collect();
+                Some(KeySummary {
+                    key: key.clone(),
+                    versions: live.len() as i64,
+                    total_size: live.iter().map(|e| e.size_bytes).sum(),
+                    last_updated: live.iter().map(|e| e.created_at).max()?,
+                    scope: scope.clone(),
+                })
+            })
+            .collect();
+
+        keys.sort_by_key(|k| std::cmp::Reverse(k.last_updated));
+        if let Some(limit) = opts.limit {
+            keys.truncate(limit);
+        }
+        Ok(keys)
+    }
+
+    fn history(&self, key: &str, opts: &HistoryOptions) -> Result<Vec<Entry>, KvError> {
+        let inner = self.lock();
+
+        let entries: Vec<Entry> = inner
+            .versions(key, opts.scope)
+            .iter()
+            .rev()
+            .take(opts.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect();
+
+        if entries.is_empty() {
+            return Err(KvError::KeyNotFound(key.to_string()));
+        }
+        Ok(entries)
+    }
+
+    fn stats(&self) -> Result<Stats, KvError> {
+        let inner = self.lock();
+        let now = Utc::now();
+        let all = || inner.slots.values().flatten();
+
+        let count_slots = |pred: &dyn Fn(&Entry) -> bool| {
+            inner.slots.values().filter(|versions| versions.iter().any(pred)).count() as i64
+        };
+
+        let oldest = all()
+            .filter(|e| e.deleted_at.is_none())
+            .min_by_key(|e| e.created_at);
+
+        // Largest key by total size across versions and scopes, ties going to the first key
+        let mut by_key: BTreeMap<&str, i64> = BTreeMap::new();
+        for e in all() {
+            *by_key.entry(&e.key).or_default() += e.size_bytes;
+        }
+        let largest = by_key.into_iter().max_by(|(a, a_size), (b, b_size)| a_size.cmp(b_size).then(b.cmp(a)));
+
+        let mut by_scope: BTreeMap<Option<&str>, (i64, HashSet<&str>)> = BTreeMap::new();
+        for e in all().filter(|e| e.deleted_at.is_none()) {
+            let (size, keys) = by_scope.entry(e.scope.as_deref()).or_default();
+            *size += e.size_bytes;
+            keys.insert(&e.key);
+        }
+        let mut scopes: Vec<ScopeStats> = by_scope
+            .into_iter()
+            .map(|(scope, (size, keys))| ScopeStats {
+                scope: scope.map(str::to_string),
+                size,
+                keys: keys.len() as i64,
+            })
+            .collect();
+        scopes.sort_by_key(|s| std::cmp::Reverse(s.size));
+
+        Ok(Stats {
+            total_size: all().map(|e| e.size_bytes).sum(),
+            total_entries: all().count() as i64,
+            active_keys: count_slots(&|e| is_live(e, now)),
+            deleted_keys: count_slots(&|e| e.deleted_at.is_some()),
+            expired_keys: count_slots(&|e| e.deleted_at.is_none() && e.expires_at.map(|x| x <= now).unwrap_or(false)),
+            oldest_key: oldest.map(|e| e.key.clone()),
+            oldest_date: oldest.map(|e| e.created_at),
+            largest_key: largest.map(|(k, _)| k.to_string()),
+            largest_size: largest.map(|(_, s)| s).unwrap_or(0),
+            scopes,
+        })
+    }
+
+    fn gc(&self, opts: &GcOptions) -> Result<GcResult, KvError> {
+        let mut inner = self.lock();
+        let now = Utc::now();
+        let cutoff = opts.older_than.map(|age| now - age);
+        let deleted_cutoff = opts.deleted_older_than.map(|age| now - age);
+
+        let collect = |e: &Entry, newer: usize| {
+            (opts.expired && e.expires_at.map(|x| x <= now).unwrap_or(false))
+                || (opts.deleted && e.deleted_at.is_some_and(|d| deleted_cutoff.is_none_or(|c| d < c)))
+                || cutoff.map(|c| e.created_at < c).unwrap_or(false)
+                || opts.keep_versions.map(|keep| newer >= keep).unwrap_or(false)
+        };
+
+        let mut entries_count = 0;
+        let mut bytes_freed = 0;
+        for versions in inner.slots.values_mut() {
+            let len = versions.len();
+            let mut index = 0;
+            versions.retain(|e| {
+                // Number of versions newer than this one
+                let newer = len - 1 - index;
+                index += 1;
+                if !collect(e, newer) {
+                    return true;
+                }
+                entries_count += 1;
+                bytes_freed += e.size_bytes;
+                opts.dry_run
+            });
+        }
+        inner.slots.retain(|_, versions| !versions.is_empty());
+
+        Ok(GcResult {
+            entries_count,
+            bytes_freed,
+            was_run: !opts.dry_run,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conformance() {
+        crate::store::conformance::run(|| Box::new(MemoryStore::new()));
+    }
+}
@@ -0,0 +1,230 @@
+//! JSON Patch (RFC 6902) and JSON Merge Patch (RFC 7386).
+//!
+//! A patch document that is a JSON array is read as a list of RFC 6902
+//! operations; anything else is a merge patch. Both are applied to a parsed
+//! value in memory, so a failing operation leaves the original untouched.
+
+use crate::error::KvError;
+use serde::Deserialize;
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Operation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Patch {
+    Json(Vec<Operation>),
+    Merge(Value),
+}
+
+impl Patch {
+    pub fn parse(input: &[u8]) -> Result<Self, KvError> {
+        let value: Value = serde_json::from_slice(input)
+            .map_err(|e| KvError::PatchFailed(format!("patch is not valid JSON: {}", e)))?;
+        match value {
+            Value::Array(_) => serde_json::from_value(value)
+                .map(Patch::Json)
+                .map_err(|e| KvError::PatchFailed(format!("invalid JSON Patch operation: {}", e))),
+            other => Ok(Patch::Merge(other)),
+        }
+    }
+
+    /// Apply the patch, returning the new document
+    pub fn apply(&self, doc: &Value) -> Result<Value, KvError> {
+        match self {
+            Patch::Json(ops) => {
+                let mut doc = doc.clone();
+                for (i, op) in ops.iter().enumerate() {
+                    apply_operation(&mut doc, op).map_err(|e| KvError::PatchFailed(format!("operation {}: {}", i, e)))?;
+                }
+                Ok(doc)
+            }
+            Patch::Merge(patch) => {
+                let mut doc = doc.clone();
+                merge(&mut doc, patch);
+                Ok(doc)
+            }
+        }
+    }
+}
+
+fn apply_operation(doc: &mut Value, op: &Operation) -> Result<(), String> {
+    match op {
+        Operation::Add { path, value } => add(doc, path, value.clone()),
+        Operation::Remove { path } => remove(doc, path).map(drop),
+        Operation::Replace { path, value } => {
+            let target = doc.pointer_mut(path).ok_or_else(|| missing(path))?;
+            *target = value.clone();
+            Ok(())
+        }
+        Operation::Move { from, path } => {
+            if path.starts_with(from.as_str()) && path[from.len()..].starts_with('/') {
+                return Err(format!("cannot move {} into its own child {}", from, path));
+            }
+            let value = remove(doc, from)?;
+            add(doc, path, value)
+        }
+        Operation::Copy { from, path } => {
+            let value = doc.pointer(from).cloned().ok_or_else(|| missing(from))?;
+            add(doc, path, value)
+        }
+        Operation::Test { path, value } => match doc.pointer(path) {
+            Some(actual) if actual == value => Ok(()),
+            Some(actual) => Err(format!("test failed at {}: expected {}, found {}", path, value, actual)),
+            None => Err(missing(path)),
+        },
+    }
+}
+
+fn missing(path: &str) -> String {
+    format!("path not found: {}", path)
+}
+
+/// Split a JSON Pointer into its parent pointer and unescaped last token
+fn split_pointer(path: &str) -> Result<(&str, String), String> {
+    if !path.starts_with('/') {
+        return Err(format!("invalid JSON Pointer: {:?}", path));
+    }
+    let at = path.rfind('/').unwrap_or(0);
+    let token = path[at + 1..].replace("~1", "/").replace("~0", "~");
+    Ok((&path[..at], token))
+}
+
+fn array_index(token: &str, len: usize, allow_end: bool) -> Result<usize, String> {
+    let index: usize = token
+        .parse()
+        .ok()
+        .filter(|_| token == "0" || !token.starts_with('0'))
+        .ok_or_else(|| format!("invalid array index: {}", token))?;
+    if index > len || (index == len && !allow_end) {
+        return Err(format!("array index {} out of bounds", index));
+    }
+    Ok(index)
+}
+
+fn add(doc: &mut Value, path: &str, value: Value) -> Result<(), String> {
+    if path.is_empty() {
+        *doc = value;
+        return Ok(());
+    }
+
+    let (parent, token) = split_pointer(path)?;
+    match doc.pointer_mut(parent).ok_or_else(|| missing(parent))? {
+        Value::Object(map) => {
+            map.insert(token, value);
+        }
+        Value::Array(items) => {
+            let index = if token == "-" {
+                items.len()
+            } else {
+                array_index(&token, items.len(), true)?
+            };
+            items.insert(index, value);
+        }
+        _ => return Err(format!("cannot add to {}: parent is not an object or array", path)),
+    }
+    Ok(())
+}
+
+fn remove(doc: &mut Value, path: &str) -> Result<Value, String> {
+    if path.is_empty() {
+        return Err("cannot remove the whole document".into());
+    }
+
+    let (parent, token) = split_pointer(path)?;
+    match doc.pointer_mut(parent).ok_or_else(|| missing(parent))? {
+        Value::Object(map) => map.shift_remove(&token).ok_or_else(|| missing(path)),
+        Value::Array(items) => {
+            let index = array_index(&token, items.len(), false)?;
+            Ok(items.remove(index))
+        }
+        _ => Err(missing(path)),
+    }
+}
+
+/// RFC 7386: objects merge recursively, null removes a member, anything else replaces
+fn merge(doc: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *doc = patch.clone();
+        return;
+    };
+    if !doc.is_object() {
+        *doc = Value::Object(Default::default());
+    }
+    let Value::Object(map) = doc else { unreachable!() };
+
+    for (name, value) in patch {
+        if value.is_null() {
+            map.shift_remove(name);
+        } else {
+            merge(map.entry(name.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn patch(doc: Value, patch: Value) -> Result<Value, KvError> {
+        Patch::parse(patch.to_string().as_bytes())?.apply(&doc)
+    }
+
+    #[test]
+    fn test_json_patch() {
+        let doc = json!({ "model": "opus", "tags": ["a", "b"], "a/b": { "~x": 1 } });
+
+        let patched = patch(
+            doc.clone(),
+            json!([
+                { "op": "test", "path": "/model", "value": "opus" },
+                { "op": "replace", "path": "/model", "value": "sonnet" },
+                { "op": "add", "path": "/tags/-", "value": "c" },
+                { "op": "add", "path": "/tags/0", "value": "z" },
+                { "op": "remove", "path": "/tags/1" },
+                { "op": "copy", "from": "/a~1b/~0x", "path": "/count" },
+                { "op": "move", "from": "/a~1b", "path": "/nested" },
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            patched,
+            json!({ "model": "sonnet", "tags": ["z", "b", "c"], "count": 1, "nested": { "~x": 1 } })
+        );
+        // Key order is kept
+        let keys: Vec<&String> = patched.as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["model", "tags", "count", "nested"]);
+
+        assert!(patch(doc.clone(), json!([{ "op": "test", "path": "/model", "value": "x" }])).is_err());
+        assert!(patch(doc.clone(), json!([{ "op": "remove", "path": "/nope" }])).is_err());
+        assert!(patch(doc.clone(), json!([{ "op": "add", "path": "/tags/5", "value": 1 }])).is_err());
+        assert!(patch(doc.clone(), json!([{ "op": "move", "from": "/a~1b", "path": "/a~1b/c" }])).is_err());
+        assert!(patch(doc, json!([{ "op": "frobnicate", "path": "/model" }])).is_err());
+    }
+
+    #[test]
+    fn test_merge_patch() {
+        let doc = json!({ "title": "Goodbye!", "author": { "givenName": "John", "familyName": "Doe" }, "tags": ["x"] });
+        let patched = patch(
+            doc,
+            json!({ "title": "Hello!", "author": { "familyName": null }, "tags": ["y"], "phone": "555" }),
+        )
+        .unwrap();
+        assert_eq!(
+            patched,
+            json!({ "title": "Hello!", "author": { "givenName": "John" }, "tags": ["y"], "phone": "555" })
+        );
+
+        assert_eq!(patch(json!({ "a": 1 }), json!("text")).unwrap(), json!("text"));
+        assert_eq!(patch(json!([1]), json!({ "a": { "b": 1 } })).unwrap(), json!({ "a": { "b": 1 } }));
+    }
+}
@@ -0,0 +1,211 @@
+//! jq-style paths and filters over JSON values.
+//!
+//! Paths look like `.config.model`, `.items[0].name` or `."odd key"`. A filter
+//! is a path, optionally compared to a JSON literal: `.status == "done"`,
+//! `.retries >= 3`. A bare path matches when the field exists.
+//!
+//! Paths are evaluated in Rust for single values and translated to SQLite
+//! `json_extract` paths so filters run inside the database.
+
+use crate::error::KvError;
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Field(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    pub segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    pub path: JsonPath,
+    /// None for a bare path, which only tests that the field exists
+    pub comparison: Option<(Op, Value)>,
+}
+
+fn invalid(msg: impl Into<String>) -> KvError {
+    KvError::InvalidQuery(msg.into())
+}
+
+impl JsonPath {
+    pub fn parse(input: &str) -> Result<Self, KvError> {
+        let (path, rest) = parse_path(input.trim())?;
+        if !rest.trim().is_empty() {
+            return Err(invalid(format!("unexpected '{}' after path", rest.trim())));
+        }
+        Ok(path)
+    }
+
+    /// Follow the path into `value`, or None if some step is missing
+    pub fn extract<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        self.segments.iter().try_fold(value, |v, segment| match segment {
+            Segment::Field(name) => v.get(name),
+            Segment::Index(i) => v.get(i),
+        })
+    }
+
+    /// The equivalent SQLite JSON path, e.g. `$."config"."model"`
+    pub fn to_sqlite(&self) -> String {
+        let mut out = String::from("$");
+        for segment in &self.segments {
+            match segment {
+                Segment::Field(name) => {
+                    out.push_str(".\"");
+                    out.push_str(&name.replace('"', "\\\""));
+                    out.push('"');
+                }
+                Segment::Index(i) => out.push_str(&format!("[{}]", i)),
+            }
+        }
+        out
+    }
+}
+
+impl std::fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.segments.is_empty() {
+            return write!(f, ".");
+        }
+        for segment in &self.segments {
+            match segment {
+                Segment::Field(name) if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') => {
+                    write!(f, ".{}", name)?
+                }
+                Segment::Field(name) => write!(f, ".\"{}\"", name)?,
+                Segment::Index(i) => write!(f, "[{}]", i)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Filter {
+    pub fn parse(input: &str) -> Result<Self, KvError> {
+        let (path, rest) = parse_path(input.trim())?;
+        let rest = rest.trim();
+        if rest.is_empty() {
+            return Ok(Self { path, comparison: None });
+        }
+
+        let ops = [("==", Op::Eq), ("!=", Op::Ne), ("<=", Op::Le), (">=", Op::Ge), ("<", Op::Lt), (">", Op::Gt)];
+        let (op, literal) = ops
+            .iter()
+            .find_map(|(token, op)| rest.strip_prefix(token).map(|lit| (*op, lit.trim())))
+            .ok_or_else(|| invalid(format!("expected a comparison operator, found '{}'", rest)))?;
+
+        let literal: Value = serde_json::from_str(literal)
+            .map_err(|_| invalid(format!("'{}' is not a JSON literal", literal)))?;
+
+        let filter = Self {
+            path,
+            comparison: Some((op, literal)),
+        };
+        filter.validate()?;
+        Ok(filter)
+    }
+
+    /// Check that the comparison is one that can be evaluated. Arrays and
+    /// objects are rejected because SQLite only compares them as JSON text,
+    /// so `[1.0]` would not match `[1]`.
+    pub fn validate(&self) -> Result<(), KvError> {
+        let Some((op, literal)) = &self.comparison else {
+            return Ok(());
+        };
+        if matches!(literal, Value::Array(_) | Value::Object(_)) {
+            return Err(invalid("can only compare against strings, numbers, booleans and null"));
+        }
+        if !matches!(op, Op::Eq | Op::Ne) && !matches!(literal, Value::String(_) | Value::Number(_)) {
+            return Err(invalid("ordering comparisons need a string or number"));
+        }
+        Ok(())
+    }
+}
+
+/// Parse a leading path, returning it and the unparsed remainder
+fn parse_path(input: &str) -> Result<(JsonPath, &str), KvError> {
+    if !input.starts_with('.') {
+        return Err(invalid(format!("path must start with '.': {}", input)));
+    }
+
+    let mut segments = Vec::new();
+    let mut rest = input;
+    loop {
+        if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(|| invalid("unclosed '['"))?;
+            let index = after[..end]
+                .trim()
+                .parse()
+                .map_err(|_| invalid(format!("bad array index: {}", &after[..end])))?;
+            segments.push(Segment::Index(index));
+            rest = &after[end + 1..];
+        } else if let Some(after) = rest.strip_prefix(".\"") {
+            let end = after.find('"').ok_or_else(|| invalid("unclosed quoted field"))?;
+            segments.push(Segment::Field(after[..end].to_string()));
+            rest = &after[end + 1..];
+        } else if let Some(after) = rest.strip_prefix('.') {
+            let end = after
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+                .unwrap_or(after.len());
+            if end > 0 {
+                segments.push(Segment::Field(after[..end].to_string()));
+            } else if !segments.is_empty() || after.starts_with('.') {
+                return Err(invalid(format!("empty field name in path: {}", input)));
+            }
+            rest = &after[end..];
+        } else {
+            return Ok((JsonPath { segments }, rest));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_and_extract_path() {
+        let path = JsonPath::parse(".config.models[1].\"display name\"").unwrap();
+        assert_eq!(path.to_sqlite(), "$.\"config\".\"models\"[1].\"display name\"");
+
+        let doc = json!({ "config": { "models": [{}, { "display name": "Opus" }] } });
+        assert_eq!(path.extract(&doc), Some(&json!("Opus")));
+        assert_eq!(path.to_string(), ".config.models[1].\"display name\"");
+        assert_eq!(JsonPath::parse(".").unwrap().extract(&doc), Some(&doc));
+        assert_eq!(JsonPath::parse(".config.nope").unwrap().extract(&doc), None);
+
+        assert!(JsonPath::parse("config").is_err());
+        assert!(JsonPath::parse(".a..b").is_err());
+        assert!(JsonPath::parse(".a[x]").is_err());
+        assert!(JsonPath::parse(".a b").is_err());
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        let filter = Filter::parse(".status == \"done\"").unwrap();
+        assert_eq!(filter.comparison, Some((Op::Eq, json!("done"))));
+        assert_eq!(Filter::parse(".retries>=3").unwrap().comparison, Some((Op::Ge, json!(3))));
+        assert_eq!(Filter::parse(".owner").unwrap().comparison, None);
+
+        assert!(Filter::parse(".a == done").is_err());
+        assert!(Filter::parse(".a < true").is_err());
+        assert!(Filter::parse(".a ~ 1").is_err());
+        assert!(Filter::parse(".a == [1]").is_err());
+        assert!(Filter::parse(r#".a != {"b": 1}"#).is_err());
+    }
+}
@@ -0,0 +1,617 @@
+//! Redis-compatible (RESP2) front end for a subset of commands:
+//! PING, ECHO, GET, SET [EX s | PX ms] [NX | XX], DEL, EXISTS, KEYS, SCAN,
+//! TTL, PTTL, EXPIRE, PEXPIRE, PERSIST, INCR, DECR, INCRBY, DECRBY.
+//!
+//! Every command operates in a single configured scope.
+
+use crate::db::Database;
+use crate::error::KvError;
+use crate::store::{DeleteOptions, GetOptions, IncrOptions, KvStore, ListOptions, SetOptions};
+use chrono::{Duration, Utc};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Most arguments one command may have
+const MAX_ARGS: usize = 1024 * 1024;
+
+/// Largest bulk string a client may send, like Redis' proto-max-bulk-len
+const MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+
+#[derive(Debug, PartialEq)]
+pub enum Reply {
+    Simple(String),
+    Error(String),
+    Integer(i64),
+    Bulk(Option<Vec<u8>>),
+    Array(Vec<Reply>),
+}
+
+impl Reply {
+    fn ok() -> Self {
+        Reply::Simple("OK".into())
+    }
+
+    fn bulk(bytes: impl Into<Vec<u8>>) -> Self {
+        Reply::Bulk(Some(bytes.into()))
+    }
+
+    fn nil() -> Self {
+        Reply::Bulk(None)
+    }
+
+    fn syntax_error() -> Self {
+        Reply::Error("ERR syntax error".into())
+    }
+
+    fn wrong_args(cmd: &str) -> Self {
+        Reply::Error(format!("ERR wrong number of arguments for '{}' command", cmd.to_lowercase()))
+    }
+
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Reply::Simple(s) => out.extend_from_slice(format!("+{}\r\n", s).as_bytes()),
+            Reply::Error(s) => out.extend_from_slice(format!("-{}\r\n", s).as_bytes()),
+            Reply::Integer(n) => out.extend_from_slice(format!(":{}\r\n", n).as_bytes()),
+            Reply::Bulk(None) => out.extend_from_slice(b"$-1\r\n"),
+            Reply::Bulk(Some(b)) => {
+                out.extend_from_slice(format!("${}\r\n", b.len()).as_bytes());
+                out.extend_from_slice(b);
+                out.extend_from_slice(b"\r\n");
+            }
+            Reply::Array(items) => {
+                out.extend_from_slice(format!("*{}\r\n", items.len()).as_bytes());
+                for item in items {
+                    item.encode(out);
+                }
+            }
+        }
+    }
+}
+
+/// Serve RESP clients until the process is killed. All connections share one
+/// `Database` and operate in `scope` (None for global).
+pub fn serve(db: Database, bind: &str, scope: Option<String>) -> Result<(), KvError> {
+    let listener = TcpListener::bind(bind)?;
+    eprintln!("listening on redis://{}", listener.local_addr()?);
+
+    let db = Arc::new(Mutex::new(db));
+    let scope = Arc::new(scope);
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("warning: accept failed: {}", e);
+                continue;
+            }
+        };
+        let db = Arc::clone(&db);
+        let scope = Arc::clone(&scope);
+        thread::spawn(move || {
+            if let Err(e) = handle_connection(stream, &db, scope.as_deref()) {
+                eprintln!("warning: connection error: {}", e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+fn handle_connection(stream: TcpStream, db: &Mutex<Database>, scope: Option<&str>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    loop {
+        let args = match read_command(&mut reader) {
+            Ok(Some(args)) => args,
+            Ok(None) => break,
+            // Like Redis, report the protocol error and hang up: the stream can't be resynced
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                writer.write_all(format!("-ERR Protocol error: {}\r\n", e).as_bytes())?;
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        if args.is_empty() {
+            continue;
+        }
+        if args[0].eq_ignore_ascii_case(b"QUIT") {
+            writer.write_all(b"+OK\r\n")?;
+            break;
+        }
+
+        let reply = {
+            let db = db.lock().unwrap_or_else(|e| e.into_inner());
+            execute(&db, scope, &args)
+        };
+        let mut out = Vec::new();
+        reply.encode(&mut out);
+        writer.write_all(&out)?;
+    }
+
+    Ok(())
+}
+
+/// Read one command, either a RESP array of bulk strings or an inline command.
+/// Returns None on EOF.
+pub fn read_command<R: BufRead>(reader: &mut R) -> io::Result<Option<Vec<Vec<u8>>>> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    let line = line.trim_end_matches(['\r', '\n']);
+
+    let Some(count) = line.strip_prefix('*') else {
+        // Inline command, as typed into telnet
+        return Ok(Some(line.split_whitespace().map(|s| s.as_bytes().to_vec()).collect()));
+    };
+
+    let count: usize = count
+        .parse()
+        .ok()
+        .filter(|n| *n <= MAX_ARGS)
+        .ok_or_else(|| invalid("invalid multibulk length"))?;
+    // Grow as arguments arrive rather than trusting the announced count
+    let mut args = Vec::with_capacity(count.min(16));
+    for _ in 0..count {
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let len: usize = header
+            .trim_end()
+            .strip_prefix('$')
+            .and_then(|l| l.parse().ok())
+            .ok_or_else(|| invalid("expected bulk string"))?;
+        if len > MAX_BULK_LEN {
+            return Err(invalid("invalid bulk length"));
+        }
+
+        let mut arg = Vec::new();
+        reader.by_ref().take(len as u64 + 2).read_to_end(&mut arg)?;
+        if arg.len() < len + 2 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        arg.truncate(len);
+        args.push(arg);
+    }
+
+    Ok(Some(args))
+}
+
+/// Run one command against the store
+pub fn execute(db: &Database, scope: Option<&str>, args: &[Vec<u8>]) -> Reply {
+    let name = String::from_utf8_lossy(&args[0]).to_uppercase();
+    let args: Vec<&[u8]> = args[1..].iter().map(Vec::as_slice).collect();
+    let key = |i: usize| String::from_utf8_lossy(args[i]).to_string();
+
+    let result = match (name.as_str(), args.len()) {
+        ("PING", 0) => Ok(Reply::Simple("PONG".into())),
+        ("PING", 1) | ("ECHO", 1) => Ok(Reply::bulk(args[0])),
+        // redis-cli asks for command docs on connect; an empty answer is fine
+        ("COMMAND", _) => Ok(Reply::Array(Vec::new())),
+        ("GET", 1) => get(db, scope, &key(0)),
+        ("SET", n) if n >= 2 => set(db, scope, &key(0), args[1], &args[2..]),
+        ("DEL", n) if n >= 1 => del(db, scope, &args),
+        ("EXISTS", n) if n >= 1 => exists(db, scope, &args),
+        ("KEYS", 1) => keys(db, scope, &key(0)),
+        ("SCAN", n) if n >= 1 => scan(db, scope, &args),
+        ("TTL", 1) => ttl(db, scope, &key(0), 1000),
+        ("PTTL", 1) => ttl(db, scope, &key(0), 1),
+        ("EXPIRE", 2) => expire(db, scope, &key(0), args[1], 1000),
+        ("PEXPIRE", 2) => expire(db, scope, &key(0), args[1], 1),
+        ("PERSIST", 1) => persist(db, scope, &key(0)),
+        ("INCR", 1) => incr(db, scope, &key(0), 1),
+        ("DECR", 1) => incr(db, scope, &key(0), -1),
+        ("INCRBY", 2) | ("DECRBY", 2) => match parse_int(args[1]) {
+            Some(by) if name == "INCRBY" => incr(db, scope, &key(0), by),
+            Some(by) => match by.checked_neg() {
+                Some(by) => incr(db, scope, &key(0), by),
+                None => Ok(not_an_integer()),
+            },
+            None => Ok(not_an_integer()),
+        },
+        (
+            "GET" | "SET" | "DEL" | "EXISTS" | "KEYS" | "SCAN" | "TTL" | "PTTL" | "EXPIRE" | "PEXPIRE" | "PERSIST"
+            | "INCR" | "DECR" | "INCRBY" | "DECRBY" | "PING" | "ECHO",
+            _,
+        ) => Ok(Reply::wrong_args(&name)),
+        _ => Ok(Reply::Error(format!("ERR unknown command '{}'", name.to_lowercase()))),
+    };
+
+    result.unwrap_or_else(|e| Reply::Error(format!("ERR {}", e)))
+}
+
+fn not_an_integer() -> Reply {
+    Reply::Error("ERR value is not an integer or out of range".into())
+}
+
+fn parse_int(arg: &[u8]) -> Option<i64> {
+    std::str::from_utf8(arg).ok()?.parse().ok()
+}
+
+/// The latest live version of a key, or None if it is missing, deleted or expired
+fn live_version(db: &Database, scope: Option<&str>, key: &str) -> Result<Option<i64>, KvError> {
+    match db.get(key, &GetOptions { scope, version: None }) {
+        Ok(entry) => Ok(Some(entry.version)),
+        Err(KvError::KeyNotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn get(db: &Database, scope: Option<&str>, key: &str) -> Result<Reply, KvError> {
+    match db.get(key, &GetOptions { scope, version: None }) {
+        Ok(entry) => Ok(Reply::bulk(entry.value)),
+        Err(KvError::KeyNotFound(_)) => Ok(Reply::nil()),
+        Err(e) => Err(e),
+    }
+}
+
+fn set(db: &Database, scope: Option<&str>, key: &str, value: &[u8], options: &[&[u8]]) -> Result<Reply, KvError> {
+    let mut expires_at = None;
+    let mut nx = false;
+    let mut xx = false;
+
+    let mut i = 0;
+    while i < options.len() {
+        let option = String::from_utf8_lossy(options[i]).to_uppercase();
+        match option.as_str() {
+            "NX" => nx = true,
+            "XX" => xx = true,
+            "EX" | "PX" => {
+                let unit_ms = if option == "EX" { 1000 } else { 1 };
+                let Some(at) = options
+                    .get(i + 1)
+                    .and_then(|a| parse_int(a))
+                    .filter(|n| *n > 0)
+                    .and_then(|n| n.checked_mul(unit_ms))
+                    .and_then(Duration::try_milliseconds)
+                    .and_then(|d| Utc::now().checked_add_signed(d))
+                else {
+                    return Ok(Reply::Error("ERR invalid expire time in 'set' command".into()));
+                };
+                expires_at = Some(at);
+                i += 1;
+            }
+            _ => return Ok(Reply::syntax_error()),
+        }
+        i += 1;
+    }
+
+    if nx && xx {
+        return Ok(Reply::syntax_error());
+    }
+
+    let opts = SetOptions {
+        scope,
+        expires_at,
+        ..Default::default()
+    };
+    let result = if nx || xx {
+        let expected = match live_version(db, scope, key)? {
+            Some(_) if nx => return Ok(Reply::nil()),
+            None if xx => return Ok(Reply::nil()),
+            current => current.unwrap_or(0),
+        };
+        db.set_if_version(key, value, &opts, expected)
+    } else {
+        db.set(key, value, &opts)
+    };
+
+    match result {
+        Ok(_) => Ok(Reply::ok()),
+        // Lost a race with another writer; report it the way Redis reports a failed condition
+        Err(KvError::VersionMismatch { .. }) => Ok(Reply::nil()),
+        Err(e) => Err(e),
+    }
+}
+
+fn del(db: &Database, scope: Option<&str>, keys: &[&[u8]]) -> Result<Reply, KvError> {
+    let mut deleted = 0;
+    for key in keys {
+        match db.delete(&String::from_utf8_lossy(key), &DeleteOptions { scope, hard: false }) {
+            Ok(_) => deleted += 1,
+            Err(KvError::KeyNotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(Reply::Integer(deleted))
+}
+
+fn exists(db: &Database, scope: Option<&str>, keys: &[&[u8]]) -> Result<Reply, KvError> {
+    let mut count = 0;
+    for key in keys {
+        if live_version(db, scope, &String::from_utf8_lossy(key))?.is_some() {
+            count += 1;
+        }
+    }
+    Ok(Reply::Integer(count))
+}
+
+fn matching_keys(db: &Database, scope: Option<&str>, pattern: &str) -> Result<Vec<String>, KvError> {
+    let mut keys: Vec<String> = db
+        .list_keys(&ListOptions {
+            scope,
+            ..Default::default()
+        })?
+        .into_iter()
+        .map(|s| s.key)
+        .filter(|k| glob_match(pattern.as_bytes(), k.as_bytes()))
+        .collect();
+    keys.sort();
+    Ok(keys)
+}
+
+fn keys(db: &Database, scope: Option<&str>, pattern: &str) -> Result<Reply, KvError> {
+    let keys = matching_keys(db, scope, pattern)?;
+    Ok(Reply::Array(keys.into_iter().map(Reply::bulk).collect()))
+}
+
+/// SCAN over the sorted key list; the cursor is an offset into it
+fn scan(db: &Database, scope: Option<&str>, args: &[&[u8]]) -> Result<Reply, KvError> {
+    let Some(cursor) = parse_int(args[0]).and_then(|c| usize::try_from(c).ok()) else {
+        return Ok(Reply::Error("ERR invalid cursor".into()));
+    };
+
+    let mut pattern = "*".to_string();
+    let mut count = 10usize;
+    let mut i = 1;
+    while i < args.len() {
+        let option = String::from_utf8_lossy(args[i]).to_uppercase();
+        let Some(value) = args.get(i + 1) else {
+            return Ok(Reply::syntax_error());
+        };
+        match option.as_str() {
+            "MATCH" => pattern = String::from_utf8_lossy(value).to_string(),
+            "COUNT" => match parse_int(value).filter(|c| *c > 0) {
+                Some(c) => count = c as usize,
+                None => return Ok(Reply::syntax_error()),
+            },
+            _ => return Ok(Reply::syntax_error()),
+        }
+        i += 2;
+    }
+
+    let keys = matching_keys(db, scope, &pattern)?;
+    let end = (cursor + count).min(keys.len());
+    let page = keys.get(cursor..end).unwrap_or_default();
+    let next = if end >= keys.len() { 0 } else { end };
+
+    Ok(Reply::Array(vec![
+        Reply::bulk(next.to_string()),
+        Reply::Array(page.iter().map(|k| Reply::bulk(k.as_str())).collect()),
+    ]))
+}
+
+/// TTL / PTTL: -2 if the key is missing, -1 if it never expires
+fn ttl(db: &Database, scope: Option<&str>, key: &str, unit_ms: i64) -> Result<Reply, KvError> {
+    let entry = match db.peek_entry(key, scope) {
+        Ok(entry) => entry,
+        Err(KvError::KeyNotFound(_)) => return Ok(Reply::Integer(-2)),
+        Err(e) => return Err(e),
+    };
+    Ok(Reply::Integer(match entry.expires_at {
+        Some(expires) => ((expires - Utc::now()).num_milliseconds().max(0) + unit_ms - 1) / unit_ms,
+        None => -1,
+    }))
+}
+
+/// 1 if the timeout was set, 0 if the key does not exist
+fn expire(db: &Database, scope: Option<&str>, key: &str, amount: &[u8], unit_ms: i64) -> Result<Reply, KvError> {
+    let Some(expires_at) = parse_int(amount)
+        .and_then(|n| n.checked_mul(unit_ms))
+        .and_then(chrono::Duration::try_milliseconds)
+        .and_then(|d| Utc::now().checked_add_signed(d))
+    else {
+        return Ok(not_an_integer());
+    };
+    match db.set_expiry(key, Some(expires_at), scope) {
+        Ok(_) => Ok(Reply::Integer(1)),
+        Err(KvError::KeyNotFound(_)) => Ok(Reply::Integer(0)),
+        Err(e) => Err(e),
+    }
+}
+
+/// 1 if a timeout was removed, 0 if the key has none or does not exist
+fn persist(db: &Database, scope: Option<&str>, key: &str) -> Result<Reply, KvError> {
+    match db.peek_entry(key, scope) {
+        Ok(entry) if entry.expires_at.is_some() => {
+            db.set_expiry(key, None, scope)?;
+            Ok(Reply::Integer(1))
+        }
+        Ok(_) | Err(KvError::KeyNotFound(_)) => Ok(Reply::Integer(0)),
+        Err(e) => Err(e),
+    }
+}
+
+fn incr(db: &Database, scope: Option<&str>, key: &str, delta: i64) -> Result<Reply, KvError> {
+    match db.incr(key, delta, &IncrOptions { scope, in_place: false }) {
+        Ok(value) => Ok(Reply::Integer(value)),
+        Err(KvError::NotAnInteger(_)) | Err(KvError::IntegerOverflow(_)) => Ok(not_an_integer()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Redis glob matching: `*`, `?`, `[abc]`, `[^a-z]` and `\` escapes.
+/// Iterative, backtracking only to the most recent `*`, so matching stays
+/// linear in practice however many stars the pattern has.
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Pattern position after the last `*`, and the text position it resumes from
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if pattern.get(p) == Some(&b'*') {
+            p += 1;
+            star = Some((p, t));
+            continue;
+        }
+        if let Some(len) = match_token(&pattern[p..], text[t]) {
+            p += len;
+            t += 1;
+            continue;
+        }
+        // Let the last `*` swallow one more byte and retry from there
+        match star {
+            Some((after_star, from)) => {
+                p = after_star;
+                t = from + 1;
+                star = Some((after_star, t));
+            }
+            None => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|&b| b == b'*')
+}
+
+/// Match the single non-`*` token at the start of `pattern` against `c`,
+/// returning the token's length if it matches
+fn match_token(pattern: &[u8], c: u8) -> Option<usize> {
+    match pattern.first()? {
+        b'?' => Some(1),
+        b'[' => {
+            let Some(close) = pattern[1..].iter().position(|&b| b == b']').map(|p| p + 1) else {
+                return (c == b'[').then_some(1);
+            };
+            let class = &pattern[1..close];
+            let (negate, class) = match class.first() {
+                Some(b'^') => (true, &class[1..]),
+                _ => (false, class),
+            };
+            let mut matched = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == b'-' {
+                    matched |= (class[i]..=class[i + 2]).contains(&c);
+                    i += 3;
+                } else {
+                    matched |= class[i] == c;
+                    i += 1;
+                }
+            }
+            (matched != negate).then_some(close + 1)
+        }
+        b'\\' if pattern.len() > 1 => (pattern[1] == c).then_some(2),
+        &p => (p == c).then_some(1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(db: &Database, cmd: &str) -> Reply {
+        let args: Vec<Vec<u8>> = cmd.split_whitespace().map(|s| s.as_bytes().to_vec()).collect();
+        execute(db, Some("s"), &args)
+    }
+
+    #[test]
+    fn test_read_command_array_and_inline() {
+        let mut input = &b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$4\r\na\r\nb\r\nPING\r\n"[..];
+        let args = read_command(&mut input).unwrap().unwrap();
+        assert_eq!(args, vec![b"SET".to_vec(), b"k".to_vec(), b"a\r\nb".to_vec()]);
+        assert_eq!(read_command(&mut input).unwrap().unwrap(), vec![b"PING".to_vec()]);
+        assert!(read_command(&mut input).unwrap().is_none());
+
+        // Announced lengths are capped instead of being allocated up front
+        for input in ["*9999999999999\r\n", "*1\r\n$9999999999999\r\n", "*-1\r\n"] {
+            let err = read_command(&mut input.as_bytes()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", input);
+        }
+        let err = read_command(&mut &b"*1\r\n$5\r\nab"[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut out = Vec::new();
+        Reply::Array(vec![Reply::Integer(1), Reply::nil(), Reply::bulk("hi")]).encode(&mut out);
+        assert_eq!(out, b"*3\r\n:1\r\n$-1\r\n$2\r\nhi\r\n");
+    }
+
+    #[test]
+    fn test_set_conditions_and_expiry() {
+        let db = Database::open_in_memory().unwrap();
+        assert_eq!(run(&db, "SET k v XX"), Reply::nil());
+        assert_eq!(run(&db, "SET k v NX"), Reply::ok());
+        assert_eq!(run(&db, "SET k w NX"), Reply::nil());
+        assert_eq!(run(&db, "SET k w XX EX 100"), Reply::ok());
+        assert_eq!(run(&db, "GET k"), Reply::bulk("w"));
+        assert_eq!(run(&db, "TTL k"), Reply::Integer(100));
+        assert_eq!(run(&db, "TTL missing"), Reply::Integer(-2));
+        assert_eq!(run(&db, "SET k v EX 0"), Reply::Error("ERR invalid expire time in 'set' command".into()));
+        for huge in ["EX 100000000000000", "EX 9223372036854775807", "PX 9223372036854775807"] {
+            let reply = run(&db, &format!("SET k v {}", huge));
+            assert_eq!(reply, Reply::Error("ERR invalid expire time in 'set' command".into()));
+        }
+        assert_eq!(run(&db, "SET k v NX XX"), Reply::syntax_error());
+
+        assert_eq!(run(&db, "PERSIST k"), Reply::Integer(1));
+        assert_eq!(run(&db, "TTL k"), Reply::Integer(-1));
+        assert_eq!(run(&db, "PERSIST k"), Reply::Integer(0));
+        assert_eq!(run(&db, "EXPIRE k 50"), Reply::Integer(1));
+        assert_eq!(run(&db, "TTL k"), Reply::Integer(50));
+        assert_eq!(run(&db, "EXPIRE missing 50"), Reply::Integer(0));
+        assert_eq!(run(&db, "EXPIRE k soon"), not_an_integer());
+        assert_eq!(run(&db, "PEXPIRE k -1"), Reply::Integer(1));
+        assert_eq!(run(&db, "GET k"), Reply::nil());
+
+        // Rewriting the same value still revives an expired key and applies the new expiry
+        assert_eq!(run(&db, "SET k w"), Reply::ok());
+        assert_eq!(run(&db, "GET k"), Reply::bulk("w"));
+        assert_eq!(run(&db, "SET k w EX 100"), Reply::ok());
+        assert_eq!(run(&db, "TTL k"), Reply::Integer(100));
+        assert_eq!(run(&db, "SET k w"), Reply::ok());
+        assert_eq!(run(&db, "TTL k"), Reply::Integer(-1));
+    }
+
+    #[test]
+    fn test_keys_scan_del_incr() {
+        let db = Database::open_in_memory().unwrap();
+        for k in ["job:1", "job:2", "job:3", "other"] {
+            run(&db, &format!("SET {} x", k));
+        }
+
+        assert_eq!(run(&db, "KEYS job:[12]"), Reply::Array(vec![Reply::bulk("job:1"), Reply::bulk("job:2")]));
+        assert_eq!(
+            run(&db, "SCAN 0 MATCH job:* COUNT 2"),
+            Reply::Array(vec![
+                Reply::bulk("2"),
+                Reply::Array(vec![Reply::bulk("job:1"), Reply::bulk("job:2")])
+            ])
+        );
+        assert_eq!(
+            run(&db, "SCAN 2 MATCH job:* COUNT 2"),
+            Reply::Array(vec![Reply::bulk("0"), Reply::Array(vec![Reply::bulk("job:3")])])
+        );
+
+        assert_eq!(run(&db, "EXISTS job:1 job:2 nope"), Reply::Integer(2));
+        assert_eq!(run(&db, "DEL job:1 nope"), Reply::Integer(1));
+        assert_eq!(run(&db, "INCR n"), Reply::Integer(1));
+        assert_eq!(run(&db, "DECRBY n 5"), Reply::Integer(-4));
+        assert_eq!(run(&db, "INCR other"), not_an_integer());
+        assert_eq!(run(&db, "GET"), Reply::wrong_args("GET"));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"h?llo", b"hello"));
+        assert!(glob_match(b"h*o", b"ho"));
+        assert!(glob_match(b"h[^e]llo", b"hallo"));
+        assert!(!glob_match(b"h[^e]llo", b"hello"));
+        assert!(glob_match(b"h[a-c]t", b"hbt"));
+        assert!(glob_match(b"a\\*", b"a*"));
+        assert!(!glob_match(b"a\\*", b"ab"));
+        assert!(glob_match(b"*", b""));
+        assert!(glob_match(b"job:*:done", b"job:1:x:done"));
+        assert!(!glob_match(b"job:*:done", b"job:1:done:x"));
+        assert!(glob_match(b"*a*b", b"xaxxb"));
+        assert!(!glob_match(b"a?", b"a"));
+        assert!(glob_match(b"[ab*", b"[ab-anything"));
+
+        // Many stars against a long non-matching key would be exponential with naive backtracking
+        let started = std::time::Instant::now();
+        assert!(!glob_match(b"*a*a*a*a*a*a*a*a*a*a*b", &[b'a'; 10_000]));
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    }
+}
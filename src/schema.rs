@@ -0,0 +1,458 @@
+//! JSON Schema validation for stored values.
+//!
+//! Schemas are attached to an exact key or to a prefix written as `config/*`,
+//! and every write to a matching key must validate against all of them.
+//!
+//! The validator implements the commonly used subset of draft 2020-12 (which
+//! covers draft 7 schemas too): types, enum/const, object, array, string
+//! length and numeric keywords, combinators, if/then/else and local `$ref`s.
+//! Keywords it cannot enforce, such as `pattern`, are rejected when the
+//! schema is attached rather than silently ignored.
+
+use crate::error::KvError;
+use serde_json::{Map, Value};
+
+/// Keywords that only annotate and never affect validation
+const ANNOTATIONS: &[&str] = &[
+    "$schema",
+    "$id",
+    "$comment",
+    "$anchor",
+    "title",
+    "description",
+    "default",
+    "examples",
+    "format",
+    "readOnly",
+    "writeOnly",
+    "deprecated",
+    "contentMediaType",
+    "contentEncoding",
+];
+
+/// Keywords whose value is a single subschema
+const SUBSCHEMA_KEYWORDS: &[&str] = &[
+    "additionalProperties",
+    "propertyNames",
+    "items",
+    "additionalItems",
+    "contains",
+    "not",
+    "if",
+    "then",
+    "else",
+];
+
+/// Keywords whose value is an array of subschemas
+const SUBSCHEMA_LIST_KEYWORDS: &[&str] = &["allOf", "anyOf", "oneOf", "prefixItems"];
+
+/// Keywords whose value is an object of named subschemas
+const SUBSCHEMA_MAP_KEYWORDS: &[&str] = &["properties", "$defs", "definitions"];
+
+const VALUE_KEYWORDS: &[&str] = &[
+    "type",
+    "enum",
+    "const",
+    "required",
+    "minProperties",
+    "maxProperties",
+    "minItems",
+    "maxItems",
+    "uniqueItems",
+    "minContains",
+    "maxContains",
+    "minLength",
+    "maxLength",
+    "minimum",
+    "maximum",
+    "exclusiveMinimum",
+    "exclusiveMaximum",
+    "multipleOf",
+    "$ref",
+];
+
+/// Recursion limit for `$ref` chains, so a self-referencing schema can't loop
+const MAX_DEPTH: usize = 64;
+
+/// Check that `schema` is well formed and only uses supported keywords
+pub fn check_schema(schema: &Value) -> Result<(), KvError> {
+    check_at(schema, schema, "#")
+}
+
+fn check_at(root: &Value, schema: &Value, location: &str) -> Result<(), KvError> {
+    let object = match schema {
+        Value::Bool(_) => return Ok(()),
+        Value::Object(object) => object,
+        _ => return Err(invalid_schema(location, "a schema must be an object or a boolean")),
+    };
+
+    for (keyword, value) in object {
+        let here = format!("{}/{}", location, keyword);
+        if SUBSCHEMA_KEYWORDS.contains(&keyword.as_str()) {
+            check_at(root, value, &here)?;
+        } else if SUBSCHEMA_LIST_KEYWORDS.contains(&keyword.as_str()) {
+            let list = value
+                .as_array()
+                .ok_or_else(|| invalid_schema(&here, "expected an array of schemas"))?;
+            for (i, subschema) in list.iter().enumerate() {
+                check_at(root, subschema, &format!("{}/{}", here, i))?;
+            }
+        } else if SUBSCHEMA_MAP_KEYWORDS.contains(&keyword.as_str()) {
+            let map = value
+                .as_object()
+                .ok_or_else(|| invalid_schema(&here, "expected an object of schemas"))?;
+            for (name, subschema) in map {
+                check_at(root, subschema, &format!("{}/{}", here, name))?;
+            }
+        } else if keyword == "$ref" {
+            let reference = value.as_str().unwrap_or_default();
+            if resolve_ref(root, reference).is_none() {
+                return Err(invalid_schema(&here, &format!("unresolvable reference {:?} (only local '#/...' references are supported)", reference)));
+            }
+        } else if keyword == "type" {
+            let names: Vec<&Value> = match value {
+                Value::Array(names) => names.iter().collect(),
+                other => vec![other],
+            };
+            for name in names {
+                let known = ["null", "boolean", "object", "array", "number", "integer", "string"];
+                if !name.as_str().map(|n| known.contains(&n)).unwrap_or(false) {
+                    return Err(invalid_schema(&here, &format!("unknown type {}", name)));
+                }
+            }
+        } else if !VALUE_KEYWORDS.contains(&keyword.as_str()) && !ANNOTATIONS.contains(&keyword.as_str()) {
+            return Err(invalid_schema(&here, &format!("unsupported keyword '{}'", keyword)));
+        }
+    }
+    Ok(())
+}
+
+fn invalid_schema(location: &str, message: &str) -> KvError {
+    KvError::InvalidSchema(format!("{}: {}", location, message))
+}
+
+/// Resolve a local reference such as `#/$defs/model` against the root schema
+fn resolve_ref<'a>(root: &'a Value, reference: &str) -> Option<&'a Value> {
+    let pointer = reference.strip_prefix('#')?;
+    root.pointer(pointer)
+}
+
+/// Validate `value`, returning one message per violation (empty if valid)
+pub fn validate(schema: &Value, value: &Value) -> Vec<String> {
+    let mut violations = Vec::new();
+    Validator { root: schema }.validate(schema, value, "", 0, &mut violations);
+    violations
+}
+
+struct Validator<'a> {
+    root: &'a Value,
+}
+
+impl Validator<'_> {
+    fn is_valid(&self, schema: &Value, value: &Value, depth: usize) -> bool {
+        let mut violations = Vec::new();
+        self.validate(schema, value, "", depth, &mut violations);
+        violations.is_empty()
+    }
+
+    fn validate(&self, schema: &Value, value: &Value, path: &str, depth: usize, out: &mut Vec<String>) {
+        let schema = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => return fail(out, path, "no value is allowed here".into()),
+            Value::Object(schema) => schema,
+            _ => return,
+        };
+        if depth > MAX_DEPTH {
+            return fail(out, path, "schema nesting is too deep".into());
+        }
+
+        if let Some(target) = schema.get("$ref").and_then(Value::as_str).and_then(|r| resolve_ref(self.root, r)) {
+            self.validate(target, value, path, depth + 1, out);
+        }
+
+        if let Some(expected) = schema.get("type") {
+            let matches = match expected {
+                Value::Array(names) => names.iter().any(|n| has_type(value, n.as_str().unwrap_or_default())),
+                name => has_type(value, name.as_str().unwrap_or_default()),
+            };
+            if !matches {
+                fail(out, path, format!("expected {}, found {}", type_list(expected), type_name(value)));
+            }
+        }
+        if let Some(Value::Array(options)) = schema.get("enum") {
+            if !options.iter().any(|option| json_eq(option, value)) {
+                fail(out, path, format!("{} is not one of {}", value, Value::Array(options.clone())));
+            }
+        }
+        if let Some(constant) = schema.get("const") {
+            if !json_eq(constant, value) {
+                fail(out, path, format!("expected {}, found {}", constant, value));
+            }
+        }
+
+        match value {
+            Value::Object(object) => self.validate_object(schema, object, path, depth, out),
+            Value::Array(items) => self.validate_array(schema, items, path, depth, out),
+            Value::String(s) => validate_string(schema, s, path, out),
+            Value::Number(n) => validate_number(schema, n.as_f64().unwrap_or(f64::NAN), path, out),
+            _ => {}
+        }
+
+        self.validate_combinators(schema, value, path, depth, out);
+    }
+
+    fn validate_object(&self, schema: &Map<String, Value>, object: &Map<String, Value>, path: &str, depth: usize, out: &mut Vec<String>) {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(name) {
+                    fail(out, path, format!("missing required property '{}'", name));
+                }
+            }
+        }
+        check_count(out, path, "properties", object.len(), schema.get("minProperties"), schema.get("maxProperties"));
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (name, member) in object {
+            let member_path = format!("{}/{}", path, name.replace('~', "~0").replace('/', "~1"));
+            match properties.and_then(|p| p.get(name)) {
+                Some(subschema) => self.validate(subschema, member, &member_path, depth + 1, out),
+                None => {
+                    if let Some(additional) = schema.get("additionalProperties") {
+                        if additional == &Value::Bool(false) {
+                            fail(out, path, format!("unexpected property '{}'", name));
+                        } else {
+                            self.validate(additional, member, &member_path, depth + 1, out);
+                        }
+                    }
+                }
+            }
+            if let Some(names) = schema.get("propertyNames") {
+                if !self.is_valid(names, &Value::String(name.clone()), depth + 1) {
+                    fail(out, path, format!("property name '{}' is not allowed", name));
+                }
+            }
+        }
+    }
+
+    fn validate_array(&self, schema: &Map<String, Value>, items: &[Value], path: &str, depth: usize, out: &mut Vec<String>) {
+        check_count(out, path, "items", items.len(), schema.get("minItems"), schema.get("maxItems"));
+
+        if schema.get("uniqueItems") == Some(&Value::Bool(true)) {
+            for (i, item) in items.iter().enumerate() {
+                if items[..i].iter().any(|earlier| json_eq(earlier, item)) {
+                    fail(out, path, format!("item {} is a duplicate", i));
+                }
+            }
+        }
+
+        // Draft 7 spells prefixItems as an array-valued items
+        let (prefix, rest) = match (schema.get("prefixItems"), schema.get("items")) {
+            (Some(Value::Array(prefix)), rest) => (prefix.as_slice(), rest),
+            (None, Some(Value::Array(prefix))) => (prefix.as_slice(), schema.get("additionalItems")),
+            (_, rest) => (&[][..], rest),
+        };
+        for (i, item) in items.iter().enumerate() {
+            let subschema = prefix.get(i).or(rest);
+            if let Some(subschema) = subschema {
+                self.validate(subschema, item, &format!("{}/{}", path, i), depth + 1, out);
+            }
+        }
+
+        if let Some(contains) = schema.get("contains") {
+            let matching = items.iter().filter(|item| self.is_valid(contains, item, depth + 1)).count();
+            let min = schema.get("minContains").and_then(Value::as_u64).unwrap_or(1) as usize;
+            let max = schema.get("maxContains").and_then(Value::as_u64).map(|m| m as usize);
+            if matching < min || max.map(|m| matching > m).unwrap_or(false) {
+                fail(out, path, format!("{} items match 'contains'", matching));
+            }
+        }
+    }
+
+    fn validate_combinators(&self, schema: &Map<String, Value>, value: &Value, path: &str, depth: usize, out: &mut Vec<String>) {
+        if let Some(Value::Array(all)) = schema.get("allOf") {
+            for subschema in all {
+                self.validate(subschema, value, path, depth + 1, out);
+            }
+        }
+        if let Some(Value::Array(any)) = schema.get("anyOf") {
+            if !any.iter().any(|s| self.is_valid(s, value, depth + 1)) {
+                fail(out, path, "does not match any schema in anyOf".into());
+            }
+        }
+        if let Some(Value::Array(one)) = schema.get("oneOf") {
+            let matching = one.iter().filter(|s| self.is_valid(s, value, depth + 1)).count();
+            if matching != 1 {
+                fail(out, path, format!("matches {} schemas in oneOf, expected exactly 1", matching));
+            }
+        }
+        if let Some(not) = schema.get("not") {
+            if self.is_valid(not, value, depth + 1) {
+                fail(out, path, "matches a schema it must not match".into());
+            }
+        }
+        if let Some(condition) = schema.get("if") {
+            let branch = if self.is_valid(condition, value, depth + 1) {
+                schema.get("then")
+            } else {
+                schema.get("else")
+            };
+            if let Some(branch) = branch {
+                self.validate(branch, value, path, depth + 1, out);
+            }
+        }
+    }
+}
+
+fn validate_string(schema: &Map<String, Value>, s: &str, path: &str, out: &mut Vec<String>) {
+    check_count(out, path, "characters", s.chars().count(), schema.get("minLength"), schema.get("maxLength"));
+}
+
+fn validate_number(schema: &Map<String, Value>, n: f64, path: &str, out: &mut Vec<String>) {
+    let bound = |name: &str| schema.get(name).and_then(Value::as_f64);
+    if let Some(min) = bound("minimum").filter(|&min| n < min) {
+        fail(out, path, format!("{} is less than the minimum of {}", n, min));
+    }
+    if let Some(max) = bound("maximum").filter(|&max| n > max) {
+        fail(out, path, format!("{} is greater than the maximum of {}", n, max));
+    }
+    if let Some(min) = bound("exclusiveMinimum").filter(|&min| n <= min) {
+        fail(out, path, format!("{} must be greater than {}", n, min));
+    }
+    if let Some(max) = bound("exclusiveMaximum").filter(|&max| n >= max) {
+        fail(out, path, format!("{} must be less than {}", n, max));
+    }
+    if let Some(step) = bound("multipleOf").filter(|&step| step > 0.0) {
+        let quotient = n / step;
+        if (quotient - quotient.round()).abs() > 1e-9 {
+            fail(out, path, format!("{} is not a multiple of {}", n, step));
+        }
+    }
+}
+
+fn check_count(out: &mut Vec<String>, path: &str, what: &str, count: usize, min: Option<&Value>, max: Option<&Value>) {
+    if let Some(min) = min.and_then(Value::as_u64).filter(|&min| (count as u64) < min) {
+        fail(out, path, format!("has {} {}, fewer than the minimum of {}", count, what, min));
+    }
+    if let Some(max) = max.and_then(Value::as_u64).filter(|&max| (count as u64) > max) {
+        fail(out, path, format!("has {} {}, more than the maximum of {}", count, what, max));
+    }
+}
+
+fn fail(out: &mut Vec<String>, path: &str, message: String) {
+    let location = if path.is_empty() { "/" } else { path };
+    out.push(format!("{}: {}", location, message));
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "integer" => value.as_f64().map(|n| n.fract() == 0.0).unwrap_or(false),
+        name => type_name(value) == name,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn type_list(expected: &Value) -> String {
+    match expected {
+        Value::Array(names) => names.iter().filter_map(Value::as_str).collect::<Vec<_>>().join(" or "),
+        name => name.as_str().unwrap_or_default().to_string(),
+    }
+}
+
+/// JSON equality where 1 and 1.0 are the same number
+fn json_eq(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64() == y.as_f64(),
+        (Value::Array(x), Value::Array(y)) => x.len() == y.len() && x.iter().zip(y).all(|(a, b)| json_eq(a, b)),
+        (Value::Object(x), Value::Object(y)) => {
+            x.len() == y.len() && x.iter().all(|(k, v)| y.get(k).map(|w| json_eq(v, w)).unwrap_or(false))
+        }
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config_schema() -> Value {
+        json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "type": "object",
+            "required": ["model", "retries"],
+            "properties": {
+                "model": { "enum": ["opus", "sonnet", "haiku"] },
+                "retries": { "type": "integer", "minimum": 0, "maximum": 5 },
+                "tags": { "type": "array", "items": { "$ref": "#/$defs/tag" }, "uniqueItems": true },
+                "timeout": { "anyOf": [{ "type": "number", "exclusiveMinimum": 0 }, { "type": "null" }] }
+            },
+            "additionalProperties": false,
+            "$defs": { "tag": { "type": "string", "minLength": 1, "maxLength": 8 } }
+        })
+    }
+
+    #[test]
+    fn test_validate() {
+        let schema = config_schema();
+        check_schema(&schema).unwrap();
+
+        let valid = json!({ "model": "opus", "retries": 2.0, "tags": ["a", "b"], "timeout": null });
+        assert!(validate(&schema, &valid).is_empty());
+
+        let invalid = json!({ "model": "gpt", "retries": 9, "tags": ["a", "a", ""], "timeout": 0, "extra": 1 });
+        let violations = validate(&schema, &invalid);
+        assert_eq!(
+            violations,
+            [
+                "/model: \"gpt\" is not one of [\"opus\",\"sonnet\",\"haiku\"]",
+                "/retries: 9 is greater than the maximum of 5",
+                "/tags: item 1 is a duplicate",
+                "/tags/2: has 0 characters, fewer than the minimum of 1",
+                "/timeout: does not match any schema in anyOf",
+                "/: unexpected property 'extra'",
+            ]
+        );
+
+        assert_eq!(validate(&schema, &json!("text")), ["/: expected object, found string"]);
+        assert_eq!(validate(&schema, &json!({})).len(), 2);
+        assert!(validate(&json!(true), &json!(1)).is_empty());
+        assert_eq!(validate(&json!(false), &json!(1)).len(), 1);
+    }
+
+    #[test]
+    fn test_combinators_and_conditionals() {
+        let schema = json!({
+            "oneOf": [{ "type": "integer" }, { "type": "number", "multipleOf": 0.5 }],
+            "not": { "const": 0 },
+            "if": { "minimum": 10 }, "then": { "maximum": 20 }
+        });
+        assert!(validate(&schema, &json!(1.5)).is_empty());
+        assert_eq!(validate(&schema, &json!(2)).len(), 1); // matches both oneOf branches
+        assert_eq!(validate(&schema, &json!(0.5)).len(), 0);
+        assert_eq!(validate(&schema, &json!(25.5)), ["/: 25.5 is greater than the maximum of 20"]);
+
+        // A self-referencing schema terminates
+        let recursive = json!({ "$ref": "#" });
+        check_schema(&recursive).unwrap();
+        assert!(!validate(&recursive, &json!(1)).is_empty());
+    }
+
+    #[test]
+    fn test_check_schema() {
+        assert!(check_schema(&json!({ "type": "string", "pattern": "^a" })).is_err());
+        assert!(check_schema(&json!({ "type": "strng" })).is_err());
+        assert!(check_schema(&json!({ "$ref": "https://example.com/schema.json" })).is_err());
+        assert!(check_schema(&json!({ "properties": { "a": 1 } })).is_err());
+        assert!(check_schema(&json!(42)).is_err());
+    }
+}
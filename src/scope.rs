@@ -24,6 +24,15 @@ pub fn hash_path(path: &Path) -> String {
     hex::encode(&result[..6])
 }
 
+/// Whether a key pattern (`key`, `prefix*`, or `*` for every key) applies to
+/// `key`. Shared by schemas and policies.
+pub fn pattern_matches(pattern: &str, key: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => key.starts_with(prefix),
+        None => pattern == key,
+    }
+}
+
 /// Simple hex encoding (to avoid adding hex crate)
 mod hex {
     const HEX_CHARS: &[u8; 16] = b"0123456789abcdef";
@@ -57,4 +66,12 @@ mod tests {
         let hash2 = hash_path(Path::new("/tmp/b"));
         assert_ne!(hash1, hash2);
     }
+
+    #[test]
+    fn test_pattern_matches() {
+        assert!(pattern_matches("config/*", "config/agent"));
+        assert!(pattern_matches("config", "config"));
+        assert!(!pattern_matches("config", "config/agent"));
+        assert!(pattern_matches("*", "anything"));
+    }
 }
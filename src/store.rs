@@ -0,0 +1,509 @@
+//! Backend-agnostic interface to a versioned key-value store.
+//!
+//! `KvStore` is the stable surface for embedding kv in other programs. Every
+//! operation takes an options struct so new knobs can be added without
+//! breaking callers; build them with struct update syntax over `Default`.
+//!
+//! ```no_run
+//! use douglance_kv::{Database, KvStore, GetOptions, SetOptions};
+//!
+//! let db = Database::open()?;
+//! db.set("greeting", b"hello", &SetOptions::default())?;
+//! let entry = db.get("greeting", &GetOptions::default())?;
+//! assert_eq!(entry.value, b"hello");
+//! # Ok::<(), douglance_kv::KvError>(())
+//! ```
+
+use crate::error::KvError;
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// One stored version of a key
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub id: i64,
+    pub key: String,
+    pub value: Vec<u8>,
+    pub version: i64,
+    pub content_type: Option<String>,
+    pub original_filename: Option<String>,
+    pub size_bytes: i64,
+    pub created_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub scope: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// With sliding expiration, each read pushes `expires_at` this far ahead
+    pub sliding_ttl: Option<chrono::Duration>,
+}
+
+impl Entry {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.map(|e| e < Utc::now()).unwrap_or(false)
+    }
+}
+
+/// One row of a key listing, aggregated over the key's versions
+#[derive(Debug, Clone)]
+pub struct KeySummary {
+    pub key: String,
+    pub versions: i64,
+    pub total_size: i64,
+    pub last_updated: DateTime<Utc>,
+    pub scope: Option<String>,
+}
+
+/// Statistics about the key-value store
+#[derive(Debug, Clone)]
+pub struct Stats {
+    pub total_size: i64,
+    pub total_entries: i64,
+    pub active_keys: i64,
+    pub deleted_keys: i64,
+    pub expired_keys: i64,
+    pub oldest_key: Option<String>,
+    pub oldest_date: Option<DateTime<Utc>>,
+    pub largest_key: Option<String>,
+    pub largest_size: i64,
+    pub scopes: Vec<ScopeStats>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScopeStats {
+    pub scope: Option<String>,
+    pub size: i64,
+    pub keys: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct GcResult {
+    pub entries_count: i64,
+    pub bytes_freed: i64,
+    pub was_run: bool,
+}
+
+/// Outcome of a `set`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetResult {
+    pub version: i64,
+    /// False when the value matched the latest version and nothing was written
+    pub was_saved: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SetOptions<'a> {
+    pub scope: Option<&'a str>,
+    pub content_type: Option<&'a str>,
+    pub original_filename: Option<&'a str>,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Slide the expiry: reads of the latest version reset it to now + this
+    pub sliding_ttl: Option<chrono::Duration>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GetOptions<'a> {
+    pub scope: Option<&'a str>,
+    /// Fetch this version instead of the latest live one
+    pub version: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DeleteOptions<'a> {
+    pub scope: Option<&'a str>,
+    /// Remove every version outright instead of marking them deleted
+    pub hard: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IncrOptions<'a> {
+    pub scope: Option<&'a str>,
+    /// Overwrite the latest version rather than adding a new one
+    pub in_place: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ListOptions<'a> {
+    pub scope: Option<&'a str>,
+    /// List keys from every scope, ignoring `scope`
+    pub all_scopes: bool,
+    pub prefix: Option<&'a str>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HistoryOptions<'a> {
+    pub scope: Option<&'a str>,
+    pub limit: Option<usize>,
+}
+
+/// Which rows `gc` collects. The default removes expired and deleted entries.
+#[derive(Debug, Clone)]
+pub struct GcOptions {
+    /// Only report what would be removed
+    pub dry_run: bool,
+    pub expired: bool,
+    pub deleted: bool,
+    /// Only remove soft-deleted entries deleted longer ago than this, so they can still be restored
+    pub deleted_older_than: Option<chrono::Duration>,
+    /// Also remove versions created longer ago than this
+    pub older_than: Option<chrono::Duration>,
+    /// Also remove all but the newest N versions of each key
+    pub keep_versions: Option<usize>,
+}
+
+impl Default for GcOptions {
+    fn default() -> Self {
+        Self {
+            dry_run: false,
+            expired: true,
+            deleted: true,
+            deleted_older_than: None,
+            older_than: None,
+            keep_versions: None,
+        }
+    }
+}
+
+/// Core operations of a versioned key-value store
+pub trait KvStore {
+    /// Store `value` as a new version of `key`, unless it equals the latest version
+    fn set(&self, key: &str, value: &[u8], opts: &SetOptions) -> Result<SetResult, KvError>;
+
+    /// Latest live version of `key`, or the version named in `opts`. Reading
+    /// the latest version of a sliding key extends its expiry.
+    fn get(&self, key: &str, opts: &GetOptions) -> Result<Entry, KvError>;
+
+    /// Delete every version of `key`, returning how many were affected
+    fn delete(&self, key: &str, opts: &DeleteOptions) -> Result<u64, KvError>;
+
+    /// Undo a soft delete, returning how many versions were restored
+    fn restore(&self, key: &str, scope: Option<&str>) -> Result<u64, KvError>;
+
+    /// Add `delta` to the integer stored at `key`, treating a missing key as 0
+    fn incr(&self, key: &str, delta: i64, opts: &IncrOptions) -> Result<i64, KvError>;
+
+    fn list_keys(&self, opts: &ListOptions) -> Result<Vec<KeySummary>, KvError>;
+
+    /// Versions of `key`, newest first
+    fn history(&self, key: &str, opts: &HistoryOptions) -> Result<Vec<Entry>, KvError>;
+
+    fn stats(&self) -> Result<Stats, KvError>;
+
+    fn gc(&self, opts: &GcOptions) -> Result<GcResult, KvError>;
+
+    /// Store `value` serialized as JSON, recorded as `application/json`
+    fn set_json<T: Serialize + ?Sized>(&self, key: &str, value: &T, opts: &SetOptions) -> Result<SetResult, KvError>
+    where
+        Self: Sized,
+    {
+        let bytes = serde_json::to_vec(value).map_err(|e| encode_error("json", e))?;
+        self.set(key, &bytes, &with_content_type(opts, "application/json"))
+    }
+
+    /// Fetch a value and deserialize it from JSON
+    fn get_json<T: DeserializeOwned>(&self, key: &str, opts: &GetOptions) -> Result<T, KvError>
+    where
+        Self: Sized,
+    {
+        let entry = self.get(key, opts)?;
+        serde_json::from_slice(&entry.value).map_err(|e| decode_error(key, "json", e))
+    }
+
+    /// Store `value` serialized as MessagePack, recorded as `application/msgpack`
+    #[cfg(feature = "msgpack")]
+    fn set_msgpack<T: Serialize + ?Sized>(&self, key: &str, value: &T, opts: &SetOptions) -> Result<SetResult, KvError>
+    where
+        Self: Sized,
+    {
+        let bytes = rmp_serde::to_vec_named(value).map_err(|e| encode_error("msgpack", e))?;
+        self.set(key, &bytes, &with_content_type(opts, "application/msgpack"))
+    }
+
+    #[cfg(feature = "msgpack")]
+    fn get_msgpack<T: DeserializeOwned>(&self, key: &str, opts: &GetOptions) -> Result<T, KvError>
+    where
+        Self: Sized,
+    {
+        let entry = self.get(key, opts)?;
+        rmp_serde::from_slice(&entry.value).map_err(|e| decode_error(key, "msgpack", e))
+    }
+
+    /// Store `value` serialized as CBOR, recorded as `application/cbor`
+    #[cfg(feature = "cbor")]
+    fn set_cbor<T: Serialize + ?Sized>(&self, key: &str, value: &T, opts: &SetOptions) -> Result<SetResult, KvError>
+    where
+        Self: Sized,
+    {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(value, &mut bytes).map_err(|e| encode_error("cbor", e))?;
+        self.set(key, &bytes, &with_content_type(opts, "application/cbor"))
+    }
+
+    #[cfg(feature = "cbor")]
+    fn get_cbor<T: DeserializeOwned>(&self, key: &str, opts: &GetOptions) -> Result<T, KvError>
+    where
+        Self: Sized,
+    {
+        let entry = self.get(key, opts)?;
+        ciborium::from_reader(entry.value.as_slice()).map_err(|e| decode_error(key, "cbor", e))
+    }
+}
+
+fn with_content_type<'a>(opts: &SetOptions<'a>, content_type: &'a str) -> SetOptions<'a> {
+    SetOptions {
+        content_type: Some(content_type),
+        ..opts.clone()
+    }
+}
+
+fn encode_error(format: &'static str, err: impl std::fmt::Display) -> KvError {
+    KvError::Encode {
+        format,
+        message: err.to_string(),
+    }
+}
+
+fn decode_error(key: &str, format: &'static str, err: impl std::fmt::Display) -> KvError {
+    KvError::Decode {
+        key: key.to_string(),
+        format,
+        message: err.to_string(),
+    }
+}
+
+/// Behaviour every `KvStore` backend must share, run by each backend's tests
+#[cfg(test)]
+pub(crate) mod conformance {
+    use super::*;
+    use chrono::Duration;
+
+    pub fn run(new_store: impl Fn() -> Box<dyn KvStore>) {
+        versioning(&*new_store());
+        scoping(&*new_store());
+        expiration(&*new_store());
+        soft_delete(&*new_store());
+        counters(&*new_store());
+        garbage_collection(&*new_store());
+        statistics(&*new_store());
+    }
+
+    fn scoped(scope: &str) -> SetOptions<'_> {
+        SetOptions {
+            scope: Some(scope),
+            ..Default::default()
+        }
+    }
+
+    fn expiring(offset: Duration) -> SetOptions<'static> {
+        SetOptions {
+            expires_at: Some(Utc::now() + offset),
+            ..Default::default()
+        }
+    }
+
+    fn value(store: &dyn KvStore, key: &str, opts: &GetOptions) -> Vec<u8> {
+        store.get(key, opts).unwrap().value
+    }
+
+    fn versioning(store: &dyn KvStore) {
+        let plain = SetOptions {
+            content_type: Some("text/plain"),
+            ..Default::default()
+        };
+        assert_eq!(store.set("k", b"a", &plain).unwrap(), SetResult { version: 1, was_saved: true });
+        assert_eq!(store.set("k", b"a", &plain).unwrap(), SetResult { version: 1, was_saved: false });
+        assert_eq!(store.set("k", b"b", &plain).unwrap(), SetResult { version: 2, was_saved: true });
+
+        let latest = store.get("k", &GetOptions::default()).unwrap();
+        assert_eq!((latest.value.as_slice(), latest.version), (&b"b"[..], 2));
+        assert_eq!(latest.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(latest.size_bytes, 1);
+        assert_eq!(value(store, "k", &GetOptions { version: Some(1), ..Default::default() }), b"a");
+        assert!(matches!(
+            store.get("k", &GetOptions { version: Some(9), ..Default::default() }),
+            Err(KvError::VersionNotFound { version: 9, .. })
+        ));
+        assert!(matches!(store.get("nope", &GetOptions::default()), Err(KvError::KeyNotFound(_))));
+
+        let history = store.history("k", &HistoryOptions::default()).unwrap();
+        assert_eq!(history.iter().map(|e| e.version).collect::<Vec<_>>(), vec![2, 1]);
+        let limited = HistoryOptions { limit: Some(1), ..Default::default() };
+        assert_eq!(store.history("k", &limited).unwrap().len(), 1);
+        assert!(matches!(store.history("nope", &HistoryOptions::default()), Err(KvError::KeyNotFound(_))));
+    }
+
+    fn scoping(store: &dyn KvStore) {
+        store.set("k", b"global", &SetOptions::default()).unwrap();
+        store.set("k", b"scoped", &scoped("s")).unwrap();
+        store.set("k/2", b"x", &scoped("s")).unwrap();
+        store.set("other", b"x", &scoped("s")).unwrap();
+
+        assert_eq!(value(store, "k", &GetOptions::default()), b"global");
+        assert_eq!(value(store, "k", &GetOptions { scope: Some("s"), ..Default::default() }), b"scoped");
+        assert!(store.get("k/2", &GetOptions::default()).is_err());
+
+        let in_scope = store.list_keys(&ListOptions { scope: Some("s"), ..Default::default() }).unwrap();
+        assert_eq!(in_scope.len(), 3);
+        assert!(in_scope.iter().all(|k| k.scope.as_deref() == Some("s")));
+
+        let prefixed = ListOptions {
+            scope: Some("s"),
+            prefix: Some("k"),
+            ..Default::default()
+        };
+        let mut keys: Vec<_> = store.list_keys(&prefixed).unwrap().into_iter().map(|k| k.key).collect();
+        keys.sort();
+        assert_eq!(keys, vec!["k", "k/2"]);
+
+        let everywhere = ListOptions { all_scopes: true, ..Default::default() };
+        assert_eq!(store.list_keys(&everywhere).unwrap().len(), 4);
+        let limited = ListOptions { all_scopes: true, limit: Some(2), ..Default::default() };
+        assert_eq!(store.list_keys(&limited).unwrap().len(), 2);
+    }
+
+    fn expiration(store: &dyn KvStore) {
+        store.set("gone", b"x", &expiring(Duration::seconds(-1))).unwrap();
+        store.set("kept", b"x", &expiring(Duration::hours(1))).unwrap();
+
+        assert!(matches!(store.get("gone", &GetOptions::default()), Err(KvError::KeyNotFound(_))));
+        assert!(store.get("kept", &GetOptions::default()).unwrap().expires_at.is_some());
+
+        let keys: Vec<_> = store.list_keys(&ListOptions::default()).unwrap().into_iter().map(|k| k.key).collect();
+        assert_eq!(keys, vec!["kept"]);
+
+        // Writing the same value again revives an expired key and updates a changed expiry
+        assert!(store.set("gone", b"x", &SetOptions::default()).unwrap().was_saved);
+        assert_eq!(value(store, "gone", &GetOptions::default()), b"x");
+        assert!(!store.set("gone", b"x", &SetOptions::default()).unwrap().was_saved);
+        assert!(store.set("gone", b"x", &expiring(Duration::hours(1))).unwrap().was_saved);
+        assert!(store.get("gone", &GetOptions::default()).unwrap().expires_at.is_some());
+
+        // An expired value counts as missing, and a new version replaces it
+        store.set("n", b"41", &expiring(Duration::seconds(-1))).unwrap();
+        assert_eq!(store.incr("n", 1, &IncrOptions::default()).unwrap(), 1);
+
+        // Reading a sliding key pushes its expiry out again, and new versions keep sliding
+        let sliding = SetOptions {
+            expires_at: Some(Utc::now() + Duration::seconds(5)),
+            sliding_ttl: Some(Duration::hours(1)),
+            ..Default::default()
+        };
+        store.set("cache", b"1", &sliding).unwrap();
+        let entry = store.get("cache", &GetOptions::default()).unwrap();
+        assert!(entry.expires_at.unwrap() > Utc::now() + Duration::minutes(59));
+        assert_eq!(entry.sliding_ttl, Some(Duration::hours(1)));
+        store.incr("cache", 1, &IncrOptions::default()).unwrap();
+        assert_eq!(store.get("cache", &GetOptions::default()).unwrap().sliding_ttl, Some(Duration::hours(1)));
+    }
+
+    fn soft_delete(store: &dyn KvStore) {
+        store.set("k", b"a", &SetOptions::default()).unwrap();
+        store.set("k", b"b", &SetOptions::default()).unwrap();
+
+        assert_eq!(store.delete("k", &DeleteOptions::default()).unwrap(), 2);
+        assert!(matches!(store.get("k", &GetOptions::default()), Err(KvError::KeyNotFound(_))));
+        assert!(matches!(store.delete("k", &DeleteOptions::default()), Err(KvError::KeyNotFound(_))));
+        assert!(store.list_keys(&ListOptions::default()).unwrap().is_empty());
+
+        // Deleted versions stay visible by number and in the history
+        assert_eq!(value(store, "k", &GetOptions { version: Some(1), ..Default::default() }), b"a");
+        let history = store.history("k", &HistoryOptions::default()).unwrap();
+        assert!(history.iter().all(|e| e.deleted_at.is_some()));
+
+        assert_eq!(store.restore("k", None).unwrap(), 2);
+        assert_eq!(value(store, "k", &GetOptions::default()), b"b");
+        assert!(matches!(store.restore("k", None), Err(KvError::KeyNotFound(_))));
+
+        let hard = DeleteOptions { hard: true, ..Default::default() };
+        assert_eq!(store.delete("k", &hard).unwrap(), 2);
+        assert!(store.history("k", &HistoryOptions::default()).is_err());
+        assert_eq!(store.set("k", b"c", &SetOptions::default()).unwrap().version, 1);
+    }
+
+    fn counters(store: &dyn KvStore) {
+        assert_eq!(store.incr("n", 5, &IncrOptions::default()).unwrap(), 5);
+        assert_eq!(store.incr("n", -7, &IncrOptions::default()).unwrap(), -2);
+        assert_eq!(store.get("n", &GetOptions::default()).unwrap().version, 2);
+
+        let in_place = IncrOptions { in_place: true, ..Default::default() };
+        assert_eq!(store.incr("n", 1, &in_place).unwrap(), -1);
+        let latest = store.get("n", &GetOptions::default()).unwrap();
+        assert_eq!((latest.value.as_slice(), latest.version), (&b"-1"[..], 2));
+        assert_eq!(latest.content_type.as_deref(), Some("text/plain"));
+
+        store.set("s", b"text", &SetOptions::default()).unwrap();
+        assert!(matches!(store.incr("s", 1, &IncrOptions::default()), Err(KvError::NotAnInteger(_))));
+        store.set("big", i64::MAX.to_string().as_bytes(), &SetOptions::default()).unwrap();
+        assert!(matches!(store.incr("big", 1, &IncrOptions::default()), Err(KvError::IntegerOverflow(_))));
+    }
+
+    fn garbage_collection(store: &dyn KvStore) {
+        store.set("deleted", b"aa", &SetOptions::default()).unwrap();
+        store.delete("deleted", &DeleteOptions::default()).unwrap();
+        store.set("expired", b"bbb", &expiring(Duration::seconds(-1))).unwrap();
+        for v in ["1", "2", "3"] {
+            store.set("versions", v.as_bytes(), &SetOptions::default()).unwrap();
+        }
+
+        let dry = store.gc(&GcOptions { dry_run: true, ..Default::default() }).unwrap();
+        assert_eq!((dry.entries_count, dry.bytes_freed, dry.was_run), (2, 5, false));
+        assert_eq!(store.stats().unwrap().total_entries, 5);
+
+        let only_deleted = GcOptions {
+            dry_run: true,
+            expired: false,
+            ..Default::default()
+        };
+        assert_eq!(store.gc(&only_deleted).unwrap().entries_count, 1);
+        let retained = GcOptions {
+            deleted_older_than: Some(Duration::hours(1)),
+            ..only_deleted.clone()
+        };
+        assert_eq!(store.gc(&retained).unwrap().entries_count, 0);
+
+        let keep = GcOptions {
+            expired: false,
+            deleted: false,
+            keep_versions: Some(1),
+            ..Default::default()
+        };
+        let result = store.gc(&keep).unwrap();
+        assert_eq!((result.entries_count, result.was_run), (2, true));
+        assert_eq!(store.history("versions", &HistoryOptions::default()).unwrap().len(), 1);
+        assert_eq!(value(store, "versions", &GetOptions::default()), b"3");
+
+        assert_eq!(store.gc(&GcOptions::default()).unwrap().entries_count, 2);
+        assert_eq!(store.stats().unwrap().total_entries, 1);
+
+        let old = GcOptions {
+            older_than: Some(Duration::zero()),
+            ..Default::default()
+        };
+        assert_eq!(store.gc(&old).unwrap().entries_count, 1);
+        assert!(store.history("versions", &HistoryOptions::default()).is_err());
+    }
+
+    fn statistics(store: &dyn KvStore) {
+        store.set("a", b"1234", &SetOptions::default()).unwrap();
+        store.set("a", b"12", &SetOptions::default()).unwrap();
+        store.set("b", b"1", &scoped("s")).unwrap();
+        store.set("c", b"1", &SetOptions::default()).unwrap();
+        store.delete("c", &DeleteOptions::default()).unwrap();
+        store.set("d", b"1", &expiring(Duration::seconds(-1))).unwrap();
+
+        let stats = store.stats().unwrap();
+        assert_eq!((stats.total_size, stats.total_entries), (9, 5));
+        assert_eq!((stats.active_keys, stats.deleted_keys, stats.expired_keys), (2, 1, 1));
+        assert_eq!(stats.oldest_key.as_deref(), Some("a"));
+        assert_eq!((stats.largest_key.as_deref(), stats.largest_size), (Some("a"), 6));
+
+        let scopes: Vec<_> = stats.scopes.iter().map(|s| (s.scope.as_deref(), s.size, s.keys)).collect();
+        assert_eq!(scopes, vec![(None, 7, 2), (Some("s"), 1, 1)]);
+
+        // Ties for the largest key go to the first key in order
+        store.set("z", b"123456", &SetOptions::default()).unwrap();
+        assert_eq!(store.stats().unwrap().largest_key.as_deref(), Some("a"));
+        store.set("0", b"123456", &SetOptions::default()).unwrap();
+        assert_eq!(store.stats().unwrap().largest_key.as_deref(), Some("0"));
+    }
+}
@@ -0,0 +1,194 @@
+//! Parsing of TTLs and expiry times.
+//!
+//! Durations are either compound unit strings (`30s`, `1h30m`, `2w3d`) or
+//! ISO-8601 durations (`PT1H30M`, `P1DT12H`, `PT0.5S`). Years and months are
+//! rejected because their length varies. Absolute times are RFC 3339
+//! timestamps or plain dates, which mean midnight UTC.
+
+use crate::error::KvError;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+/// Parse a TTL into the time it expires, counted from now
+pub fn parse_ttl(ttl: &str) -> Result<DateTime<Utc>, KvError> {
+    Utc::now()
+        .checked_add_signed(parse_duration(ttl)?)
+        .ok_or_else(|| invalid(format!("TTL too large: {}", ttl)))
+}
+
+/// Parse a duration such as "30s", "1h30m", "2w" or "PT1H30M"
+pub fn parse_duration(ttl: &str) -> Result<Duration, KvError> {
+    let ttl = ttl.trim();
+    if ttl.is_empty() {
+        return Err(invalid("empty TTL".into()));
+    }
+
+    match ttl.strip_prefix(['P', 'p']) {
+        Some(rest) => parse_iso8601(ttl, rest),
+        None => parse_units(ttl),
+    }
+}
+
+/// Parse an absolute expiry: "2026-12-01T00:00:00Z", "2026-12-01T09:00:00+02:00" or "2026-12-01"
+pub fn parse_expires_at(at: &str) -> Result<DateTime<Utc>, KvError> {
+    let at = at.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(at) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(at, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|dt| dt.and_utc())
+        .ok_or_else(|| invalid(format!("invalid time: {} (use RFC 3339, e.g. 2026-12-01T00:00:00Z)", at)))
+}
+
+/// Format a duration in compound units, e.g. 5400 seconds as "1h30m"
+pub fn format_duration(duration: Duration) -> String {
+    let mut seconds = duration.num_seconds();
+    if seconds <= 0 {
+        return "0s".into();
+    }
+    let mut out = String::new();
+    for (unit, size) in [("w", 7 * 24 * 60 * 60), ("d", 24 * 60 * 60), ("h", 60 * 60), ("m", 60), ("s", 1)] {
+        if seconds >= size {
+            out.push_str(&format!("{}{}", seconds / size, unit));
+            seconds %= size;
+        }
+    }
+    out
+}
+
+fn invalid(msg: String) -> KvError {
+    KvError::InvalidTtl(msg)
+}
+
+/// Compound durations: one or more <integer><unit> pairs with units s/m/h/d/w
+fn parse_units(ttl: &str) -> Result<Duration, KvError> {
+    let mut total = Duration::zero();
+    let mut rest = ttl;
+
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let unit_len = rest[digits..]
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len() - digits);
+        let (number, unit) = (&rest[..digits], &rest[digits..digits + unit_len]);
+        rest = &rest[digits + unit_len..];
+
+        let number: i64 = number
+            .parse()
+            .map_err(|_| invalid(format!("invalid number in TTL: {}", ttl)))?;
+        let seconds_per_unit = match unit {
+            "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            "w" => 7 * 24 * 60 * 60,
+            "" => return Err(invalid(format!("missing unit in TTL: {} (use s/m/h/d/w)", ttl))),
+            _ => return Err(invalid(format!("invalid unit in TTL: {} (use s/m/h/d/w)", ttl))),
+        };
+        total = add_seconds(ttl, total, number, seconds_per_unit)?;
+    }
+
+    Ok(total)
+}
+
+/// ISO-8601 durations: P[nW][nD][T[nH][nM][nS]], with fractional seconds allowed
+fn parse_iso8601(ttl: &str, body: &str) -> Result<Duration, KvError> {
+    let bad = || invalid(format!("invalid ISO-8601 duration: {}", ttl));
+    let (date, time) = match body.split_once(['T', 't']) {
+        Some((_, "")) => return Err(bad()),
+        Some((date, time)) => (date, Some(time)),
+        None => (body, None),
+    };
+    if date.is_empty() && time.is_none() {
+        return Err(bad());
+    }
+
+    let mut total = Duration::zero();
+    for (part, is_time) in [(date, false), (time.unwrap_or_default(), true)] {
+        let mut rest = part;
+        while !rest.is_empty() {
+            let end = rest.find(|c: char| !(c.is_ascii_digit() || c == '.')).ok_or_else(bad)?;
+            let (number, designator) = (&rest[..end], rest[end..].chars().next().ok_or_else(bad)?);
+            rest = &rest[end + designator.len_utf8()..];
+
+            let seconds_per_unit = match (designator.to_ascii_uppercase(), is_time) {
+                ('W', false) => 7 * 24 * 60 * 60,
+                ('D', false) => 24 * 60 * 60,
+                ('H', true) => 60 * 60,
+                ('M', true) => 60,
+                ('S', true) => {
+                    let seconds: f64 = number.parse().map_err(|_| bad())?;
+                    let millis = Duration::try_milliseconds((seconds * 1000.0).round() as i64).ok_or_else(bad)?;
+                    total = total.checked_add(&millis).ok_or_else(|| invalid(format!("TTL too large: {}", ttl)))?;
+                    continue;
+                }
+                ('Y', false) | ('M', false) => {
+                    return Err(invalid(format!(
+                        "years and months have no fixed length: {} (use weeks or days)",
+                        ttl
+                    )))
+                }
+                _ => return Err(bad()),
+            };
+            let number: i64 = number.parse().map_err(|_| bad())?;
+            total = add_seconds(ttl, total, number, seconds_per_unit)?;
+        }
+    }
+
+    Ok(total)
+}
+
+fn add_seconds(ttl: &str, total: Duration, number: i64, seconds_per_unit: i64) -> Result<Duration, KvError> {
+    number
+        .checked_mul(seconds_per_unit)
+        .and_then(Duration::try_seconds)
+        .and_then(|d| total.checked_add(&d))
+        .ok_or_else(|| invalid(format!("TTL too large: {}", ttl)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        let cases = [
+            ("30s", 30),
+            ("5m", 300),
+            (" 1h ", 3600),
+            ("7d", 7 * 86400),
+            ("2w", 14 * 86400),
+            ("1h30m", 5400),
+            ("1w2d3h4m5s", 7 * 86400 + 2 * 86400 + 3 * 3600 + 4 * 60 + 5),
+            ("PT1H30M", 5400),
+            ("P1DT12H", 36 * 3600),
+            ("P2W", 14 * 86400),
+            ("pt45s", 45),
+            ("PT0S", 0),
+        ];
+        for (input, seconds) in cases {
+            assert_eq!(parse_duration(input).unwrap(), Duration::seconds(seconds), "{}", input);
+        }
+        assert_eq!(parse_duration("PT1.5S").unwrap(), Duration::milliseconds(1500));
+        for input in ["30s", "1h30m", "1w2d3h4m5s", "6d"] {
+            assert_eq!(format_duration(parse_duration(input).unwrap()), input);
+        }
+
+        for input in [
+            "", "30", "h", "1x", "1.5h", "-5m", "1h 30m", "P", "PT", "P1Y", "P1M", "PT1D", "P1H", "P1DT", "5秒", "1時",
+            "99999999999999w",
+        ] {
+            assert!(matches!(parse_duration(input), Err(KvError::InvalidTtl(_))), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_expires_at() {
+        let expected = DateTime::parse_from_rfc3339("2026-12-01T00:00:00Z").unwrap();
+        assert_eq!(parse_expires_at("2026-12-01T00:00:00Z").unwrap(), expected);
+        assert_eq!(parse_expires_at("2026-12-01T02:00:00+02:00").unwrap(), expected);
+        assert_eq!(parse_expires_at("2026-12-01").unwrap(), expected);
+        assert!(parse_expires_at("tomorrow").is_err());
+    }
+}